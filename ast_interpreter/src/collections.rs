@@ -93,6 +93,36 @@ impl Collection {
         }
     }
 
+    /// Gets the value at an index, or None if the index or key is missing
+    ///
+    /// Negative indices count from the end, as when indexing normally.
+    pub fn safe_get(&self, index: IndexValue) -> RunRes<Option<Value>> {
+        let index = match (self, index) {
+            (Collection::Dict(dict), IndexValue::At(key)) if !dict.contains_key(&key)? => {
+                return Ok(None)
+            }
+            (Collection::List(list), IndexValue::At(Value::Numerical(Numerical::Int(at))))
+                if !in_bounds(at, list.len()) =>
+            {
+                return Ok(None)
+            }
+            (Collection::String(string), IndexValue::At(Value::Numerical(num))) => {
+                let at = num.to_rint();
+                let len = string.as_ref().chars().count();
+                if !in_bounds(at, len) {
+                    return Ok(None);
+                }
+
+                // Strings do not wrap negative indices themselves
+                let wrapped = if at < 0 { at + len as i64 } else { at };
+                IndexValue::At(wrapped.into())
+            }
+            (_, index) => index,
+        };
+
+        self.get(index).map(Some)
+    }
+
     pub fn to_iter(&self) -> vec::IntoIter<Value> {
         match self {
             Collection::List(list) => list.to_iter(),
@@ -243,6 +273,11 @@ fn slice_len(start: usize, stop: usize, step: i64) -> RunRes<usize> {
     }
 }
 
+/// If an index is within a collection of the given length, counting negative indices from the end
+fn in_bounds(index: i64, len: usize) -> bool {
+    -(len as i64) <= index && index < len as i64
+}
+
 impl From<List> for Collection {
     fn from(value: List) -> Self {
         Collection::List(value)
@@ -165,6 +165,12 @@ fn value_hash<H: std::hash::Hasher>(value: &Value, state: &mut H) {
             }
         }
         Value::Callable(_) => panic!("Cannot hash a function"),
+        Value::Variant(variant) => {
+            variant.name().hash(state);
+            for field in variant.fields() {
+                value_hash(field, state);
+            }
+        }
         Value::Nil => 2.hash(state),
         Value::Uninitialized => panic!("use of uninitialized in hash"),
     }
@@ -176,6 +182,7 @@ fn valid_key(value: &Value) -> bool {
         Value::Collection(Collection::String(_)) => true,
         Value::Collection(coll) => coll.to_iter().all(|inner| valid_key(&inner)),
         Value::Callable(_) => false,
+        Value::Variant(variant) => variant.fields().iter().all(valid_key),
         Value::Nil => true,
         Value::Uninitialized => false,
     }
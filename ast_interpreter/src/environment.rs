@@ -41,6 +41,15 @@ impl Environment {
         }
     }
 
+    /// All names declared in this environment and the ones it is nested in
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.borrow().keys().cloned().collect();
+        if let Some(next) = &self.next {
+            names.extend(next.names());
+        }
+        names
+    }
+
     pub fn assign(&self, name: &str, value: Value) -> RunRes<Value> {
         if let Some(current) = self.values.borrow_mut().get_mut(name) {
             *current = value.clone();
@@ -1,3 +1,5 @@
+use parser::CodeRange;
+
 pub struct ErrorReporter {
     pub had_error: bool,
 }
@@ -17,6 +19,11 @@ impl ErrorReporter {
         eprintln!("{message}");
     }
 
+    pub fn static_error(&mut self, range: &CodeRange, message: &str) {
+        self.had_error = true;
+        eprintln!("ERROR [{range}] {message}");
+    }
+
     pub fn runtime_panic(&mut self, message: &str) {
         self.had_error = true;
         eprintln!("Panic! {message}")
@@ -1,25 +1,28 @@
 use std::{cmp::Ordering, rc::Rc};
 
 use parser::{
-    BinOper, CodeLoc, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, Stmts, UnOper,
+    BinOper, CodeLoc, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, Stmts, Type, UnOper,
 };
 
 use super::{
-    collections::{eval_index, eval_slice, SliceValue},
+    collections::{eval_index, eval_slice, Collection, Dict, IndexValue, SliceValue},
     environment::Environment,
     functions::{Closure, Function},
     numerical::Numerical,
     runtime_error::{RunError, RunRes, RunResTrait},
     statements,
     value::Value,
+    variant::{Variant, VariantTag},
 };
 
 pub fn eval(expr: &ExprNode, env: &Rc<Environment>) -> RunRes<Value> {
     match expr.node.as_ref() {
         Expr::Binary(left, op, right) => eval_binary(eval(left, env)?, op, eval(right, env)?),
+        Expr::ChainedComparison(first, links) => eval_chained_comparison(first, links, env),
         Expr::Logical(left, op, right) => eval_logical(eval(left, env)?, op, right, env),
         Expr::Unary(op, right) => eval_unary(op, eval(right, env)?),
         Expr::Assign(lvalue, expr) => eval_assign(lvalue, eval(expr, env)?, env),
+        Expr::OpAssign(lvalue, op, expr) => eval_op_assign(lvalue, op, expr, env),
         Expr::Var(id) => env.get(id),
         Expr::Int(int) => Ok(Value::Numerical(Numerical::Int(*int))),
         Expr::Float(float) => Ok(Value::Numerical(Numerical::Float(*float))),
@@ -27,10 +30,33 @@ pub fn eval(expr: &ExprNode, env: &Rc<Environment>) -> RunRes<Value> {
         Expr::String(string) => Ok(string.clone().into()),
         Expr::Block(stmts) => eval_block(stmts, env),
         Expr::If(cond, then, other) => eval_if(eval(cond, env)?, then, other.as_ref(), env),
-        Expr::While(cond, repeat) => eval_while(cond, repeat, env),
-        Expr::For(lvalue, iterable, body) => eval_for(lvalue, eval(iterable, env)?, body, env),
-        Expr::Break => Err(RunError::Break),
-        Expr::Continue => Err(RunError::Continue),
+        Expr::IfLet(lvalue, matched, then, other) => {
+            eval_if_let(lvalue, eval(matched, env)?, then, other.as_ref(), env)
+        }
+        Expr::While(cond, repeat, otherwise, label) => {
+            eval_while(cond, repeat, otherwise.as_ref(), label.as_ref(), env)
+        }
+        Expr::WhileLet(lvalue, matched, repeat, otherwise, label) => eval_while_let(
+            lvalue,
+            matched,
+            repeat,
+            otherwise.as_ref(),
+            label.as_ref(),
+            env,
+        ),
+        Expr::For(lvalue, iterable, body, otherwise, label) => eval_for(
+            lvalue,
+            eval(iterable, env)?,
+            body,
+            otherwise.as_ref(),
+            label.as_ref(),
+            env,
+        ),
+        Expr::Break(label, value) => Err(RunError::Break(
+            label.clone(),
+            eval_opt(value.as_ref(), env)?,
+        )),
+        Expr::Continue(label) => Err(RunError::Continue(label.clone())),
         Expr::Call(callee, args) => eval_call(
             eval(callee, env)?,
             args.iter()
@@ -43,18 +69,41 @@ pub fn eval(expr: &ExprNode, env: &Rc<Environment>) -> RunRes<Value> {
         Expr::Return(None) => Err(RunError::Return(Value::Nil)),
         Expr::Nil => Ok(Value::Nil),
         Expr::List(content) => eval_list(content, env),
+        Expr::Dict(pairs) => eval_dict(pairs, env),
+        Expr::Set(values) => eval_set(values, env),
         Expr::Tuple(_exprs) => {
             RunError::error("Tuples are not part of the language (yet)".to_string())
         }
-        Expr::FunctionDefinition(name, param, body) => eval_func_definition(name, param, body, env),
+        Expr::FunctionDefinition(name, param, body, returns) => {
+            eval_func_definition(name, param, body, *returns, env)
+        }
         Expr::IndexInto(base, index) => eval_index_expr(base, index, env),
+        Expr::SafeIndexInto(base, index) => eval_safe_index_expr(base, index, env),
         Expr::Match(matched, arms) => eval_match(eval(matched, env)?, arms, env),
+        Expr::Rest(_) => {
+            RunError::error("A rest pattern can only be used within a tuple pattern".to_string())
+        }
+        Expr::EnumVariant(enum_name, name, fields) => {
+            let tag = Rc::new(VariantTag::new(
+                enum_name.clone(),
+                name.clone(),
+                fields.len(),
+            ));
+            // Variants without fields are values, not constructors
+            if fields.is_empty() {
+                Ok(tag.construct(vec![]))
+            } else {
+                Ok(Value::Callable(Function::Constructor(tag)))
+            }
+        }
+        Expr::Invalid => panic!("Tried to interpret an invalid expression!"),
     }
     .add_loc(expr.start_loc, expr.end_loc)
 }
 
 fn eval_match(base: Value, arms: &Vec<(LValue, ExprNode)>, env: &Rc<Environment>) -> RunRes<Value> {
     for (lvalue, expr) in arms {
+        check_variant_patterns(lvalue, env)?;
         let inner_env = Environment::nest(env);
         declare(lvalue, &inner_env)?;
         match assign(lvalue, base.clone(), &inner_env) {
@@ -65,6 +114,41 @@ fn eval_match(base: Value, arms: &Vec<(LValue, ExprNode)>, env: &Rc<Environment>
     RunError::error("None of the arms could be matched".to_string())
 }
 
+/// Checks that all variants in a pattern exist with the right number of fields
+///
+/// Failed matches are otherwise ignored, which would hide misspelled variants.
+fn check_variant_patterns(lvalue: &LValue, env: &Rc<Environment>) -> RunRes<()> {
+    match lvalue {
+        LValue::Variant(name, fields) => {
+            Variant::fields_of(&Value::Nil, &env.get(name)?, fields.len())?;
+            for field in fields {
+                check_variant_patterns(field, env)?;
+            }
+            Ok(())
+        }
+        LValue::Tuple(lvalues) => {
+            for lvalue in lvalues {
+                check_variant_patterns(lvalue, env)?;
+            }
+            Ok(())
+        }
+        LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => check_variant_patterns(lvalue, env),
+        LValue::Index(_, _) | LValue::Var(_) | LValue::Constant(_) | LValue::Wildcard => Ok(()),
+    }
+}
+
+/// Raises an error unless the value has the annotated type
+pub fn check_type(value: &Value, annotated: Type) -> RunRes<()> {
+    if value.has_type(annotated) {
+        Ok(())
+    } else {
+        RunError::error(format!(
+            "Expected a value of type {annotated}, but got {}",
+            value.type_of()
+        ))
+    }
+}
+
 fn eval_index_expr(base: &ExprNode, index_expr: &Index, env: &Rc<Environment>) -> RunRes<Value> {
     let index = eval_index(index_expr, env)?;
     let into_value = eval(base, env)?;
@@ -74,13 +158,28 @@ fn eval_index_expr(base: &ExprNode, index_expr: &Index, env: &Rc<Environment>) -
     }
 }
 
+fn eval_safe_index_expr(
+    base: &ExprNode,
+    index_expr: &Index,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    match eval(base, env)? {
+        Value::Nil => Ok(Value::Nil),
+        Value::Collection(collection) => Ok(collection
+            .safe_get(eval_index(index_expr, env)?)?
+            .unwrap_or(Value::Nil)),
+        other => RunError::error(format!("Cannot index into a {}", other.type_of())),
+    }
+}
+
 fn eval_func_definition(
     id: &str,
     param: &[LValue],
     body: &ExprNode,
+    returns: Option<Type>,
     env: &Rc<Environment>,
 ) -> RunRes<Value> {
-    let closure = Closure::new(id.to_string(), param.to_vec(), body.clone(), env);
+    let closure = Closure::new(id.to_string(), param.to_vec(), body.clone(), returns, env);
     Ok(Value::Callable(Function::Closure(closure)))
 }
 
@@ -113,6 +212,22 @@ fn eval_list(content: &ListContent, env: &Rc<Environment>) -> RunRes<Value> {
     }
 }
 
+fn eval_dict(pairs: &[(ExprNode, ExprNode)], env: &Rc<Environment>) -> RunRes<Value> {
+    let dict = Dict::new();
+    for (key, value) in pairs {
+        dict.assign_into(eval(key, env)?, eval(value, env)?)?;
+    }
+    Ok(dict.into())
+}
+
+fn eval_set(values: &[ExprNode], env: &Rc<Environment>) -> RunRes<Value> {
+    let set = Dict::new();
+    for value in values {
+        set.assign_into(eval(value, env)?, Value::Nil)?;
+    }
+    Ok(set.into())
+}
+
 fn eval_call(callee: Value, args: Vec<Value>, start: CodeLoc, end: CodeLoc) -> RunRes<Value> {
     if let Value::Callable(callable) = callee {
         callable
@@ -134,22 +249,56 @@ fn def_block_return() -> Value {
     Value::Nil
 }
 
-fn eval_while(cond: &ExprNode, repeat: &ExprNode, env: &Rc<Environment>) -> RunRes<Value> {
-    while eval(cond, env)?.truthy() {
+fn eval_while(
+    cond: &ExprNode,
+    repeat: &ExprNode,
+    otherwise: Option<&ExprNode>,
+    label: Option<&String>,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    while eval(cond, env)?.truthy()? {
         match eval(repeat, env) {
-            Err(RunError::Break) => break,
-            Err(RunError::Continue) => continue,
+            Err(RunError::Break(target, value)) if targets(&target, label) => return Ok(value),
+            Err(RunError::Continue(target)) if targets(&target, label) => continue,
             otherwise => otherwise?,
         };
     }
 
-    Ok(def_block_return())
+    eval_opt(otherwise, env)
+}
+
+fn eval_while_let(
+    lvalue: &LValue,
+    matched: &ExprNode,
+    repeat: &ExprNode,
+    otherwise: Option<&ExprNode>,
+    label: Option<&String>,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    check_variant_patterns(lvalue, env)?;
+    loop {
+        let inner_env = Environment::nest(env);
+        declare(lvalue, &inner_env)?;
+        if assign(lvalue, eval(matched, env)?, &inner_env).is_err() {
+            break;
+        }
+
+        match eval(repeat, &inner_env) {
+            Err(RunError::Break(target, value)) if targets(&target, label) => return Ok(value),
+            Err(RunError::Continue(target)) if targets(&target, label) => continue,
+            otherwise => otherwise?,
+        };
+    }
+
+    eval_opt(otherwise, env)
 }
 
 fn eval_for(
     lvalue: &LValue,
     iter: Value,
     body: &ExprNode,
+    otherwise: Option<&ExprNode>,
+    label: Option<&String>,
     outer_env: &Rc<Environment>,
 ) -> RunRes<Value> {
     let env = Environment::nest(outer_env);
@@ -157,13 +306,26 @@ fn eval_for(
         declare(lvalue, &env)?;
         assign(lvalue, value, &env)?;
         match eval(body, &env) {
-            Err(RunError::Break) => break,
-            Err(RunError::Continue) => continue,
+            Err(RunError::Break(target, value)) if targets(&target, label) => return Ok(value),
+            Err(RunError::Continue(target)) if targets(&target, label) => continue,
             other => other,
         }?;
     }
 
-    Ok(def_block_return())
+    eval_opt(otherwise, outer_env)
+}
+
+/// Checks if a break or continue with the target label refers to a loop with the label
+fn targets(target: &Option<String>, label: Option<&String>) -> bool {
+    target.is_none() || target.as_ref() == label
+}
+
+/// Evaluates an optional expression, defaulting to nil
+fn eval_opt(expr: Option<&ExprNode>, env: &Rc<Environment>) -> RunRes<Value> {
+    match expr {
+        Some(expr) => eval(expr, env),
+        None => Ok(def_block_return()),
+    }
 }
 
 fn eval_if(
@@ -172,7 +334,7 @@ fn eval_if(
     otherwise: Option<&ExprNode>,
     env: &Rc<Environment>,
 ) -> RunRes<Value> {
-    if cond.truthy() {
+    if cond.truthy()? {
         eval(then, env)
     } else if let Some(expr) = otherwise {
         eval(expr, env)
@@ -181,6 +343,24 @@ fn eval_if(
     }
 }
 
+/// Evaluates the then branch with the pattern bound if the value matches it
+fn eval_if_let(
+    lvalue: &LValue,
+    matched: Value,
+    then: &ExprNode,
+    otherwise: Option<&ExprNode>,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    check_variant_patterns(lvalue, env)?;
+    let inner_env = Environment::nest(env);
+    declare(lvalue, &inner_env)?;
+    if assign(lvalue, matched, &inner_env).is_ok() {
+        eval(then, &inner_env)
+    } else {
+        eval_opt(otherwise, env)
+    }
+}
+
 fn eval_block(stmts: &Stmts, env: &Rc<Environment>) -> RunRes<Value> {
     let nested_env = Environment::nest(env);
     match statements::eval_statements(stmts, &nested_env)? {
@@ -193,7 +373,45 @@ fn eval_assign(lvalue: &LValue, rvalue: Value, env: &Rc<Environment>) -> RunRes<
     assign(lvalue, rvalue, env)
 }
 
-fn eval_binary(left: Value, op: &BinOper, right: Value) -> RunRes<Value> {
+/// Evaluates a compound assignment, only evaluating the parts of the lvalue once
+fn eval_op_assign(
+    lvalue: &LValue,
+    op: &BinOper,
+    expr: &ExprNode,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    match lvalue {
+        LValue::Var(id) => {
+            let value = eval_binary(env.get(id)?, op, eval(expr, env)?)?;
+            env.assign(id, value)
+        }
+        LValue::Index(callee_expr, index_expr) => {
+            let index = eval_index(index_expr, env)?;
+            let base = eval(callee_expr, env)?;
+            match base {
+                Value::Collection(collection) => {
+                    let old = collection.get(index.clone())?;
+                    let value = eval_binary(old, op, eval(expr, env)?)?;
+                    collection.assign_into(value, index)
+                }
+                other => error(format!(
+                    "Cannot index into {} for assignment",
+                    other.type_of()
+                )),
+            }
+        }
+        LValue::Tuple(_)
+        | LValue::Constant(_)
+        | LValue::Wildcard
+        | LValue::Rest(_)
+        | LValue::Variant(_, _)
+        | LValue::Typed(_, _) => {
+            error("Compound assignment requires a variable or an indexing".to_string())
+        }
+    }
+}
+
+pub fn eval_binary(left: Value, op: &BinOper, right: Value) -> RunRes<Value> {
     match op {
         BinOper::Append => bin_append(left, right),
         BinOper::Add => bin_add(left, right),
@@ -208,8 +426,36 @@ fn eval_binary(left: Value, op: &BinOper, right: Value) -> RunRes<Value> {
         BinOper::Leq => bin_leq(left, right),
         BinOper::Gt => bin_gt(left, right),
         BinOper::Geq => bin_geq(left, right),
+        BinOper::BitAnd
+        | BinOper::BitOr
+        | BinOper::BitXor
+        | BinOper::LShift
+        | BinOper::RShift => bin_bitwise(left, op, right),
+        BinOper::In => bin_in(left, right),
+        BinOper::NotIn => Ok((!bin_in(left, right)?.truthy()?).into()),
     }
 }
+
+fn bin_in(left: Value, right: Value) -> RunRes<Value> {
+    let contained = match (&left, &right) {
+        (value, Value::Collection(Collection::List(list))) => {
+            list.to_iter().any(|item| &item == value)
+        }
+        (value, Value::Collection(Collection::Dict(dict))) => dict.contains_key(value)?,
+        (
+            Value::Collection(Collection::String(substring)),
+            Value::Collection(Collection::String(string)),
+        ) => string.contains(substring.as_str()),
+        _ => {
+            return error(format!(
+                "Cannot check if {} is in {}",
+                left.type_of(),
+                right.type_of()
+            ))
+        }
+    };
+    Ok(contained.into())
+}
 fn bin_append(left: Value, right: Value) -> RunRes<Value> {
     match left {
         Value::Collection(x) => x.concat(right),
@@ -279,6 +525,32 @@ fn bin_pow(left: Value, right: Value) -> RunRes<Value> {
     }
 }
 
+fn bin_bitwise(left: Value, op: &BinOper, right: Value) -> RunRes<Value> {
+    let (Value::Numerical(x), Value::Numerical(y)) = (&left, &right) else {
+        return error(format!(
+            "Bitwise operations only work on integers, not {} and {}",
+            left.type_of(),
+            right.type_of()
+        ));
+    };
+
+    let (x, y) = (x.to_bits()?, y.to_bits()?);
+    let shift = || u32::try_from(y).or_else(|_| error(format!("Cannot shift by {y} bits")));
+    let res = match op {
+        BinOper::BitAnd => Some(x & y),
+        BinOper::BitOr => Some(x | y),
+        BinOper::BitXor => Some(x ^ y),
+        BinOper::LShift => x.checked_shl(shift()?),
+        BinOper::RShift => x.checked_shr(shift()?),
+        _ => panic!("Internal error: {op:?} is not a bitwise operation"),
+    };
+
+    match res {
+        Some(int) => Ok(int.into()),
+        None => error(format!("Cannot shift by {y} bits")),
+    }
+}
+
 fn bin_eq(left: Value, right: Value) -> RunRes<Value> {
     Ok(Value::Numerical(Numerical::Bool(left == right)))
 }
@@ -341,7 +613,11 @@ fn eval_unary(op: &UnOper, right: Value) -> RunRes<Value> {
             Value::Numerical(num) => Ok(num.un_sub()?.into()),
             _other => error("Unary subtraction only works for a number".to_string()),
         },
-        UnOper::Not => Ok(Value::Numerical(Numerical::Bool(!right.truthy()))),
+        UnOper::Not => Ok(Value::Numerical(Numerical::Bool(!right.truthy()?))),
+        UnOper::BitNot => match right {
+            Value::Numerical(num) => Ok((!num.to_bits()?).into()),
+            _other => error("Bitwise negation only works for an integer".to_string()),
+        },
     }
 }
 
@@ -360,6 +636,14 @@ pub fn declare(lvalue: &LValue, env: &Rc<Environment>) -> RunRes<()> {
             }
             Ok(())
         }
+        LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => declare(lvalue, env),
+        LValue::Variant(_, fields) => {
+            for field in fields {
+                declare(field, env)?;
+            }
+            Ok(())
+        }
+        LValue::Wildcard => Ok(()),
         LValue::Constant(_expr) => Ok(()), // TODO: This causes some strange allowed decl
     }
 }
@@ -379,6 +663,13 @@ pub fn assign(lvalue: &LValue, rvalue: Value, env: &Rc<Environment>) -> RunRes<V
                 )),
             }
         }
+        LValue::Tuple(lvalues)
+            if let Some(rest) = lvalues
+                .iter()
+                .position(|lvalue| matches!(lvalue, LValue::Rest(_))) =>
+        {
+            assign_with_rest(lvalues, rest, rvalue, env)
+        }
         LValue::Tuple(lvalues) => {
             let mut lvalues_iter = lvalues.into_iter();
             let mut rvalues_iter = rvalue.clone().to_iter()?;
@@ -403,6 +694,29 @@ pub fn assign(lvalue: &LValue, rvalue: Value, env: &Rc<Environment>) -> RunRes<V
                 }
             }
         }
+        LValue::Wildcard => Ok(rvalue),
+        LValue::Variant(name, fields) => {
+            let constructor = env.get(name)?;
+            match Variant::fields_of(&rvalue, &constructor, fields.len())? {
+                Some(values) => {
+                    for (field, value) in fields.iter().zip(values) {
+                        assign(field, value, env)?;
+                    }
+                    Ok(rvalue)
+                }
+                None => RunError::error(format!(
+                    "Assignment to variant failed (not a {name})\nRight: {}",
+                    rvalue
+                )),
+            }
+        }
+        LValue::Rest(_) => {
+            RunError::error("A rest pattern can only be used within a tuple pattern".to_string())
+        }
+        LValue::Typed(lvalue, annotated) => {
+            check_type(&rvalue, *annotated)?;
+            assign(lvalue, rvalue, env)
+        }
         LValue::Constant(expr) => {
             let lvalue = eval(expr, env)?;
             if lvalue == rvalue {
@@ -417,6 +731,61 @@ pub fn assign(lvalue: &LValue, rvalue: Value, env: &Rc<Environment>) -> RunRes<V
     }
 }
 
+/// Assigns into a tuple with a rest pattern at the given position
+///
+/// The rest pattern is assigned a list of all values not taken by the other lvalues.
+fn assign_with_rest(
+    lvalues: &[LValue],
+    rest: usize,
+    rvalue: Value,
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    let values: Vec<Value> = rvalue.to_iter()?.collect();
+    if values.len() < lvalues.len() - 1 {
+        return RunError::error(format!(
+            "{} lvalues remain after all rvalues",
+            lvalues.len() - 1 - values.len()
+        ));
+    }
+
+    let after = lvalues.len() - rest - 1;
+    let rest_end = values.len() - after;
+    for (lvalue, value) in lvalues[..rest].iter().zip(&values[..rest]) {
+        assign(lvalue, value.clone(), env)?;
+    }
+    if let (LValue::Rest(lvalue), Value::Collection(collection)) = (&lvalues[rest], &rvalue) {
+        // The rest is sliced out as in the VM, so that the rest of a string is a string
+        let slice = SliceValue {
+            start: Some(Numerical::Int(rest as i64)),
+            stop: (after > 0).then_some(Numerical::Int(-(after as i64))),
+            step: None,
+        };
+        assign(lvalue, collection.get(IndexValue::Slice(slice))?, env)?;
+    }
+    for (lvalue, value) in lvalues[rest + 1..].iter().zip(&values[rest_end..]) {
+        assign(lvalue, value.clone(), env)?;
+    }
+
+    Ok(rvalue)
+}
+
+/// Evaluates comparisons such as `a < b <= c`, stopping at the first false one
+fn eval_chained_comparison(
+    first: &ExprNode,
+    links: &[(BinOper, ExprNode)],
+    env: &Rc<Environment>,
+) -> RunRes<Value> {
+    let mut left = eval(first, env)?;
+    for (op, operand) in links {
+        let right = eval(operand, env)?;
+        if !eval_binary(left, op, right.clone())?.truthy()? {
+            return Ok(Value::Numerical(Numerical::Bool(false)));
+        }
+        left = right;
+    }
+    Ok(Value::Numerical(Numerical::Bool(true)))
+}
+
 fn eval_logical(
     left: Value,
     op: &LogicalOper,
@@ -424,8 +793,14 @@ fn eval_logical(
     env: &Rc<Environment>,
 ) -> RunRes<Value> {
     let res = match op {
-        LogicalOper::And => left.truthy() && eval(right, env)?.truthy(),
-        LogicalOper::Or => left.truthy() || eval(right, env)?.truthy(),
+        LogicalOper::And => left.truthy()? && eval(right, env)?.truthy()?,
+        LogicalOper::Or => left.truthy()? || eval(right, env)?.truthy()?,
+        LogicalOper::Coalesce => {
+            return match left {
+                Value::Nil => eval(right, env),
+                left => Ok(left),
+            }
+        }
     };
     Ok(Value::Numerical(Numerical::Bool(res)))
 }
@@ -4,9 +4,12 @@ use std::{
     rc::Rc,
 };
 
-use parser::{ExprNode, LValue};
+use parser::{ExprNode, LValue, Type};
 
-use super::{environment::Environment, expressions, runtime_error::RunError, RunRes, Value};
+use super::{
+    environment::Environment, expressions, runtime_error::RunError, variant::VariantTag, RunRes,
+    Value,
+};
 
 use builtins::Builtin;
 
@@ -16,13 +19,14 @@ mod builtins;
 pub enum Function {
     Closure(Closure),
     Builtin(Rc<dyn Builtin>),
+    Constructor(Rc<VariantTag>),
 }
 
 impl Function {
     pub fn call(&self, args: Vec<Value>) -> RunRes<Value> {
         if self.accept_arity(args.len()) {
             match self.delegate_call(args) {
-                Err(RunError::Break) => {
+                Err(RunError::Break(_, _)) => {
                     RunError::error("Break encountered outside loop".to_string())
                 }
                 Err(RunError::Return(value)) => Ok(value),
@@ -41,6 +45,7 @@ impl Function {
         match self {
             Function::Closure(closure) => closure.call(args),
             Function::Builtin(builtin) => builtin.run(args),
+            Function::Constructor(tag) => Ok(tag.construct(args)),
         }
     }
 
@@ -48,6 +53,7 @@ impl Function {
         match self {
             Function::Closure(closure) => arity == closure.arity(),
             Function::Builtin(builtin) => builtin.accept_arity(arity),
+            Function::Constructor(tag) => arity == tag.arity(),
         }
     }
 
@@ -55,6 +61,7 @@ impl Function {
         match self {
             Function::Closure(closure) => closure.name(),
             Function::Builtin(builtin) => builtin.name(),
+            Function::Constructor(tag) => tag.name(),
         }
     }
 
@@ -64,6 +71,7 @@ impl Function {
         match self {
             Function::Closure(closure) => format!("{}", closure.arity()),
             Function::Builtin(builtin) => builtin.arity().to_string(),
+            Function::Constructor(tag) => tag.arity().to_string(),
         }
     }
 }
@@ -77,6 +85,9 @@ impl Debug for Function {
             Function::Builtin(builtin) => {
                 f.write_str(&format!("Builtin({}/{})", builtin.name(), builtin.arity()))
             }
+            Function::Constructor(tag) => {
+                f.write_str(&format!("Constructor({}/{})", tag.name(), tag.arity()))
+            }
         }
     }
 }
@@ -86,6 +97,7 @@ impl PartialEq for Function {
         match (self, other) {
             // (Function::Closure(_, _, _), Function::Closure(_, _, _)) => true, // Uncomment this line when you add Closure variant back
             (Function::Builtin(a), Function::Builtin(b)) => a.name() == b.name(),
+            (Function::Constructor(a), Function::Constructor(b)) => a == b,
             _ => false,
         }
     }
@@ -104,15 +116,23 @@ pub struct Closure {
     id: String, // Maybe not ideal to have
     params: Vec<LValue>,
     body: ExprNode, // Should we have this as a borrow instead maybe? Probably just a hassle
+    returns: Option<Type>,
     env: Rc<Environment>,
 }
 
 impl Closure {
-    pub fn new(id: String, params: Vec<LValue>, body: ExprNode, env: &Rc<Environment>) -> Self {
+    pub fn new(
+        id: String,
+        params: Vec<LValue>,
+        body: ExprNode,
+        returns: Option<Type>,
+        env: &Rc<Environment>,
+    ) -> Self {
         Self {
             id,
             params,
             body,
+            returns,
             env: env.clone(),
         }
     }
@@ -123,7 +143,17 @@ impl Closure {
             expressions::declare(param, &env)?;
             expressions::assign(param, arg, &env)?;
         }
-        expressions::eval(&self.body, &env)
+        let Some(returns) = self.returns else {
+            return expressions::eval(&self.body, &env);
+        };
+
+        // Explicit returns must also be checked against the annotated return type
+        let value = match expressions::eval(&self.body, &env) {
+            Err(RunError::Return(value)) => value,
+            otherwise => otherwise?,
+        };
+        expressions::check_type(&value, returns)?;
+        Ok(value)
     }
 
     fn arity(&self) -> usize {
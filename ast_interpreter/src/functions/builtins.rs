@@ -1,6 +1,7 @@
 use itertools::Itertools;
 use std::{cmp::Ordering, fs::read_to_string, rc::Rc};
 
+use super::Function;
 use crate::{
     collections::{slice_iter, Collection, Dict, SliceValue},
     environment::Environment,
@@ -31,7 +32,8 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         MaxBuiltin,
         MinBuiltin,
         JoinBuiltin,
-        SortBuiltin
+        SortBuiltin,
+        PartialBuiltin
     ];
 
     builtins.new_0arg("time", || {
@@ -93,7 +95,7 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         val => RunError::error(format!("Cannot convert {} to an float", val.type_of())),
     });
 
-    builtins.new_1arg("bool", |arg| Ok(arg.truthy().into()));
+    builtins.new_1arg("bool", |arg| Ok(arg.truthy()?.into()));
 
     // TODO: Should work for any iterator
     builtins.new_1arg("sum", |arg| match arg {
@@ -222,6 +224,20 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         )),
     });
 
+    // Creates a function calling the second function on the result of the first one
+    builtins.new_2arg("compose", |first, second| {
+        let first = first.cast_func("Expect functions as arguments to compose.")?;
+        let second = second.cast_func("Expect functions as arguments to compose.")?;
+        let name = format!("compose({}, {})", first.name(), second.name());
+        let arity = first.arity();
+        Ok(Value::Callable(Function::Builtin(Rc::new(Composed {
+            first,
+            second,
+            name,
+            arity,
+        }))))
+    });
+
     // Maps the function over the iterable, then converting it back into a list
     builtins.new_2arg("map", |base, func| match (base, func) {
         (Value::Collection(coll), Value::Callable(func)) => Ok(coll
@@ -241,7 +257,7 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         (Value::Collection(coll), Value::Callable(func)) => {
             let mut filtered = vec![];
             for val in coll.to_iter() {
-                if func.call(vec![val.clone()])?.truthy() {
+                if func.call(vec![val.clone()])?.truthy()? {
                     filtered.push(val);
                 }
             }
@@ -515,6 +531,93 @@ impl Builtin for SortBuiltin {
     }
 }
 
+/// Binds the first arguments of a function, returning a function taking the rest of them
+struct PartialBuiltin;
+impl Builtin for PartialBuiltin {
+    fn run(&self, args: Vec<Value>) -> RunRes<Value> {
+        let mut arg_iter = args.into_iter();
+        let func = arg_iter
+            .next()
+            .unwrap()
+            .cast_func("Expect a function as first argument to partial.")?;
+        let bound: Vec<Value> = arg_iter.collect();
+        let name = format!("partial({}, {} args)", func.name(), bound.len());
+        let arity = format!("{} - {}", func.arity(), bound.len());
+        Ok(Value::Callable(Function::Builtin(Rc::new(Partial {
+            func,
+            bound,
+            name,
+            arity,
+        }))))
+    }
+
+    fn accept_arity(&self, arity: usize) -> bool {
+        arity > 0
+    }
+
+    fn name(&self) -> &str {
+        "partial"
+    }
+
+    fn arity(&self) -> &str {
+        "[>0]"
+    }
+}
+
+/// The function created by compose
+struct Composed {
+    first: Function,
+    second: Function,
+    name: String,
+    arity: String,
+}
+
+impl Builtin for Composed {
+    fn run(&self, args: Vec<Value>) -> RunRes<Value> {
+        self.second.call(vec![self.first.call(args)?])
+    }
+
+    fn accept_arity(&self, arity: usize) -> bool {
+        self.first.accept_arity(arity)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> &str {
+        &self.arity
+    }
+}
+
+/// The function created by partial
+struct Partial {
+    func: Function,
+    bound: Vec<Value>,
+    name: String,
+    arity: String,
+}
+
+impl Builtin for Partial {
+    fn run(&self, args: Vec<Value>) -> RunRes<Value> {
+        let mut all_args = self.bound.clone();
+        all_args.extend(args);
+        self.func.call(all_args)
+    }
+
+    fn accept_arity(&self, arity: usize) -> bool {
+        self.func.accept_arity(arity + self.bound.len())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> &str {
+        &self.arity
+    }
+}
+
 /// Trait for more easily adding builtins with a certain number of args
 trait Builtins {
     fn new_0arg(&mut self, name: &'static str, func: impl Fn() -> RunRes<Value> + 'static);
@@ -17,6 +17,7 @@ mod numerical;
 mod runtime_error;
 mod statements;
 mod value;
+mod variant;
 
 pub struct InterpreterState {
     env: Rc<Environment>,
@@ -49,13 +50,34 @@ pub fn interpret(program: &Stmts, env: &mut InterpreterState) {
         error_reporter,
     } = env;
 
+    // Names from earlier REPL lines are declared as well
+    let declared = env.names();
+    let declared: Vec<&str> = declared.iter().map(String::as_str).collect();
+    let undefined = semantic_analyzer::find_undefined_variables(program, &declared);
+    for variable in undefined.iter() {
+        error_reporter.static_error(&variable.range, &variable.to_string());
+    }
+    let type_errors = semantic_analyzer::find_type_errors(program);
+    for error in type_errors.iter() {
+        error_reporter.static_error(&error.range, &error.message);
+    }
+    let variant_errors = semantic_analyzer::find_variant_errors(program);
+    for error in variant_errors.iter() {
+        error_reporter.static_error(&error.range, &error.message);
+    }
+    if !undefined.is_empty() || !type_errors.is_empty() || !variant_errors.is_empty() {
+        return;
+    }
+
     match statements::eval_statements(program, &env) {
         Ok(Some(Value::Nil)) => (), // Might want to print this sometimes, but mostly I assume it is not intended
         Ok(Some(value)) => println!("{}", value.stringify()),
         Ok(None) => (),
         Err(RunError::Error(trace)) => error_reporter.runtime_error(&format!("{trace}")),
-        Err(RunError::Break) => error_reporter.runtime_panic("Break propagated to top-level scope"),
-        Err(RunError::Continue) => {
+        Err(RunError::Break(_, _)) => {
+            error_reporter.runtime_panic("Break propagated to top-level scope")
+        }
+        Err(RunError::Continue(_)) => {
             error_reporter.runtime_panic("Continue propagated to top-level scope")
         }
         // Just prints and terminates
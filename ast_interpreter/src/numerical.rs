@@ -132,6 +132,16 @@ impl Numerical {
         }
     }
 
+    /// Gets the integer used in bitwise operations, which are not defined for floats
+    pub fn to_bits(self) -> RunRes<i64> {
+        match self {
+            Numerical::Float(_) => {
+                RunError::error("Bitwise operations are not defined for floats".to_string())
+            }
+            other => Ok(other.to_rint()),
+        }
+    }
+
     pub fn abs(self) -> Numerical {
         match self {
             Numerical::Int(int) => Numerical::Int(int.abs()),
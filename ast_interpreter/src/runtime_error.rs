@@ -35,8 +35,9 @@ impl<T> RunResTrait for RunRes<T> {
 #[derive(Debug)]
 pub enum RunError {
     Error(Box<Trace>),
-    Break, // Maybe include code loc for error messages? Or just handle that with static analysis?
-    Continue,
+    // Maybe include code loc for error messages? Or just handle that with static analysis?
+    Break(Option<String>, Value), // The optional label of the loop to break out of
+    Continue(Option<String>),
     Return(Value),
 }
 
@@ -1,11 +1,11 @@
 use std::rc::Rc;
 
-use parser::{ExprNode, LValue, Stmt, StmtNode, Stmts};
+use parser::{BinOper, Expr, ExprNode, LValue, Stmt, StmtNode, Stmts};
 
 use super::{
     environment::Environment,
-    expressions::{self, assign, declare},
-    runtime_error::RunResTrait,
+    expressions::{self, assign, declare, eval_binary},
+    runtime_error::{RunError, RunResTrait},
     value::Value,
     RunRes,
 };
@@ -33,10 +33,14 @@ fn eval(stmt: &StmtNode, env: &Rc<Environment>) -> RunRes<Option<Value>> {
         start_loc,
         end_loc,
         box node,
+        id: _,
     } = stmt;
     match node {
         Stmt::Decl(id, expr) => decl(id, expr, env).map(|_| None),
         Stmt::Expr(expr) => expressions::eval(expr, env).map(Some),
+        Stmt::Assert(cond, message, source) => {
+            assert(cond, message.as_ref(), source, env).map(|_| None)
+        }
         Stmt::Invalid => panic!("Tried to interpret an invalid statement!"),
     }
     .add_loc(*start_loc, *end_loc) // OPT: How slow are these polymorphic wrappers?
@@ -54,6 +58,55 @@ fn decl(lvalue: &LValue, expr: &Option<ExprNode>, env: &Rc<Environment>) -> RunR
     Ok(())
 }
 
+fn assert(
+    cond: &ExprNode,
+    message: Option<&ExprNode>,
+    source: &str,
+    env: &Rc<Environment>,
+) -> RunRes<()> {
+    // Comparisons keep their operands, so they can be shown on failure
+    let (holds, operands) = match cond.node.as_ref() {
+        Expr::Binary(lhs, op, rhs)
+            if matches!(
+                op,
+                BinOper::Eq
+                    | BinOper::Neq
+                    | BinOper::Lt
+                    | BinOper::Leq
+                    | BinOper::Gt
+                    | BinOper::Geq
+                    | BinOper::In
+                    | BinOper::NotIn
+            ) =>
+        {
+            let lhs = expressions::eval(lhs, env)?;
+            let rhs = expressions::eval(rhs, env)?;
+            let holds = eval_binary(lhs.clone(), op, rhs.clone())?.truthy()?;
+            (holds, Some((lhs, rhs)))
+        }
+        _ => (expressions::eval(cond, env)?.truthy()?, None),
+    };
+
+    if holds {
+        return Ok(());
+    }
+
+    let mut reason = format!("Assertion failed: {source}");
+    if let Some(message) = message {
+        let message = expressions::eval(message, env)?;
+        reason.push_str(&format!("\n  message: {}", message.stringify()));
+    }
+    if let Some((lhs, rhs)) = operands {
+        reason.push_str(&format!(
+            "\n  left: {}\n  right: {}",
+            lhs.stringify(),
+            rhs.stringify()
+        ));
+    }
+
+    RunError::error(reason)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
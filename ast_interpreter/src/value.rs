@@ -1,10 +1,13 @@
 use std::{fmt, rc::Rc, vec};
 
+use parser::Type;
+
 use super::{
     collections::{Collection, Dict, List},
     functions::Function,
     numerical::Numerical,
     runtime_error::{RunError, RunRes},
+    variant::Variant,
 };
 
 // An interface between Zote and Rust values
@@ -13,18 +16,23 @@ pub enum Value {
     Numerical(Numerical),
     Collection(Collection),
     Callable(Function),
+    Variant(Rc<Variant>),
     Nil,
     Uninitialized,
 }
 
 impl Value {
-    pub fn truthy(&self) -> bool {
+    pub fn truthy(&self) -> RunRes<bool> {
         match self {
-            Value::Numerical(num) => num.truthy(),
-            Value::Collection(collection) => !collection.is_empty(),
+            Value::Numerical(num) => Ok(num.truthy()),
+            Value::Collection(collection) => Ok(!collection.is_empty()),
             Value::Callable(_) => panic!("Can't convert function to bool"), // TODO: real error, or just warning
-            Value::Nil => false,
-            Value::Uninitialized => false,
+            Value::Variant(variant) => RunError::error(format!(
+                "An enum variant does not have a truthiness ({})",
+                variant.name()
+            )),
+            Value::Nil => Ok(false),
+            Value::Uninitialized => Ok(false),
         }
     }
 
@@ -34,6 +42,7 @@ impl Value {
             Value::Numerical(num) => num.stringify(),
             Value::Collection(collection) => collection.stringify(),
             Value::Callable(callable) => callable.name().to_string(),
+            Value::Variant(variant) => variant.stringify(),
             Value::Nil => "Nil".to_string(),
             Value::Uninitialized => panic!("Use of uninit value!"),
         }
@@ -45,11 +54,27 @@ impl Value {
             Value::Numerical(num) => num.type_of(),
             Value::Collection(collection) => collection.type_of(),
             Value::Callable(_) => "Function",
+            Value::Variant(_) => "Variant",
             Value::Nil => "Nil",
             Value::Uninitialized => "Uninitialized",
         }
     }
 
+    /// Checks if the value has the annotated type, where ints can also be used as floats
+    pub fn has_type(&self, annotated: Type) -> bool {
+        match (self, annotated) {
+            (Value::Numerical(Numerical::Int(_)), Type::Int | Type::Float)
+            | (Value::Numerical(Numerical::Float(_)), Type::Float)
+            | (Value::Numerical(Numerical::Bool(_)), Type::Bool)
+            | (Value::Collection(Collection::String(_)), Type::Str)
+            | (Value::Collection(Collection::List(_)), Type::List)
+            | (Value::Collection(Collection::Dict(_)), Type::Dict)
+            | (Value::Callable(_), Type::Fn)
+            | (Value::Nil, Type::Nil) => true,
+            _ => false,
+        }
+    }
+
     pub fn to_iter(&self) -> RunRes<vec::IntoIter<Value>> {
         match self {
             Value::Collection(collection) => Ok(collection.to_iter()),
@@ -61,6 +86,7 @@ impl Value {
     pub fn deepclone(&self) -> Value {
         match self {
             Value::Collection(coll) => coll.deepclone().into(),
+            Value::Variant(variant) => Value::Variant(Rc::new(variant.deepclone())),
             other => other.clone(),
         }
     }
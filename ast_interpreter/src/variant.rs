@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use super::{
+    functions::Function,
+    runtime_error::{RunError, RunRes},
+    value::Value,
+};
+
+/// Identifies one variant of an enum, and constructs values of it when called
+#[derive(Debug)]
+pub struct VariantTag {
+    enum_name: String,
+    name: String,
+    arity: usize,
+}
+
+/// A tagged value, created by calling the constructor of an enum variant
+#[derive(Debug, PartialEq)]
+pub struct Variant {
+    tag: Rc<VariantTag>,
+    fields: Vec<Value>,
+}
+
+impl VariantTag {
+    pub fn new(enum_name: String, name: String, arity: usize) -> Self {
+        Self {
+            enum_name,
+            name,
+            arity,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Creates a variant with this tag from the given field values
+    pub fn construct(self: &Rc<Self>, fields: Vec<Value>) -> Value {
+        Value::Variant(Rc::new(Variant {
+            tag: self.clone(),
+            fields,
+        }))
+    }
+}
+
+/// Tags are the same if they are the same variant of the same enum, even if declared twice
+impl PartialEq for VariantTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.enum_name == other.enum_name
+    }
+}
+
+impl Variant {
+    pub fn name(&self) -> &str {
+        &self.tag.name
+    }
+
+    pub fn fields(&self) -> &[Value] {
+        &self.fields
+    }
+
+    pub fn deepclone(&self) -> Self {
+        Self {
+            tag: self.tag.clone(),
+            fields: self.fields.iter().map(Value::deepclone).collect(),
+        }
+    }
+
+    pub fn stringify(&self) -> String {
+        if self.fields.is_empty() {
+            return self.tag.name.clone();
+        }
+
+        let fields: Vec<String> = self.fields.iter().map(Value::stringify).collect();
+        format!("{}({})", self.tag.name, fields.join(", "))
+    }
+
+    /// Gets the fields of the value if it is of the given variant, for destructuring it
+    ///
+    /// The pattern must have as many fields as the variant, to catch mistakes in it.
+    /// Variants without fields are values rather than constructors, and are matched by tag.
+    pub fn fields_of(
+        value: &Value,
+        constructor: &Value,
+        nbr_fields: usize,
+    ) -> RunRes<Option<Vec<Value>>> {
+        let tag = match constructor {
+            Value::Callable(Function::Constructor(tag)) => tag,
+            Value::Variant(unit) if unit.fields.is_empty() => &unit.tag,
+            _ => {
+                return RunError::error(format!(
+                    "Can only match against enum variants, not {}",
+                    constructor.type_of()
+                ))
+            }
+        };
+        if tag.arity != nbr_fields {
+            return RunError::error(format!(
+                "The variant {} has {} fields, but the pattern has {nbr_fields}",
+                tag.name, tag.arity
+            ));
+        }
+
+        match value {
+            Value::Variant(variant) if &variant.tag == tag => Ok(Some(variant.fields.clone())),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,564 @@
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, Formatting, GotoDefinition, HoverRequest, Request as _},
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DocumentFormattingParams, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range, SaveOptions,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TextDocumentSyncSaveOptions, TextEdit, Url,
+};
+use parser::{CodeLoc, ParseError, Stmts};
+use positions::{name_range, to_loc, to_position, to_range};
+use semantic_analyzer::{find_declarations, NativeArity, Reference};
+use vm::value::{get_natives, Native};
+
+mod positions;
+
+type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Runs the language server, communicating with the editor over stdio
+fn main() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(capabilities())?;
+    connection.initialize(capabilities)?;
+
+    // The connection must be dropped when done, for the io threads to finish
+    let server = Server {
+        connection,
+        documents: HashMap::new(),
+        natives: get_natives(),
+    };
+    server.run()?;
+
+    io_threads.join()?;
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                    include_text: Some(false),
+                })),
+                ..Default::default()
+            },
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(Default::default()),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+struct Server {
+    connection: Connection,
+
+    /// The latest text of all open documents
+    documents: HashMap<Url, String>,
+
+    natives: Vec<Native>,
+}
+
+impl Server {
+    fn run(mut self) -> LspResult<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                respond::<GotoDefinition>(request, |params| self.definition(params))
+            }
+            HoverRequest::METHOD => respond::<HoverRequest>(request, |params| self.hover(params)),
+            Completion::METHOD => respond::<Completion>(request, |params| self.completion(params)),
+            Formatting::METHOD => respond::<Formatting>(request, |params| self.format(params)),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {method}"),
+            ),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> LspResult<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?;
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), document.text);
+                self.publish_diagnostics(document.uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidChangeTextDocumentParams>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+                // The whole document is sent on every change, so the last one is the current text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(params.text_document.uri, change.text);
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidSaveTextDocumentParams>(DidSaveTextDocument::METHOD)?;
+                self.publish_diagnostics(params.text_document.uri)?;
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidCloseTextDocumentParams>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+                self.documents.remove(&params.text_document.uri);
+                self.send_diagnostics(params.text_document.uri, vec![])?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn publish_diagnostics(&self, uri: Url) -> LspResult<()> {
+        let Some(text) = self.documents.get(&uri) else {
+            return Ok(());
+        };
+
+        // The analysis would mostly report errors following from the parse errors
+        let (stmts, errors) = parse_document(&uri, text);
+        let diagnostics = if errors.is_empty() {
+            self.analysis_diagnostics(&stmts, text)
+        } else {
+            errors
+                .iter()
+                .map(|error| parse_diagnostic(error, text))
+                .collect()
+        };
+        self.send_diagnostics(uri, diagnostics)
+    }
+
+    fn send_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> LspResult<()> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    /// The errors and warnings the compiler and linter report for a parsed document
+    fn analysis_diagnostics(&self, stmts: &Stmts, text: &str) -> Vec<Diagnostic> {
+        let names = self.native_names();
+        let arities: Vec<NativeArity> = self
+            .natives
+            .iter()
+            .map(|native| NativeArity {
+                name: native.name(),
+                arity: native.arity(),
+                accepts: Box::new(|count| native.accept_arity(count)),
+            })
+            .collect();
+
+        let undefined = semantic_analyzer::find_undefined_variables(stmts, &names)
+            .into_iter()
+            .map(|undefined| (undefined.range.clone(), undefined.to_string()));
+        let arity_errors = semantic_analyzer::find_arity_errors(stmts, &arities)
+            .into_iter()
+            .map(|error| (error.range.clone(), error.to_string()));
+        let type_errors = semantic_analyzer::find_type_errors(stmts)
+            .into_iter()
+            .map(|error| (error.range, error.message));
+        let variant_errors = semantic_analyzer::find_variant_errors(stmts)
+            .into_iter()
+            .map(|error| (error.range, error.message));
+
+        let errors = undefined
+            .chain(arity_errors)
+            .chain(type_errors)
+            .chain(variant_errors)
+            .map(|(range, message)| Diagnostic {
+                range: to_range(text, &range),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("zote".to_string()),
+                message,
+                ..Default::default()
+            });
+        let warnings = semantic_analyzer::lint(stmts, text, &names)
+            .into_iter()
+            .map(|warning| Diagnostic {
+                range: to_range(text, &warning.range),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(warning.code.to_string())),
+                source: Some("zote".to_string()),
+                message: warning.message,
+                ..Default::default()
+            });
+
+        errors.chain(warnings).collect()
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let text = self.documents.get(&uri)?;
+        let (stmts, _) = parse_document(&uri, text);
+
+        let loc = to_loc(text, position.position);
+        let (declarations, references) = find_declarations(&stmts);
+        let reference = reference_at(&references, loc)?;
+        let declaration = &declarations[reference.declaration?];
+
+        let range = name_range(text, &declaration.range, &reference.name)
+            .unwrap_or_else(|| to_range(text, &declaration.range));
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+    }
+
+    /// Shows the signature of natives
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let text = self.documents.get(&uri)?;
+        let (stmts, _) = parse_document(&uri, text);
+
+        let loc = to_loc(text, position.position);
+        let (_, references) = find_declarations(&stmts);
+        let reference = reference_at(&references, loc)?;
+        if reference.declaration.is_some() {
+            // Shadowed by a declaration in the code
+            return None;
+        }
+
+        let native = self
+            .natives
+            .iter()
+            .find(|native| native.name() == reference.name)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```zote\nfn {}\n```", native.debug_print()),
+            }),
+            range: Some(to_range(text, &reference.range)),
+        })
+    }
+
+    /// Completes the names of all variables in scope, as well as the natives
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        let text = self.documents.get(&uri)?;
+
+        let mut items: Vec<CompletionItem> = self
+            .natives
+            .iter()
+            .map(|native| CompletionItem {
+                label: native.name().to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(format!("fn {}", native.debug_print())),
+                ..Default::default()
+            })
+            .collect();
+
+        // Code which does not parse, such as while typing, still has the declarations around it
+        let (stmts, _) = parse_document(&uri, text);
+        let loc = to_loc(text, position.position);
+        let (declarations, _) = find_declarations(&stmts);
+        let visible = declarations
+            .into_iter()
+            .filter(|declaration| declaration.is_visible_at(loc));
+        for declaration in visible {
+            let name = declaration.name;
+            if name.starts_with('@') || items.iter().any(|item| item.label == name) {
+                continue;
+            }
+
+            items.push(CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::VARIABLE),
+                ..Default::default()
+            });
+        }
+
+        Some(CompletionResponse::Array(items))
+    }
+
+    /// Replaces the whole document with its pretty printed version
+    fn format(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let uri = params.text_document.uri;
+        let text = self.documents.get(&uri)?;
+        let (stmts, errors) = parse_document(&uri, text);
+        if !errors.is_empty() {
+            // Formatting the partial AST would drop the code which failed to parse
+            return None;
+        }
+
+        let formatted = semantic_analyzer::format_parsed(&stmts);
+        let whole = Range::new(Position::new(0, 0), to_position(text, text.len()));
+        Some(vec![TextEdit::new(whole, formatted)])
+    }
+
+    fn native_names(&self) -> Vec<&str> {
+        self.natives.iter().map(|native| native.name()).collect()
+    }
+}
+
+/// Parses the parameters of a request and responds with the result of the handler
+fn respond<R: lsp_types::request::Request>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(request.id, handler(params)),
+        Err(error) => Response::new_err(
+            request.id,
+            ErrorCode::InvalidParams as i32,
+            error.to_string(),
+        ),
+    }
+}
+
+/// Parses a document, resolving its includes relative to its directory as the CLI does
+fn parse_document(uri: &Url, text: &str) -> (Stmts, Vec<ParseError>) {
+    // The working directory is shared by the whole process, so documents are parsed one at a time
+    static WORKING_DIR: Mutex<()> = Mutex::new(());
+    let _lock = WORKING_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let saved = env::current_dir().ok();
+    if let Some(dir) = uri.to_file_path().ok().as_deref().and_then(Path::parent) {
+        let _ = env::set_current_dir(dir);
+    }
+    let parsed = parser::parse_with_errors(uri.path(), text);
+    if let Some(saved) = saved {
+        let _ = env::set_current_dir(saved);
+    }
+    parsed
+}
+
+fn parse_diagnostic(error: &ParseError, text: &str) -> Diagnostic {
+    let position = to_position(text, error.loc.index());
+    Diagnostic {
+        range: Range::new(position, position),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("zote".to_string()),
+        message: error.message.clone(),
+        ..Default::default()
+    }
+}
+
+/// The innermost reference to a variable at the location
+fn reference_at(references: &[Reference], loc: CodeLoc) -> Option<&Reference> {
+    references
+        .iter()
+        .filter(|reference| reference.range.contains(loc))
+        .min_by_key(|reference| reference.range.end().index() - reference.range.start().index())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use lsp_server::{Connection, ErrorCode, Message, Request, RequestId};
+    use lsp_types::{
+        notification::{Notification as _, PublishDiagnostics},
+        request::{Formatting, GotoDefinition, Request as _},
+        CompletionParams, CompletionResponse, DocumentFormattingParams, FormattingOptions,
+        GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams, Position,
+        PublishDiagnosticsParams, Range, TextDocumentIdentifier, TextDocumentPositionParams,
+        TextEdit, Url,
+    };
+    use vm::value::get_natives;
+
+    use super::Server;
+
+    fn uri() -> Url {
+        Url::parse("file:///test.zote").unwrap()
+    }
+
+    /// A server with the text as its only open document, and the client end of its connection
+    fn server(text: &str) -> (Server, Connection) {
+        let (connection, client) = Connection::memory();
+        let server = Server {
+            connection,
+            documents: HashMap::from([(uri(), text.to_string())]),
+            natives: get_natives(),
+        };
+        (server, client)
+    }
+
+    fn at(line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri()),
+            Position::new(line, character),
+        )
+    }
+
+    fn definition(server: &Server, line: u32, character: u32) -> Option<Range> {
+        let params = GotoDefinitionParams {
+            text_document_position_params: at(line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        match server.definition(params)? {
+            GotoDefinitionResponse::Scalar(location) => Some(location.range),
+            other => panic!("Expected a single location, got {other:?}"),
+        }
+    }
+
+    fn hover(server: &Server, line: u32, character: u32) -> Option<String> {
+        let params = HoverParams {
+            text_document_position_params: at(line, character),
+            work_done_progress_params: Default::default(),
+        };
+        match server.hover(params)?.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            other => panic!("Expected markup, got {other:?}"),
+        }
+    }
+
+    fn completions(server: &Server, line: u32, character: u32) -> Vec<String> {
+        let params = CompletionParams {
+            text_document_position: at(line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+        match server.completion(params) {
+            Some(CompletionResponse::Array(items)) => {
+                items.into_iter().map(|item| item.label).collect()
+            }
+            other => panic!("Expected a list of completions, got {other:?}"),
+        }
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    #[test]
+    fn definitions() {
+        let (server, _client) = server("x := 1;\nfn f(x) -> {\n\tx := x + 1;\n\tx\n};\nprint(x);");
+
+        assert_eq!(definition(&server, 2, 6), Some(range((1, 5), (1, 6))));
+        assert_eq!(definition(&server, 3, 1), Some(range((2, 1), (2, 2))));
+        assert_eq!(definition(&server, 5, 6), Some(range((0, 0), (0, 1))));
+
+        // Natives are not declared in the code
+        assert_eq!(definition(&server, 5, 1), None);
+    }
+
+    #[test]
+    fn hovers() {
+        let (server, _client) = server("print(1);\nfn f(print) -> print;");
+
+        assert_eq!(
+            hover(&server, 0, 2),
+            Some("```zote\nfn print(values...)\n```".to_string())
+        );
+
+        // Shadowed by a parameter
+        assert_eq!(hover(&server, 1, 17), None);
+    }
+
+    #[test]
+    fn completions_in_scope() {
+        let (server, _client) = server("fn f(param) -> {\n\tlocal := 1;\n\tlocal\n};\nf(1)");
+
+        let inside = completions(&server, 2, 1);
+        for name in ["param", "local", "f", "print"] {
+            assert!(
+                inside.contains(&name.to_string()),
+                "{name} not in {inside:?}"
+            );
+        }
+
+        let outside = completions(&server, 4, 0);
+        assert!(outside.contains(&"f".to_string()));
+        assert!(!outside.contains(&"param".to_string()));
+        assert!(!outside.contains(&"local".to_string()));
+    }
+
+    #[test]
+    fn requests() {
+        let (server, _client) = server("x:=1;");
+
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        };
+        let request = Request::new(RequestId::from(1), Formatting::METHOD.to_string(), params);
+        let response = server.handle_request(request);
+        let edits: Vec<TextEdit> = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range, range((0, 0), (0, 5)));
+        assert_eq!(edits[0].new_text, "x := 1;\n");
+
+        // Malformed parameters and unknown methods are reported as errors
+        let request = Request::new(RequestId::from(2), GotoDefinition::METHOD.to_string(), 0);
+        let error = server.handle_request(request).error.unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidParams as i32);
+
+        let request = Request::new(RequestId::from(3), "zote/unknown".to_string(), 0);
+        let error = server.handle_request(request).error.unwrap();
+        assert_eq!(error.code, ErrorCode::MethodNotFound as i32);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let (server, client) = server("x := 1;\nprint(y);\nz := ;");
+
+        // Only the parse errors are reported when there are any
+        server.publish_diagnostics(uri()).unwrap();
+        let Ok(Message::Notification(notification)) = client.receiver.try_recv() else {
+            panic!("Expected the diagnostics to be published")
+        };
+        assert_eq!(notification.method, PublishDiagnostics::METHOD);
+        let params: PublishDiagnosticsParams = serde_json::from_value(notification.params).unwrap();
+        let lines: Vec<u32> = params
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![2]);
+    }
+
+    #[test]
+    fn includes() {
+        // Includes are resolved relative to the document, not the working directory of the server
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/programs/main.zote");
+        let uri = Url::from_file_path(path).unwrap();
+        let text = "include!(\"nested/options.zote\");\nprint(Some(1));";
+        let (mut server, client) = server("");
+        server.documents = HashMap::from([(uri.clone(), text.to_string())]);
+
+        server.publish_diagnostics(uri).unwrap();
+        let Ok(Message::Notification(notification)) = client.receiver.try_recv() else {
+            panic!("Expected the diagnostics to be published")
+        };
+        let params: PublishDiagnosticsParams = serde_json::from_value(notification.params).unwrap();
+        assert_eq!(params.diagnostics, vec![]);
+    }
+}
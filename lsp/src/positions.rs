@@ -0,0 +1,108 @@
+use lsp_types::{Position, Range};
+use parser::{CodeLoc, CodeRange};
+
+/// The range of the first occurrence of the name as an identifier in the code range
+pub fn name_range(text: &str, range: &CodeRange, name: &str) -> Option<Range> {
+    let start = range.start().index();
+    let code = text.get(start..range.end().index())?;
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    let (offset, _) = code.match_indices(name).find(|(offset, _)| {
+        let before = code[..*offset].chars().next_back();
+        let after = code[offset + name.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })?;
+    Some(Range::new(
+        to_position(text, start + offset),
+        to_position(text, start + offset + name.len()),
+    ))
+}
+
+/// Converts an editor position, counted in UTF-16 units, to a location in the code
+pub fn to_loc(text: &str, position: Position) -> CodeLoc {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+
+    let (mut index, mut col, mut units) = (line_start, 1, 0);
+    for c in text[line_start..].chars() {
+        if units >= position.character as usize || c == '\n' {
+            break;
+        }
+        index += c.len_utf8();
+        units += c.len_utf16();
+        col += 1;
+    }
+    CodeLoc::new(index, position.line as usize + 1, col)
+}
+
+/// Converts a byte index in the code to an editor position
+pub fn to_position(text: &str, index: usize) -> Position {
+    let before = text.get(..index).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+pub fn to_range(text: &str, range: &CodeRange) -> Range {
+    Range::new(
+        to_position(text, range.start().index()),
+        to_position(text, range.end().index()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+    use parser::{CodeLoc, CodeRange};
+
+    use super::{name_range, to_loc, to_position};
+
+    #[test]
+    fn utf16_columns() {
+        // 'ö' is two bytes but one UTF-16 unit, while '😀' is four bytes and two units
+        let text = "ö := 1;\n😀x := ö;\n";
+
+        let loc = to_loc(text, Position::new(0, 1));
+        assert_eq!((loc.index(), loc.line(), loc.col()), (2, 1, 2));
+        let loc = to_loc(text, Position::new(1, 2));
+        assert_eq!((loc.index(), loc.line(), loc.col()), (13, 2, 2));
+
+        assert_eq!(to_position(text, 2), Position::new(0, 1));
+        assert_eq!(to_position(text, 13), Position::new(1, 2));
+        assert_eq!(to_position(text, 18), Position::new(1, 7));
+    }
+
+    #[test]
+    fn line_ends() {
+        let text = "x := 1;\r\ny\n";
+
+        // Positions past the end of a line are clamped to it, before the line break
+        let loc = to_loc(text, Position::new(0, 100));
+        assert_eq!((loc.index(), loc.line(), loc.col()), (8, 1, 9));
+        let loc = to_loc(text, Position::new(1, 100));
+        assert_eq!((loc.index(), loc.line(), loc.col()), (10, 2, 2));
+
+        // The end of the text, after the last line break, is on a line of its own
+        assert_eq!(to_position(text, text.len()), Position::new(2, 0));
+        let loc = to_loc(text, Position::new(2, 0));
+        assert_eq!((loc.index(), loc.line(), loc.col()), (text.len(), 3, 1));
+    }
+
+    #[test]
+    fn name_ranges() {
+        let text = "fn f(fa, a) -> a";
+        let range = CodeRange::from_locs(CodeLoc::new(0, 1, 1), CodeLoc::new(text.len(), 1, 17));
+
+        // Only whole identifiers match
+        assert_eq!(
+            name_range(text, &range, "a"),
+            Some(Range::new(Position::new(0, 9), Position::new(0, 10)))
+        );
+        assert_eq!(name_range(text, &range, "b"), None);
+    }
+}
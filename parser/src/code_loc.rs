@@ -66,6 +66,19 @@ impl CodeRange {
         }
     }
 
+    pub fn start(&self) -> CodeLoc {
+        self.start
+    }
+
+    pub fn end(&self) -> CodeLoc {
+        self.end
+    }
+
+    /// Checks if the location is within the range, including its end
+    pub fn contains(&self, loc: CodeLoc) -> bool {
+        self.start.index() <= loc.index() && loc.index() <= self.end.index()
+    }
+
     pub fn sl(&self) -> u16 {
         self.start.line
     }
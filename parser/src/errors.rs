@@ -1,12 +1,27 @@
+use std::fmt::{self, Display};
+
 use crate::code_loc::CodeLoc;
 
+/// An error found when scanning or parsing the code
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub loc: CodeLoc,
+    pub message: String,
+    error_type: &'static str,
+    scriptname: String,
+}
+
 pub struct ErrorReporter {
     pub had_error: bool,
+    pub errors: Vec<ParseError>,
 }
 
 impl ErrorReporter {
     pub fn new() -> Self {
-        Self { had_error: false }
+        Self {
+            had_error: false,
+            errors: vec![],
+        }
     }
 
     pub fn scan_error(&mut self, loc: &CodeLoc, message: &str, scriptname: &str) {
@@ -17,13 +32,28 @@ impl ErrorReporter {
         self.error("Compilation", loc, message, scriptname)
     }
 
-    fn error(&mut self, error_type: &str, loc: &CodeLoc, message: &str, scriptname: &str) {
-        eprintln!(
-            "[line: {}, col: {}] {error_type} Error: {message}. In {scriptname}",
-            loc.line(),
-            loc.col()
-        );
+    fn error(&mut self, error_type: &'static str, loc: &CodeLoc, message: &str, scriptname: &str) {
+        self.errors.push(ParseError {
+            loc: *loc,
+            message: message.to_string(),
+            error_type,
+            scriptname: scriptname.to_string(),
+        });
 
         self.had_error = true;
     }
 }
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line: {}, col: {}] {} Error: {}. In {}",
+            self.loc.line(),
+            self.loc.col(),
+            self.error_type,
+            self.message,
+            self.scriptname
+        )
+    }
+}
@@ -1,7 +1,15 @@
 use std::{fmt::Debug, rc::Rc};
 
+use either::Either;
+
 use super::{AstNode, Parser, Stmts};
-use crate::{code_loc::CodeLoc, scanner::Token};
+use crate::{
+    code_loc::CodeLoc,
+    fresh_ids::fresh_copy,
+    partial_application::{is_placeholder, resolve_placeholders},
+    scanner::Token,
+    types::Type,
+};
 
 // Cannot have more than this many arguments to a function
 pub const MAX_ARGS: usize = 255;
@@ -13,10 +21,13 @@ pub type ExprNode = AstNode<Expr>;
 pub enum Expr {
     Call(ExprNode, Vec<ExprNode>),
     IndexInto(ExprNode, Index),
+    SafeIndexInto(ExprNode, Index),
     Binary(ExprNode, BinOper, ExprNode),
+    ChainedComparison(ExprNode, Vec<(BinOper, ExprNode)>), // Such as `a < b <= c`, evaluating each operand once
     Unary(UnOper, ExprNode),
     Logical(ExprNode, LogicalOper, ExprNode),
     Assign(LValue, ExprNode),
+    OpAssign(LValue, BinOper, ExprNode),
     Var(String),
     Int(i64),
     Float(f64),
@@ -24,16 +35,24 @@ pub enum Expr {
     String(Rc<String>),
     Block(Stmts),
     If(ExprNode, ExprNode, Option<ExprNode>),
-    While(ExprNode, ExprNode),
-    For(LValue, ExprNode, ExprNode),
-    Break, // TODO Do we want to return an optional value from this?
-    Continue,
+    IfLet(LValue, ExprNode, ExprNode, Option<ExprNode>), // Takes the then branch if the pattern matches
+    // Loops take an optional else, evaluated if not breaking, and an optional label
+    While(ExprNode, ExprNode, Option<ExprNode>, Option<String>),
+    WhileLet(LValue, ExprNode, ExprNode, Option<ExprNode>, Option<String>),
+    For(LValue, ExprNode, ExprNode, Option<ExprNode>, Option<String>),
+    Break(Option<String>, Option<ExprNode>), // The value of the loop when breaking out of it
+    Continue(Option<String>),
     Return(Option<ExprNode>),
     Nil,
     List(ListContent),
+    Dict(Vec<(ExprNode, ExprNode)>),
+    Set(Vec<ExprNode>),
     Tuple(Vec<ExprNode>),
-    FunctionDefinition(String, Vec<LValue>, ExprNode),
+    FunctionDefinition(String, Vec<LValue>, ExprNode, Option<Type>), // The type is the annotated return type
     Match(ExprNode, Vec<(LValue, ExprNode)>),
+    Rest(Option<String>), // Only valid within a tuple or list pattern, such as (head, ..tail)
+    EnumVariant(String, String, Vec<String>), // The constructor of a variant, with its enum and field names
+    Invalid, // An error node, in place of an expression which could not be parsed
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -48,6 +67,16 @@ pub enum LValue {
     Var(String),
     Tuple(Vec<LValue>),
     Constant(ExprNode),
+    /// Matches anything without binding it, written as '_'
+    Wildcard,
+    /// Binds the remaining values in a tuple pattern, written as '..name' or '..'
+    Rest(Box<LValue>),
+    /// Destructures an enum variant by its constructor name, written as 'Name(fields...)'
+    ///
+    /// Variants without fields are also matched by their bare name, as 'Name'.
+    Variant(String, Vec<LValue>),
+    /// An lvalue annotated with the type its value must have, written as 'name: type'
+    Typed(Box<LValue>, Type),
 }
 
 impl From<String> for LValue {
@@ -90,18 +119,27 @@ pub enum BinOper {
     Gt,
     Geq,
     Append,
+    BitAnd,
+    BitOr,
+    BitXor,
+    LShift,
+    RShift,
+    In,
+    NotIn,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum UnOper {
     Not,
     Sub,
+    BitNot,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum LogicalOper {
     And,
     Or,
+    Coalesce,
 }
 
 impl<'a> Parser<'a> {
@@ -122,6 +160,8 @@ impl<'a> Parser<'a> {
 
     fn assignment(&mut self) -> Option<ExprNode> {
         // assignment     → lvalue binOper? "=" assignment | equality ;
+        // Binary shorthands such as "+=" evaluate their lvalue only once, while logical ones
+        // such as "or=" are desugared into a normal assignment.
         let expr = self.pipe()?;
 
         // Ugly duplication as we need different code for logical and binary
@@ -135,12 +175,15 @@ impl<'a> Parser<'a> {
             )?;
 
             let start = expr.start_loc;
-            let lvalue = self.expr_to_lvalue(expr.clone(), false)?;
+            let lvalue = self.expr_to_lvalue(expr, false)?;
+            if !matches!(lvalue, LValue::Var(_) | LValue::Index(_, _)) {
+                self.error("Compound assignment requires a variable or an indexing to the left");
+                return None;
+            }
             let rvalue = self.assignment()?;
             let end = rvalue.end_loc;
 
-            let binary = ExprNode::binary(expr, oper, rvalue);
-            let assign = Expr::Assign(lvalue, binary);
+            let assign = Expr::OpAssign(lvalue, oper, rvalue);
             Some(ExprNode::new(assign, start, end))
         } else if let Some(oper) = FromToken::try_from(self.peek())
             && self.peek2() == Some(&Token::Eq)
@@ -152,11 +195,13 @@ impl<'a> Parser<'a> {
             )?;
 
             let start = expr.start_loc;
-            let lvalue = self.expr_to_lvalue(expr.clone(), false)?;
+            // The target is both read and assigned, so it is copied with ids of its own
+            let target = fresh_copy(&expr);
+            let lvalue = self.expr_to_lvalue(expr, false)?;
             let rvalue = self.assignment()?;
             let end = rvalue.end_loc;
 
-            let logical = ExprNode::logical(expr, oper, rvalue);
+            let logical = ExprNode::logical(target, oper, rvalue);
             let assign = Expr::Assign(lvalue, logical);
             Some(ExprNode::new(assign, start, end))
         } else if self.match_token(Token::Eq) {
@@ -177,8 +222,52 @@ impl<'a> Parser<'a> {
         self.expr_to_lvalue(expr, decl)
     }
 
+    /// Parses a function parameter, with an optional type annotation
+    pub fn parameter(&mut self) -> Option<LValue> {
+        let lvalue = self.lvalue(true)?;
+        self.opt_type_annotation(lvalue)
+    }
+
+    /// Wraps the lvalue in its type if it is followed by an annotation such as ': int'
+    pub fn opt_type_annotation(&mut self, lvalue: LValue) -> Option<LValue> {
+        if self.match_token(Token::Colon) {
+            let annotated = self.type_annotation()?;
+            Some(LValue::Typed(Box::new(lvalue), annotated))
+        } else {
+            Some(lvalue)
+        }
+    }
+
+    /// Parses the name of a type, such as 'int' or 'list'
+    pub fn type_annotation(&mut self) -> Option<Type> {
+        let annotated = match self.peek() {
+            Token::Identifier(name) => Type::from_name(name),
+            Token::Fn => Some(Type::Fn),
+            Token::Nil => Some(Type::Nil),
+            _ => None,
+        };
+
+        if annotated.is_some() {
+            self.take();
+        } else {
+            let names: Vec<&str> = Type::ALL.iter().map(Type::name).collect();
+            self.error(&format!("Expect a type, one of {}", names.join(", ")));
+        }
+        annotated
+    }
+
+    /// Is the next token a type, followed by the start of a function body?
+    pub fn at_return_type(&self) -> bool {
+        let is_type = match self.peek() {
+            Token::Identifier(name) => Type::from_name(name).is_some(),
+            Token::Fn | Token::Nil => true,
+            _ => false,
+        };
+        is_type && matches!(self.peek2(), Some(Token::LBrace | Token::RArrow))
+    }
+
     pub fn expr_to_lvalue(&mut self, expr: ExprNode, decl: bool) -> Option<LValue> {
-        match expr.conv_to_lvalue(decl) {
+        match expr.conv_to_lvalue(decl, self) {
             Ok(lvalue) => Some(lvalue),
             Err(reason) => {
                 self.error(&reason);
@@ -227,7 +316,19 @@ impl<'a> Parser<'a> {
     fn add_pipe_call(&mut self, expr: ExprNode) -> Option<ExprNode> {
         let start = expr.start_loc;
         let (func, mut args, end) = self.accept_call_pipe()?;
-        args.insert(0, expr); // Does this really work with ownership?
+
+        // The piped value is placed at the placeholder '_', or otherwise as the first argument
+        match args.iter().filter(|arg| is_placeholder(arg)).count() {
+            0 => args.insert(0, expr),
+            1 => {
+                let slot = args.iter_mut().find(|arg| is_placeholder(arg))?;
+                *slot = expr;
+            }
+            _ => {
+                self.error("Can only pipe into a single placeholder '_'");
+                return None;
+            }
+        }
         Some(ExprNode::new(Expr::Call(func, args), start, end))
     }
 
@@ -245,11 +346,13 @@ impl<'a> Parser<'a> {
         // call_pipe   → lambda | IDENTIFIER | primary ( "(" exprs_list ")" )+
         // Really accepts a variable, variable with an arg list, or a lambda
 
-        // Similar to self.call, but accepting lambda as well
+        // Similar to self.call, but accepting lambda as well.
+        // Placeholders in the outermost call are left for the pipe to fill
         let call = if self.peek() == &Token::Backslash {
             self.lambda()?
         } else {
-            self.call()?
+            let expr = self.primary()?;
+            self.add_calls(expr)?
         };
 
         // Is it just a variable?
@@ -261,10 +364,15 @@ impl<'a> Parser<'a> {
             start_loc: _,
             end_loc,
             node: box Expr::Call(caller, args),
+            id: _,
         } = call
         {
-            Some((caller, args, end_loc))
-        } else if matches!(call.node.as_ref(), Expr::FunctionDefinition(_, _, _)) {
+            Some((
+                resolve_placeholders(caller, &mut self.partial_calls),
+                args,
+                end_loc,
+            ))
+        } else if matches!(call.node.as_ref(), Expr::FunctionDefinition(_, _, _, _)) {
             let end = call.end_loc;
             Some((call, vec![], end))
         } else {
@@ -276,7 +384,7 @@ impl<'a> Parser<'a> {
     }
 
     fn lambda(&mut self) -> Option<ExprNode> {
-        // lambda     → or | "\" lvalue "->" or | "\>>" lambda ( ">>" lambda )*
+        // lambda     → coalesce | "\" lvalue "->" coalesce | "\>>" lambda ( ">>" lambda )*
 
         let start = *self.peek_start_loc();
         if self.match_token(Token::Backslash) {
@@ -285,7 +393,7 @@ impl<'a> Parser<'a> {
                 // Expect parameters
                 while {
                     // Do-while loop
-                    params.push(self.lvalue(true)?);
+                    params.push(self.parameter()?);
                     self.match_token(Token::Comma)
                 } {}
                 self.accept(Token::RArrow, "Expect \"->\" to follow lvalue in lambda")?;
@@ -304,7 +412,7 @@ impl<'a> Parser<'a> {
                 start.col()
             );
             Some(ExprNode::new(
-                Expr::FunctionDefinition(name, params, body),
+                Expr::FunctionDefinition(name, params, body, None),
                 start,
                 end,
             ))
@@ -324,13 +432,25 @@ impl<'a> Parser<'a> {
             let expr = self.pipe_extension(initial_expr)?;
 
             Some(ExprNode::new(
-                Expr::FunctionDefinition(name, params, expr),
+                Expr::FunctionDefinition(name, params, expr, None),
                 start,
                 start,
             ))
         } else {
-            self.or()
+            self.coalesce()
+        }
+    }
+
+    fn coalesce(&mut self) -> Option<ExprNode> {
+        // coalesce       → or ( "??" or )* ;
+        let mut expr = self.or()?;
+
+        while let Some(op) = self.match_bin_expr_op([LogicalOper::Coalesce]) {
+            let right = self.or()?;
+            expr = ExprNode::logical(expr, op, right);
         }
+
+        Some(expr)
     }
 
     fn or(&mut self) -> Option<ExprNode> {
@@ -358,10 +478,22 @@ impl<'a> Parser<'a> {
     }
 
     fn equality(&mut self) -> Option<ExprNode> {
-        // equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-        let mut expr = self.comparison()?;
+        // equality       → membership ( ( "!=" | "==" ) membership )* ;
+        let mut expr = self.membership()?;
 
         while let Some(op) = self.match_op([BinOper::Eq, BinOper::Neq]) {
+            let right = self.membership()?;
+            expr = ExprNode::binary(expr, op, right);
+        }
+
+        Some(expr)
+    }
+
+    fn membership(&mut self) -> Option<ExprNode> {
+        // membership     → comparison ( ( "in" | "not" "in" ) comparison )* ;
+        let mut expr = self.comparison()?;
+
+        while let Some(op) = self.match_membership_op() {
             let right = self.comparison()?;
             expr = ExprNode::binary(expr, op, right);
         }
@@ -369,11 +501,94 @@ impl<'a> Parser<'a> {
         Some(expr)
     }
 
+    /// Matches an infix `in` or `not in`
+    ///
+    /// They are not keywords, so `in` can still be used as a function, as in `x >> in(xs)`.
+    fn match_membership_op(&mut self) -> Option<BinOper> {
+        if !self.membership_allowed {
+            return None;
+        }
+
+        let is_in =
+            |token: Option<&Token>| matches!(token, Some(Token::Identifier(name)) if name == "in");
+        if is_in(Some(self.peek())) {
+            self.take();
+            Some(BinOper::In)
+        } else if matches!(self.peek(), Token::Identifier(name) if name == "not")
+            && is_in(self.peek2())
+        {
+            self.take();
+            self.take();
+            Some(BinOper::NotIn)
+        } else {
+            None
+        }
+    }
+
     fn comparison(&mut self) -> Option<ExprNode> {
-        // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-        let mut term = self.term()?;
+        // comparison     → bit_or ( ( ">" | ">=" | "<" | "<=" ) bit_or )* ;
+        let bit_or = self.bit_or()?;
 
+        // Several comparisons are chained as in math, so `a < b < c` means `a < b and b < c`
+        let mut links = vec![];
         while let Some(op) = self.match_op([BinOper::Gt, BinOper::Lt, BinOper::Geq, BinOper::Leq]) {
+            links.push((op, self.bit_or()?));
+        }
+
+        if links.len() <= 1 {
+            Some(match links.pop() {
+                Some((op, right)) => ExprNode::binary(bit_or, op, right),
+                None => bit_or,
+            })
+        } else {
+            let start_loc = bit_or.start_loc;
+            let end_loc = links.last().expect("Several links").1.end_loc;
+            let expr = Expr::ChainedComparison(bit_or, links);
+            Some(AstNode::new(expr, start_loc, end_loc))
+        }
+    }
+
+    fn bit_or(&mut self) -> Option<ExprNode> {
+        // bit_or         → bit_xor ( "|" bit_xor )* ;
+        let mut bit_xor = self.bit_xor()?;
+
+        while let Some(op) = self.match_bin_expr_op([BinOper::BitOr]) {
+            let right = self.bit_xor()?;
+            bit_xor = ExprNode::binary(bit_xor, op, right);
+        }
+
+        Some(bit_xor)
+    }
+
+    fn bit_xor(&mut self) -> Option<ExprNode> {
+        // bit_xor        → bit_and ( "xor" bit_and )* ;
+        let mut bit_and = self.bit_and()?;
+
+        while let Some(op) = self.match_bin_expr_op([BinOper::BitXor]) {
+            let right = self.bit_and()?;
+            bit_and = ExprNode::binary(bit_and, op, right);
+        }
+
+        Some(bit_and)
+    }
+
+    fn bit_and(&mut self) -> Option<ExprNode> {
+        // bit_and        → shift ( "&" shift )* ;
+        let mut shift = self.shift()?;
+
+        while let Some(op) = self.match_bin_expr_op([BinOper::BitAnd]) {
+            let right = self.shift()?;
+            shift = ExprNode::binary(shift, op, right);
+        }
+
+        Some(shift)
+    }
+
+    fn shift(&mut self) -> Option<ExprNode> {
+        // shift          → term ( ( "<<" | ">>>" ) term )* ;
+        let mut term = self.term()?;
+
+        while let Some(op) = self.match_bin_expr_op([BinOper::LShift, BinOper::RShift]) {
             let right = self.term()?;
             term = ExprNode::binary(term, op, right);
         }
@@ -418,9 +633,9 @@ impl<'a> Parser<'a> {
     }
 
     fn unary(&mut self) -> Option<ExprNode> {
-        // unary          → ( "!" | "-" )? call ;
+        // unary          → ( "!" | "-" | "~" )? call ;
         let start = *self.peek_start_loc();
-        if let Some(op) = self.match_op([UnOper::Sub, UnOper::Not]) {
+        if let Some(op) = self.match_op([UnOper::Sub, UnOper::Not, UnOper::BitNot]) {
             let right = self.call()?;
             Some(ExprNode::unary(start, op, right))
         } else {
@@ -430,15 +645,18 @@ impl<'a> Parser<'a> {
 
     fn call(&mut self) -> Option<ExprNode> {
         // call           → primary ( call | index )* ;
+        // Calls with a placeholder '_' as an argument are partially applied
         let expr = self.primary()?;
-        self.add_calls(expr)
+        let calls = self.add_calls(expr)?;
+        Some(resolve_placeholders(calls, &mut self.partial_calls))
     }
 
     fn add_calls(&mut self, base: ExprNode) -> Option<ExprNode> {
-        // Takes a base expressions, and adds     ( "(" expr_list ")" | "[" indexing "]" )*
+        // Takes a base expressions, and adds
+        //     ( "(" expr_list ")" | "?"? "[" indexing "]" | "." IDENTIFIER "(" expr_list ")" )*
 
-        if self.peek_info().seperated {
-            // Only allow calls/indexing if not seperated at all
+        if self.peek_info().seperated && self.peek() != &Token::Dot {
+            // Only allow calls/indexing if not seperated at all. Method calls can be chained over lines
             return Some(base);
         }
 
@@ -456,11 +674,49 @@ impl<'a> Parser<'a> {
             let index = self.accept_indexing()?;
             let end = *self.peek_last_end_loc()?;
             self.add_calls(ExprNode::new(Expr::IndexInto(base, index), start, end))
+        } else if self.match_token(Token::QuestionLBrack) {
+            let index = self.accept_indexing()?;
+            let end = *self.peek_last_end_loc()?;
+            self.add_calls(ExprNode::new(Expr::SafeIndexInto(base, index), start, end))
+        } else if self.match_token(Token::Dot) {
+            let method_call = self.accept_method_call(base)?;
+            self.add_calls(method_call)
         } else {
             Some(base)
         }
     }
 
+    /// Accepts a method call after a dot, which is called with the base as its first argument
+    ///
+    /// So `xs.map(f)` is the same as `map(xs, f)`, or `xs >> map(f)`.
+    fn accept_method_call(&mut self, base: ExprNode) -> Option<ExprNode> {
+        let start = base.start_loc;
+        let Token::Identifier(name) = self.peek() else {
+            self.error("Expect a method name after '.'");
+            return None;
+        };
+        let method = ExprNode::new(
+            Expr::Var(name.clone()),
+            *self.peek_start_loc(),
+            *self.peek_end_loc(),
+        );
+        self.take();
+
+        self.accept(
+            Token::LPar,
+            "Expect '(' after method name, as there are no fields to access",
+        )?;
+        let mut args = self.accept_exprs_list(&Token::RPar)?;
+        self.accept(Token::RPar, "Expect ')' to close method call arguments")?;
+
+        if args.len() + 1 >= MAX_ARGS {
+            self.error(&format!("Can't have more than {MAX_ARGS} arguments"));
+        }
+        args.insert(0, base);
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Call(method, args), start, end))
+    }
+
     // Note: Does capture ending ], but not the starting [
     fn accept_indexing(&mut self) -> Option<Index> {
         // indexing     -> ( expression | expression? ":" (expression? ( ":" expression? )? )? ) "]"
@@ -522,14 +778,20 @@ impl<'a> Parser<'a> {
         // primary        → "(" expression ")" | "(" expression ( "," expression)+ ")"
         //                | block | if | "break" expr? ;
 
+        if self.peek() == &Token::LBrace && self.is_dict_literal() {
+            return self.accept_dict();
+        }
         match self.peek() {
             Token::If => self.accept_if(),
             Token::LBrace => self.accept_block(),
-            Token::While => self.accept_while(),
+            Token::HashLBrace => self.accept_set(),
+            Token::While => self.accept_while(None),
             Token::LBrack => self.accept_list(),
             Token::LPar => self.maybe_tuple(),
-            Token::For => self.accept_for(),
+            Token::For => self.accept_for(None),
+            Token::Label(_) => self.accept_labeled_loop(),
             Token::Match => self.accept_match(),
+            Token::DotDot => self.accept_rest(),
             Token::MacroInvocation(_) => self.accept_expr_macro_invocation(),
             _ => self.simple_primary(),
         }
@@ -549,8 +811,9 @@ impl<'a> Parser<'a> {
             Token::Identifier(str) => some_node(Expr::Var(str.to_owned()), start, end),
             Token::Nil => some_node(Expr::Nil, start, end),
             _ => {
+                // Keep parsing around the missing expression, without consuming the token
                 self.error("Expect expression");
-                None
+                return some_node(Expr::Invalid, start, start);
             }
         }
         .map(|res| {
@@ -560,6 +823,23 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn accept_rest(&mut self) -> Option<ExprNode> {
+        // rest -> ".." Identifier?
+        let start = *self.peek_start_loc();
+        self.accept(Token::DotDot, "Internal error at rest pattern")?;
+
+        let name = if let Token::Identifier(name) = self.peek() {
+            let name = name.clone();
+            self.take();
+            Some(name)
+        } else {
+            None
+        };
+
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Rest(name), start, end))
+    }
+
     fn maybe_tuple(&mut self) -> Option<ExprNode> {
         self.accept(
             Token::LPar,
@@ -623,13 +903,99 @@ impl<'a> Parser<'a> {
         Some(ExprNode::new(Expr::List(contained), start, end))
     }
 
+    /// Checks if the upcoming braces contain a dict literal rather than a block
+    ///
+    /// A dict literal is either `{:}`, or has its first expression directly followed by a ':'.
+    /// The expression is parsed speculatively, so that colons within it, such as in the typed
+    /// parameters of a lambda, are not mistaken for the colon of a dict.
+    fn is_dict_literal(&mut self) -> bool {
+        // A block can start with an annotated declaration, such as 'x: int := 0'
+        let upcoming: Vec<&Token> = self.tokens[self.current + 1..]
+            .iter()
+            .take(4)
+            .map(|info| &info.token)
+            .collect();
+        match upcoming[..] {
+            [Token::Colon, Token::RBrace, ..] => return true,
+            [Token::Identifier(_), Token::Colon, _, Token::ColonEq] => return false,
+            // Or with a labeled loop, such as 'outer: for x in xs'
+            [Token::Label(_), Token::Colon, Token::For | Token::While, ..] => return false,
+            _ => (),
+        }
+
+        // Errors are not reported while panicking, and the parser is reset afterwards
+        let (current, panicking, membership_allowed) =
+            (self.current, self.panicking, self.membership_allowed);
+        self.current += 1;
+        self.panicking = true;
+        self.membership_allowed = true;
+        let is_dict = self.expression().is_some() && self.peek() == &Token::Colon;
+        self.current = current;
+        self.panicking = panicking;
+        self.membership_allowed = membership_allowed;
+        is_dict
+    }
+
+    fn accept_dict(&mut self) -> Option<ExprNode> {
+        // dict -> "{" ( expr ":" expr ( "," expr ":" expr )* ","? | ":" ) "}"
+        let start = *self.peek_start_loc();
+        self.accept(Token::LBrace, "Internal error at dict")?;
+
+        let mut pairs = vec![];
+        if !self.match_token(Token::Colon) {
+            while self.peek() != &Token::RBrace {
+                let key = self.expression()?;
+                self.accept(Token::Colon, "Expect ':' between key and value in dict")?;
+                let value = self.expression()?;
+                pairs.push((key, value));
+
+                if !self.match_token(Token::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.accept(Token::RBrace, "Need to close dict with '}'")?;
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Dict(pairs), start, end))
+    }
+
+    fn accept_set(&mut self) -> Option<ExprNode> {
+        // set -> "#{" expr_list "}"
+        let start = *self.peek_start_loc();
+        self.accept(Token::HashLBrace, "Internal error at set")?;
+
+        let values = self.accept_exprs_list(&Token::RBrace)?;
+
+        self.accept(Token::RBrace, "Need to close set with '}'")?;
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Set(values), start, end))
+    }
+
     fn accept_return(&mut self) -> Option<ExprNode> {
         let start = *self.peek_start_loc();
         self.accept(Token::Return, "Internal error at return")?;
 
+        let expr = self.opt_trailing_expression()?;
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Return(expr), start, end))
+    }
+
+    fn accept_break(&mut self) -> Option<ExprNode> {
+        let start = *self.peek_start_loc();
+        self.accept(Token::Break, "Internal error at break")?;
+
+        let label = self.match_label();
+        let expr = self.opt_trailing_expression()?;
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Break(label, expr), start, end))
+    }
+
+    /// Parses the optional expression following a return or break
+    fn opt_trailing_expression(&mut self) -> Option<Option<ExprNode>> {
         // Ugly way, but if there is no expression we try to infer a nil return,
         // but only a simple check, which might miss things in strange expressions
-        let expr = if ![
+        if ![
             Token::Semicolon,
             Token::Comma,
             Token::Else,
@@ -639,26 +1005,30 @@ impl<'a> Parser<'a> {
         ]
         .contains(self.peek())
         {
-            Some(self.expression()?)
+            Some(Some(self.expression()?))
         } else {
-            None
-        };
-        let end = *self.peek_last_end_loc()?;
-        Some(ExprNode::new(Expr::Return(expr), start, end))
-    }
-
-    fn accept_break(&mut self) -> Option<ExprNode> {
-        let start = *self.peek_start_loc();
-        let end = *self.peek_end_loc();
-        self.accept(Token::Break, "Internal error at break")?;
-        Some(ExprNode::new(Expr::Break, start, end))
+            Some(None)
+        }
     }
 
     fn accept_continue(&mut self) -> Option<ExprNode> {
         let start = *self.peek_start_loc();
-        let end = *self.peek_end_loc();
         self.accept(Token::Continue, "Internal error at continue")?;
-        Some(ExprNode::new(Expr::Continue, start, end))
+
+        let label = self.match_label();
+        let end = *self.peek_last_end_loc()?;
+        Some(ExprNode::new(Expr::Continue(label), start, end))
+    }
+
+    /// Takes a label, if one is next
+    fn match_label(&mut self) -> Option<String> {
+        if let Token::Label(label) = self.peek() {
+            let label = label.clone();
+            self.take();
+            Some(label)
+        } else {
+            None
+        }
     }
 
     fn accept_if(&mut self) -> Option<ExprNode> {
@@ -666,53 +1036,123 @@ impl<'a> Parser<'a> {
         self.accept(Token::If, "Internal error at if")?;
 
         let cond = self.expression()?;
-        let then = self.expression()?;
-        let otherwise = if self.match_token(Token::Else) {
-            Some(self.expression()?)
-        } else {
-            None
-        };
+        let pattern = self.opt_let_pattern(cond)?;
+        let mut then = self.expression()?;
+        let mut otherwise = self.opt_else()?;
+
+        // A loop directly in the then branch should not take the else branch of the if
+        if otherwise.is_none()
+            && let Expr::While(_, _, loop_else, _)
+            | Expr::WhileLet(_, _, _, loop_else, _)
+            | Expr::For(_, _, _, loop_else, _) = then.node.as_mut()
+        {
+            otherwise = loop_else.take();
+        }
 
+        let if_expr = match pattern {
+            Either::Left(cond) => Expr::If(cond, then, otherwise),
+            Either::Right((lvalue, matched)) => Expr::IfLet(lvalue, matched, then, otherwise),
+        };
         let end = *self.peek_last_end_loc()?;
-        Some(ExprNode::new(Expr::If(cond, then, otherwise), start, end))
+        Some(ExprNode::new(if_expr, start, end))
+    }
+
+    /// Converts the condition of an if or while to a pattern, if it is followed by ':='
+    ///
+    /// Returns either the unchanged condition, or the pattern and the expression to match against it.
+    fn opt_let_pattern(&mut self, cond: ExprNode) -> Option<Either<ExprNode, (LValue, ExprNode)>> {
+        if self.match_token(Token::ColonEq) {
+            let lvalue = self.expr_to_lvalue(cond, true)?;
+            let matched = self.expression()?;
+            Some(Either::Right((lvalue, matched)))
+        } else {
+            Some(Either::Left(cond))
+        }
     }
 
     fn accept_block(&mut self) -> Option<ExprNode> {
         let start = *self.peek_start_loc();
         self.accept(Token::LBrace, "Internal error at block")?;
 
-        let stmts = self.statements(Token::RBrace).ok()?;
+        // Keep the valid statements of the block, the invalid ones are already reported
+        let stmts = self
+            .statements(Token::RBrace)
+            .unwrap_or_else(|partial| partial);
 
         let end = *self.peek_last_end_loc()?;
         self.accept(Token::RBrace, "Need to close block with '}'")?;
         Some(ExprNode::new(Expr::Block(stmts), start, end))
     }
 
-    fn accept_while(&mut self) -> Option<ExprNode> {
+    fn accept_labeled_loop(&mut self) -> Option<ExprNode> {
+        let start = *self.peek_start_loc();
+        let label = self.match_label();
+        self.accept(Token::Colon, "Expect ':' after loop label")?;
+
+        let mut expr = match self.peek() {
+            Token::While => self.accept_while(label)?,
+            Token::For => self.accept_for(label)?,
+            _ => {
+                self.error("Expect a loop after a label");
+                return None;
+            }
+        };
+        expr.start_loc = start;
+        Some(expr)
+    }
+
+    fn accept_while(&mut self, label: Option<String>) -> Option<ExprNode> {
         let start = *self.peek_start_loc();
         self.accept(Token::While, "Internal error at while")?;
 
         let cond = self.expression()?;
+        let pattern = self.opt_let_pattern(cond)?;
         let repeat = self.expression()?;
+        let otherwise = self.opt_else()?;
 
+        let while_expr = match pattern {
+            Either::Left(cond) => Expr::While(cond, repeat, otherwise, label),
+            Either::Right((lvalue, matched)) => {
+                Expr::WhileLet(lvalue, matched, repeat, otherwise, label)
+            }
+        };
         let end = *self.peek_last_end_loc()?;
-        Some(ExprNode::new(Expr::While(cond, repeat), start, end))
+        Some(ExprNode::new(while_expr, start, end))
     }
 
-    fn accept_for(&mut self) -> Option<ExprNode> {
+    fn accept_for(&mut self, label: Option<String>) -> Option<ExprNode> {
         let start = *self.peek_start_loc();
         self.accept(Token::For, "Internal error at for")?;
 
-        let lvalue = self.lvalue(true)?;
+        // The "in" separates the lvalue from the iterable, so it is not a membership test here
+        self.membership_allowed = false;
+        let lvalue = self.lvalue(true);
+        self.membership_allowed = true;
+        let lvalue = lvalue?;
+
         self.accept(
             Token::Identifier("in".to_string()),
             "Expect \"in\" to follow the lvalue in a for expression",
         )?;
         let iterable = self.expression()?;
         let body = self.expression()?;
+        let otherwise = self.opt_else()?;
 
         let end = *self.peek_last_end_loc()?;
-        Some(ExprNode::new(Expr::For(lvalue, iterable, body), start, end))
+        Some(ExprNode::new(
+            Expr::For(lvalue, iterable, body, otherwise, label),
+            start,
+            end,
+        ))
+    }
+
+    /// Parses an optional else branch
+    fn opt_else(&mut self) -> Option<Option<ExprNode>> {
+        if self.match_token(Token::Else) {
+            Some(Some(self.expression()?))
+        } else {
+            Some(None)
+        }
     }
 
     fn accept_match(&mut self) -> Option<ExprNode> {
@@ -749,8 +1189,15 @@ impl<'a> Parser<'a> {
     }
 
     /// Should accept the macro, and expand it as necessary
+    ///
+    /// Expression macro invocations are not implemented, as if naively implemented they would
+    /// clash with a statement macro invocation beginning a line. So they are reported as errors.
     fn accept_expr_macro_invocation(&mut self) -> Option<ExprNode> {
-        todo!("Expression macro invocations not implemented. If naively implemented, they would clash with a statement macro invocation beginning a line");
+        let start = *self.peek_start_loc();
+        let end = *self.peek_end_loc();
+        self.error("Macros can only be invoked at the start of a statement");
+        self.take();
+        some_node(Expr::Invalid, start, end)
 
         // let _start = *self.peek_start_loc();
         // let Token::MacroInvocation(name) = self.take().clone() else {
@@ -818,25 +1265,68 @@ impl ExprNode {
         AstNode::new(expr, start_loc, end_loc)
     }
 
-    pub fn conv_to_lvalue(self, declaration: bool) -> Result<LValue, String> {
+    pub(crate) fn conv_to_lvalue(
+        self,
+        declaration: bool,
+        parser: &Parser,
+    ) -> Result<LValue, String> {
         let ExprNode {
             box node,
             start_loc: start,
             end_loc: end,
+            id,
         } = self;
         match node {
             Expr::IndexInto(expr_node, index) if !declaration => {
                 Ok(LValue::Index(expr_node, index))
             }
             Expr::IndexInto(_, _) => Err("Cannot index into a value in a declaration".to_string()),
+            Expr::Var(id) if id == "_" => Ok(LValue::Wildcard),
+            Expr::Var(id) if declaration && parser.unit_variants.contains(&id) => {
+                Ok(LValue::Variant(id, vec![]))
+            }
             Expr::Var(id) => Ok(LValue::Var(id)),
-            Expr::Tuple(exprs) => {
+            Expr::Tuple(exprs) | Expr::List(ListContent::Exprs(exprs)) => {
                 let lvalues = exprs
                     .into_iter()
-                    .map(|expr| expr.conv_to_lvalue(declaration))
+                    .map(|expr| match *expr.node {
+                        Expr::Rest(Some(name)) if name != "_" => {
+                            Ok(LValue::Rest(Box::new(LValue::Var(name))))
+                        }
+                        Expr::Rest(_) => Ok(LValue::Rest(Box::new(LValue::Wildcard))),
+                        _ => expr.conv_to_lvalue(declaration, parser),
+                    })
                     .collect::<Result<Vec<LValue>, String>>()?;
+
+                let rests = lvalues
+                    .iter()
+                    .filter(|lvalue| matches!(lvalue, LValue::Rest(_)))
+                    .count();
+                if rests > 1 {
+                    return Err("Cannot have more than one rest pattern in a tuple".to_string());
+                }
                 Ok(LValue::Tuple(lvalues))
             }
+            Expr::Rest(_) => {
+                Err("A rest pattern can only be used directly within a tuple or list".to_string())
+            }
+            Expr::Call(
+                ExprNode {
+                    node: box Expr::Var(name),
+                    ..
+                },
+                args,
+            ) => {
+                let fields = args
+                    .into_iter()
+                    .map(|arg| arg.conv_to_lvalue(declaration, parser))
+                    .collect::<Result<Vec<LValue>, String>>()?;
+                Ok(LValue::Variant(name, fields))
+            }
+            Expr::Block(_) => match parser.partial_calls.get(&id) {
+                Some(call) => call.clone().conv_to_lvalue(declaration, parser),
+                None => Err("Cannot convert block to an lvalue.".to_string()),
+            },
             Expr::Int(x) => Ok(LValue::Constant(ExprNode::new(Expr::Int(x), start, end))),
             Expr::Float(x) => Ok(LValue::Constant(ExprNode::new(Expr::Float(x), start, end))),
             Expr::Bool(x) => Ok(LValue::Constant(ExprNode::new(Expr::Bool(x), start, end))),
@@ -862,10 +1352,13 @@ impl Expr {
         match self {
             Expr::Call(_, _) => "call",
             Expr::IndexInto(_, _) => "index",
+            Expr::SafeIndexInto(_, _) => "safe index",
             Expr::Binary(_, _, _) => "binary",
+            Expr::ChainedComparison(_, _) => "chained comparison",
             Expr::Unary(_, _) => "unary",
             Expr::Logical(_, _, _) => "logical",
             Expr::Assign(_, _) => "assign",
+            Expr::OpAssign(_, _, _) => "compound assign",
             Expr::Var(_) => "var",
             Expr::Int(_) => "int",
             Expr::Float(_) => "float",
@@ -873,16 +1366,23 @@ impl Expr {
             Expr::String(_) => "string",
             Expr::Block(_) => "block",
             Expr::If(_, _, _) => "if",
-            Expr::While(_, _) => "while",
-            Expr::For(_, _, _) => "for",
-            Expr::Break => "break",
-            Expr::Continue => "continue",
+            Expr::IfLet(_, _, _, _) => "if let",
+            Expr::While(_, _, _, _) => "while",
+            Expr::WhileLet(_, _, _, _, _) => "while let",
+            Expr::For(_, _, _, _, _) => "for",
+            Expr::Break(_, _) => "break",
+            Expr::Continue(_) => "continue",
             Expr::Return(_) => "return",
             Expr::Nil => "nil",
             Expr::List(_) => "list",
+            Expr::Dict(_) => "dict",
+            Expr::Set(_) => "set",
             Expr::Tuple(_) => "tuple",
-            Expr::FunctionDefinition(_, _, _) => "func_def",
+            Expr::FunctionDefinition(_, _, _, _) => "func_def",
             Expr::Match(_, _) => "match",
+            Expr::Rest(_) => "rest",
+            Expr::EnumVariant(_, _, _) => "enum variant",
+            Expr::Invalid => "invalid",
         }
     }
 }
@@ -907,6 +1407,11 @@ impl FromToken for BinOper {
             Token::UpArr => Some(BinOper::Pow),
             Token::Percent => Some(BinOper::Mod),
             Token::DoublePlus => Some(BinOper::Append),
+            Token::Ampersand => Some(BinOper::BitAnd),
+            Token::Bar => Some(BinOper::BitOr),
+            Token::Xor => Some(BinOper::BitXor),
+            Token::DoubleLt => Some(BinOper::LShift),
+            Token::TripleGt => Some(BinOper::RShift),
             _ => None,
         }
     }
@@ -917,6 +1422,7 @@ impl FromToken for UnOper {
         match token {
             Token::Minus => Some(UnOper::Sub),
             Token::Bang => Some(UnOper::Not),
+            Token::Tilde => Some(UnOper::BitNot),
             _ => None,
         }
     }
@@ -927,6 +1433,7 @@ impl FromToken for LogicalOper {
         match token {
             Token::And => Some(LogicalOper::And),
             Token::Or => Some(LogicalOper::Or),
+            Token::DoubleQuestion => Some(LogicalOper::Coalesce),
             _ => None,
         }
     }
@@ -939,7 +1446,7 @@ fn some_node<T: Debug>(grammar: T, start: CodeLoc, end: CodeLoc) -> Option<AstNo
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{code_loc::CodeLoc, errors::ErrorReporter, scanner::TokenInfo};
+    use crate::{code_loc::CodeLoc, errors::ErrorReporter, scanner::TokenInfo, Stmt};
 
     fn fake_token(token: Token) -> TokenInfo {
         TokenInfo {
@@ -1176,7 +1683,8 @@ mod tests {
                     fake_node(Expr::Int(2)),
                     BinOper::Add,
                     fake_node(Expr::Var("x".to_string()))
-                )
+                ),
+                None
             ))
         );
 
@@ -1212,8 +1720,128 @@ mod tests {
                         fake_node(Expr::Var("x".to_string())),
                         fake_node(Expr::Var("y".to_string()))
                     ]
-                ))
+                )),
+                None
             ))
         );
     }
+
+    #[test]
+    fn partial_ast() {
+        let code = "x := ;\nf := \\a -> {\n\ty := a + );\n\ta\n};\nz := 1;";
+        let (stmts, errors) = crate::parse_with_errors("test", code);
+
+        // Only the first error of each statement is reported
+        let lines: Vec<usize> = errors.iter().map(|error| error.loc.line()).collect();
+        assert_eq!(lines, vec![1, 3]);
+
+        // The code around the errors is kept, with error nodes in place of what failed to parse
+        let inits: Vec<&Expr> = stmts
+            .stmts
+            .iter()
+            .map(|stmt| match stmt.node.as_ref() {
+                Stmt::Decl(_, Some(init)) => init.node.as_ref(),
+                other => panic!("Expected a declaration, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(inits.len(), 3);
+        assert_eq!(inits[0], &Expr::Invalid);
+        assert_eq!(inits[2], &Expr::Int(1));
+
+        let Expr::FunctionDefinition(_, _, body, _) = inits[1] else {
+            panic!("Expected a function definition")
+        };
+        let Expr::Block(block) = body.node.as_ref() else {
+            panic!("Expected the function body to be a block")
+        };
+        assert_eq!(block.stmts.len(), 2);
+        assert_eq!(block.stmts[0].node.as_ref(), &Stmt::Invalid);
+        assert!(block.output);
+    }
+
+    #[test]
+    fn logical_assignment_ids() {
+        let (stmts, errors) = crate::parse_with_errors("test", "xs[i] or= 1;");
+        assert!(errors.is_empty());
+
+        // The target is both assigned and read, but each of its nodes must have its own id
+        let Stmt::Expr(expr) = stmts.stmts[0].node.as_ref() else {
+            panic!("Expected an expression statement")
+        };
+        let Expr::Assign(LValue::Index(assigned, Index::At(assigned_at)), logical) =
+            expr.node.as_ref()
+        else {
+            panic!("Expected an assignment to an index")
+        };
+        let Expr::Logical(read, LogicalOper::Or, _) = logical.node.as_ref() else {
+            panic!("Expected an or expression")
+        };
+        let Expr::IndexInto(read, Index::At(read_at)) = read.node.as_ref() else {
+            panic!("Expected an indexing")
+        };
+
+        assert_eq!(assigned, read);
+        assert_ne!(assigned.id, read.id);
+        assert_eq!(assigned_at, read_at);
+        assert_ne!(assigned_at.id, read_at.id);
+    }
+
+    #[test]
+    fn blocks_with_colons() {
+        // Colons within the first expression or statement of a block do not make it a dict
+        let codes = [
+            "fn f() -> { g := \\x: int -> x + 1; g(2) };",
+            "{ if true {1} else {2} x: int := 0; x };",
+            "{ xs[1:] };",
+        ];
+        for code in codes {
+            let (stmts, errors) = crate::parse_with_errors("test", code);
+            assert!(errors.is_empty(), "{code}: {errors:?}");
+            if let Stmt::Expr(expr) = stmts.stmts[0].node.as_ref() {
+                assert!(!matches!(expr.node.as_ref(), Expr::Dict(_)), "{code}");
+            }
+        }
+
+        let (stmts, errors) = crate::parse_with_errors("test", "{:}; {\\x: int -> x: 1};");
+        assert!(errors.is_empty());
+        for stmt in &stmts.stmts {
+            let Stmt::Expr(expr) = stmt.node.as_ref() else {
+                panic!("Expected an expression statement")
+            };
+            assert!(matches!(expr.node.as_ref(), Expr::Dict(_)));
+        }
+    }
+
+    #[test]
+    fn partial_ast_unterminated_string() {
+        // The unterminated quote is skipped, leaving 'Two!' as a macro invocation within the call
+        let code = r#"match [1,2,3,4] {
+	(a, b) -> print("Two!)),
+	(a, b, c) -> print("Three!"),
+	(a, b, c, d) -> print("Four!"),
+	(a, b, c, d, e) -> print("Five!"),
+	one -> print(one),
+};
+
+match [1,2,3,4] {
+	(a, b) -> print("Two!"),
+	(a, b, c) -> print("Three!"),
+	(a, b, c, d, e) -> print("Five!"),
+	otherwise -> print("otherwise ", otherwise),
+};"#;
+        let (stmts, errors) = crate::parse_with_errors("test", code);
+
+        let lines: Vec<usize> = errors.iter().map(|error| error.loc.line()).collect();
+        assert_eq!(lines, vec![2, 2, 7]);
+        assert!(errors[1].message.contains("Macros can only be invoked"));
+
+        // The following statement is still parsed
+        let Some(Stmt::Expr(last)) = stmts.stmts.last().map(|stmt| stmt.node.as_ref()) else {
+            panic!("Expected an expression statement")
+        };
+        let Expr::Match(_, arms) = last.node.as_ref() else {
+            panic!("Expected a match")
+        };
+        assert_eq!(arms.len(), 4);
+    }
 }
@@ -9,18 +9,24 @@ pub fn gen_functions_doc(stmts: &Stmts) -> String {
             Stmt::Decl(
                 LValue::Var(func_name),
                 Some(AstNode {
-                    node: box Expr::FunctionDefinition(_, params, _),
+                    node: box Expr::FunctionDefinition(_, params, _, return_type),
                     start_loc: _,
                     end_loc: _,
+                    id: _,
                 }),
             ) => {
+                let returns = match return_type {
+                    Some(annotated) => format!(" -> {annotated}"),
+                    None => String::new(),
+                };
                 docs.push_str(&format!(
-                    "fn {func_name}{};\n",
+                    "fn {func_name}{}{returns};\n",
                     pretty_print_lvalues(params)
                 ));
             }
             Stmt::Decl(_, _) => {}
             Stmt::Expr(_) => {}
+            Stmt::Assert(_, _, _) => {}
             Stmt::Invalid => {}
         }
     }
@@ -36,6 +42,13 @@ fn pretty_print_lvalues(lvalues: &[LValue]) -> String {
         match lvalue {
             LValue::Var(name) => doc.push_str(name),
             LValue::Tuple(lvalues) => doc.push_str(&pretty_print_lvalues(lvalues)),
+            LValue::Wildcard => doc.push('_'),
+            LValue::Rest(box LValue::Var(name)) => doc.push_str(&format!("..{name}")),
+            LValue::Rest(_) => doc.push_str(".."),
+            LValue::Typed(lvalue, annotated) => {
+                let inner = pretty_print_lvalues(std::slice::from_ref(lvalue.as_ref()));
+                doc.push_str(&format!("{}: {annotated}", &inner[1..inner.len() - 1]));
+            }
 
             // TODO: Make this better
             _ => panic!("Strange parameter enountered!"),
@@ -0,0 +1,163 @@
+use crate::{AstNode, Expr, ExprNode, Index, LValue, ListContent, Slice, Stmt, StmtNode, Stmts};
+
+/// Copies an expression, giving every node in the copy a new id
+///
+/// Used when desugaring duplicates a part of the code, as the ids must be unique among all
+/// nodes for the results of analysis stored by them to be correct.
+pub(crate) fn fresh_copy(expr: &ExprNode) -> ExprNode {
+    refresh_expr(expr.clone())
+}
+
+fn refresh<T: std::fmt::Debug>(node: AstNode<T>, refresh_node: impl FnOnce(T) -> T) -> AstNode<T> {
+    let AstNode {
+        box node,
+        start_loc,
+        end_loc,
+        id: _,
+    } = node;
+    AstNode::new(refresh_node(node), start_loc, end_loc)
+}
+
+fn refresh_expr(expr: ExprNode) -> ExprNode {
+    refresh(expr, |node| match node {
+        Expr::Call(callee, args) => Expr::Call(refresh_expr(callee), refresh_exprs(args)),
+        Expr::IndexInto(base, index) => Expr::IndexInto(refresh_expr(base), refresh_index(index)),
+        Expr::SafeIndexInto(base, index) => {
+            Expr::SafeIndexInto(refresh_expr(base), refresh_index(index))
+        }
+        Expr::Binary(left, op, right) => Expr::Binary(refresh_expr(left), op, refresh_expr(right)),
+        Expr::ChainedComparison(first, rest) => Expr::ChainedComparison(
+            refresh_expr(first),
+            rest.into_iter()
+                .map(|(op, expr)| (op, refresh_expr(expr)))
+                .collect(),
+        ),
+        Expr::Unary(op, expr) => Expr::Unary(op, refresh_expr(expr)),
+        Expr::Logical(left, op, right) => {
+            Expr::Logical(refresh_expr(left), op, refresh_expr(right))
+        }
+        Expr::Assign(lvalue, expr) => Expr::Assign(refresh_lvalue(lvalue), refresh_expr(expr)),
+        Expr::OpAssign(lvalue, op, expr) => {
+            Expr::OpAssign(refresh_lvalue(lvalue), op, refresh_expr(expr))
+        }
+        Expr::Block(stmts) => Expr::Block(refresh_stmts(stmts)),
+        Expr::If(cond, then, otherwise) => Expr::If(
+            refresh_expr(cond),
+            refresh_expr(then),
+            otherwise.map(refresh_expr),
+        ),
+        Expr::IfLet(lvalue, expr, then, otherwise) => Expr::IfLet(
+            refresh_lvalue(lvalue),
+            refresh_expr(expr),
+            refresh_expr(then),
+            otherwise.map(refresh_expr),
+        ),
+        Expr::While(cond, body, otherwise, label) => Expr::While(
+            refresh_expr(cond),
+            refresh_expr(body),
+            otherwise.map(refresh_expr),
+            label,
+        ),
+        Expr::WhileLet(lvalue, expr, body, otherwise, label) => Expr::WhileLet(
+            refresh_lvalue(lvalue),
+            refresh_expr(expr),
+            refresh_expr(body),
+            otherwise.map(refresh_expr),
+            label,
+        ),
+        Expr::For(lvalue, collection, body, otherwise, label) => Expr::For(
+            refresh_lvalue(lvalue),
+            refresh_expr(collection),
+            refresh_expr(body),
+            otherwise.map(refresh_expr),
+            label,
+        ),
+        Expr::Break(label, expr) => Expr::Break(label, expr.map(refresh_expr)),
+        Expr::Return(expr) => Expr::Return(expr.map(refresh_expr)),
+        Expr::List(ListContent::Exprs(exprs)) => {
+            Expr::List(ListContent::Exprs(refresh_exprs(exprs)))
+        }
+        Expr::List(ListContent::Range(slice)) => {
+            Expr::List(ListContent::Range(refresh_slice(slice)))
+        }
+        Expr::Dict(entries) => Expr::Dict(
+            entries
+                .into_iter()
+                .map(|(key, value)| (refresh_expr(key), refresh_expr(value)))
+                .collect(),
+        ),
+        Expr::Set(exprs) => Expr::Set(refresh_exprs(exprs)),
+        Expr::Tuple(exprs) => Expr::Tuple(refresh_exprs(exprs)),
+        Expr::FunctionDefinition(name, params, body, returns) => Expr::FunctionDefinition(
+            name,
+            params.into_iter().map(refresh_lvalue).collect(),
+            refresh_expr(body),
+            returns,
+        ),
+        Expr::Match(matched, arms) => Expr::Match(
+            refresh_expr(matched),
+            arms.into_iter()
+                .map(|(lvalue, arm)| (refresh_lvalue(lvalue), refresh_expr(arm)))
+                .collect(),
+        ),
+        leaf @ (Expr::Var(_)
+        | Expr::Int(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::String(_)
+        | Expr::Continue(_)
+        | Expr::Nil
+        | Expr::Rest(_)
+        | Expr::EnumVariant(_, _, _)
+        | Expr::Invalid) => leaf,
+    })
+}
+
+fn refresh_exprs(exprs: Vec<ExprNode>) -> Vec<ExprNode> {
+    exprs.into_iter().map(refresh_expr).collect()
+}
+
+fn refresh_index(index: Index) -> Index {
+    match index {
+        Index::At(expr) => Index::At(refresh_expr(expr)),
+        Index::Slice(slice) => Index::Slice(refresh_slice(slice)),
+    }
+}
+
+fn refresh_slice(Slice { start, stop, step }: Slice) -> Slice {
+    Slice {
+        start: start.map(refresh_expr),
+        stop: stop.map(refresh_expr),
+        step: step.map(refresh_expr),
+    }
+}
+
+fn refresh_lvalue(lvalue: LValue) -> LValue {
+    match lvalue {
+        LValue::Index(base, index) => LValue::Index(refresh_expr(base), refresh_index(index)),
+        LValue::Tuple(lvalues) => LValue::Tuple(lvalues.into_iter().map(refresh_lvalue).collect()),
+        LValue::Constant(expr) => LValue::Constant(refresh_expr(expr)),
+        LValue::Rest(lvalue) => LValue::Rest(Box::new(refresh_lvalue(*lvalue))),
+        LValue::Variant(name, fields) => {
+            LValue::Variant(name, fields.into_iter().map(refresh_lvalue).collect())
+        }
+        LValue::Typed(lvalue, typ) => LValue::Typed(Box::new(refresh_lvalue(*lvalue)), typ),
+        leaf @ (LValue::Var(_) | LValue::Wildcard) => leaf,
+    }
+}
+
+fn refresh_stmts(Stmts { stmts, output }: Stmts) -> Stmts {
+    let stmts = stmts.into_iter().map(refresh_stmt).collect();
+    Stmts { stmts, output }
+}
+
+fn refresh_stmt(stmt: StmtNode) -> StmtNode {
+    refresh(stmt, |node| match node {
+        Stmt::Decl(lvalue, init) => Stmt::Decl(refresh_lvalue(lvalue), init.map(refresh_expr)),
+        Stmt::Expr(expr) => Stmt::Expr(refresh_expr(expr)),
+        Stmt::Assert(cond, message, source) => {
+            Stmt::Assert(refresh_expr(cond), message.map(refresh_expr), source)
+        }
+        Stmt::Invalid => Stmt::Invalid,
+    })
+}
@@ -1,9 +1,10 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
-use super::{AstNode, Parser};
+use super::{AstNode, NodeId, Parser};
 use crate::code_loc::CodeLoc;
 use crate::errors::ErrorReporter;
 use crate::scanner::{Token, TokenInfo};
+use crate::statements;
 
 // Module with different helper functions for the parsing.
 
@@ -14,6 +15,7 @@ impl<T: Debug> AstNode<T> {
             node: Box::new(node),
             start_loc,
             end_loc,
+            id: NodeId::fresh(),
         }
     }
 }
@@ -30,15 +32,27 @@ impl<'a> Parser<'a> {
         tokens: &'a [TokenInfo],
         error_reporter: &'a mut ErrorReporter,
     ) -> Self {
+        let tokens = Vec::from_iter(tokens.iter());
+        let unit_variants = statements::unit_variants(&tokens);
         Self {
             scriptname: filename,
-            tokens: Vec::from_iter(tokens.iter()),
+            tokens,
             current: 0,
             error_reporter,
+            membership_allowed: true,
+            panicking: false,
+            unit_variants,
+            partial_calls: HashMap::new(),
         }
     }
 
     pub fn error(&mut self, str: &str) {
+        // Only the first error of a statement is reported, as the rest are likely caused by it
+        if self.panicking {
+            return;
+        }
+        self.panicking = true;
+
         // Should probably have the error reporter outside of the parser...
         let loc = *self.peek_start_loc();
         let error_string = format!("{} at '{}' {}", loc.line(), &self.peek_string(), str);
@@ -53,9 +67,10 @@ impl<'a> Parser<'a> {
                 return;
             }
 
-            // If the next token is the start of a new statement (declaration) we can also return
+            // If the next token is the start of a new statement (declaration) we can also return,
+            // or if it closes the block so that the rest of the enclosing statement can be parsed
             match self.peek() {
-                Token::Fn => return,
+                Token::Fn | Token::RBrace => return,
                 _ => continue,
             }
         }
@@ -1,5 +1,10 @@
 #![feature(box_patterns, iterator_try_reduce, let_chains)]
 
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 pub use code_loc::{CodeLoc, CodeRange};
 use errors::ErrorReporter;
 use scanner::TokenInfo;
@@ -8,16 +13,21 @@ mod code_loc;
 mod errors;
 mod expressions;
 mod fn_doc_gen;
+mod fresh_ids;
 mod generics;
 mod macros;
+mod partial_application;
 mod scanner;
 mod statements;
+mod types;
 
+pub use errors::ParseError;
 pub use expressions::{
     BinOper, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, Slice, UnOper,
 };
 pub use fn_doc_gen::gen_functions_doc;
 pub use statements::{Stmt, StmtNode, Stmts};
+pub use types::Type;
 
 // Each node in the AST is some branch/leaf wrapped in this extra info
 #[derive(Debug, Clone)]
@@ -25,20 +35,48 @@ pub struct AstNode<T> {
     pub node: Box<T>,
     pub start_loc: CodeLoc,
     pub end_loc: CodeLoc, // Not including last char. Should we change?
+    pub id: NodeId,
+}
+
+/// Identifies a node in the AST, so that analysis results can be stored on the side
+///
+/// The id is kept when the node is cloned or moved, and is unique among all parsed nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Creates an id which no other node has
+    fn fresh() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 pub fn parse(scriptname: &str, code: &str) -> Option<Stmts> {
+    let (ast, errors) = parse_with_errors(scriptname, code);
+    if errors.is_empty() {
+        Some(ast)
+    } else {
+        for error in errors {
+            eprintln!("{error}");
+        }
+        None
+    }
+}
+
+/// Parses as much of the code as possible, returning all errors found instead of printing them
+///
+/// Code which could not be parsed is kept in the AST as `Stmt::Invalid` and `Expr::Invalid`
+/// nodes, so the AST is only valid to run if there are no errors.
+pub fn parse_with_errors(scriptname: &str, code: &str) -> (Stmts, Vec<ParseError>) {
     let mut error_reporter = errors::ErrorReporter::new();
     let tokens = scanner::tokenize(code, scriptname, &mut error_reporter);
-    if error_reporter.had_error {
-        return None;
-    }
 
     let mut parser = Parser::new(scriptname, &tokens, &mut error_reporter);
-    match parser.statements(crate::scanner::Token::Eof).ok() {
-        Some(ast) if !error_reporter.had_error => Some(ast),
-        _otherwise => None,
-    }
+    let ast = parser
+        .statements(crate::scanner::Token::Eof)
+        .unwrap_or_else(|partial| partial);
+    (ast, error_reporter.errors)
 }
 
 // All submodules will add some functionality to this, like parsing expressions
@@ -47,6 +85,14 @@ struct Parser<'a> {
     tokens: Vec<&'a TokenInfo>,
     current: usize,
     error_reporter: &'a mut ErrorReporter,
+    /// If `in` can be used as an infix operator, which it cannot in the lvalue of a for loop
+    membership_allowed: bool,
+    /// If the current statement already has an error, so that errors following from it are not reported
+    panicking: bool,
+    /// Enum variants without fields, which are matched by their bare name in patterns
+    unit_variants: HashSet<String>,
+    /// Calls with placeholders by the id of their partial application, as they can be patterns
+    partial_calls: HashMap<NodeId, ExprNode>,
 }
 
 impl<T> AstNode<T> {
@@ -1,6 +1,6 @@
 use std::fs::read;
 
-use crate::{parse, scanner::Token, Parser, StmtNode};
+use crate::{code_loc::CodeLoc, parse, scanner::Token, Parser, Stmt, StmtNode};
 
 impl<'a> Parser<'a> {
     /// Parse a whole other file to a sequence of statements
@@ -13,23 +13,14 @@ impl<'a> Parser<'a> {
         };
         let path = path.clone();
 
-        // TODO: This is a really bad way to do this. Stdlib should always be included for the vm in some nice way...
-        let included_bytes = match path.as_str() {
-            "stdlib" => include_str!("../../vm/stdlib.zote").as_bytes().to_vec(),
-            otherwise => {
-                let Ok(included_bytes) = read(otherwise) else {
-                    self.error(&format!("Could not open {path} for including code"));
-                    return None;
-                };
-                included_bytes
+        let included_code = match read_included(&path) {
+            Ok(included_code) => included_code,
+            Err(message) => {
+                self.error(&message);
+                return None;
             }
         };
 
-        let Ok(included_code) = String::from_utf8(included_bytes) else {
-            self.error(&format!("Could not read {path} as utf8 for including code"));
-            return None;
-        };
-
         self.accept(
             Token::RPar,
             "Expect parenthesis after file path in 'include!'",
@@ -45,4 +36,60 @@ impl<'a> Parser<'a> {
             Some(statements.stmts)
         }
     }
+
+    /// Parse an assertion, keeping the source text of the condition for error messages
+    pub(crate) fn macro_assert_statement(&mut self, start: CodeLoc) -> Option<StmtNode> {
+        self.accept(Token::LPar, "Expect parenthesis after 'assert!'")?;
+
+        let cond_start = self.current;
+        let cond = self.expression()?;
+        let source = self.source_text(cond_start, self.current);
+
+        let message = if self.match_token(Token::Comma) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.accept(
+            Token::RPar,
+            "Expect parenthesis after the arguments to 'assert!'",
+        )?;
+        let end = *self.peek_last_end_loc().unwrap();
+
+        Some(StmtNode::new(
+            Stmt::Assert(cond, message, source),
+            start,
+            end,
+        ))
+    }
+
+    /// Reconstructs the source text of the tokens in the range [from, to)
+    fn source_text(&self, from: usize, to: usize) -> String {
+        let mut text = String::new();
+        for (ind, info) in self.tokens[from..to].iter().enumerate() {
+            if ind > 0 && info.seperated {
+                text.push(' ');
+            }
+            text.push_str(&info.string);
+        }
+        text
+    }
+}
+
+/// Reads the code of an included file, or of the standard library
+pub(crate) fn read_included(path: &str) -> Result<String, String> {
+    // TODO: This is a really bad way to do this. Stdlib should always be included for the vm in some nice way...
+    let included_bytes = match path {
+        "stdlib" => include_str!("../../vm/stdlib.zote").as_bytes().to_vec(),
+        otherwise => {
+            let Ok(included_bytes) = read(otherwise) else {
+                return Err(format!("Could not open {path} for including code"));
+            };
+            included_bytes
+        }
+    };
+
+    String::from_utf8(included_bytes)
+        .map_err(|_| format!("Could not read {path} as utf8 for including code"))
 }
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::{code_loc::CodeLoc, Expr, ExprNode, LValue, NodeId, Stmt, StmtNode, Stmts};
+
+/// Checks if the expression is the placeholder '_', used for partial application
+pub(crate) fn is_placeholder(expr: &ExprNode) -> bool {
+    matches!(expr.node.as_ref(), Expr::Var(name) if name == "_")
+}
+
+/// Converts all calls with placeholder arguments in a chain of calls and indexing to partial applications
+///
+/// So `f(_, 2)` becomes a closure taking one argument. The original calls are kept in
+/// `partial_calls` by the id of their partial application, as they can also be patterns
+/// such as `Rect(w, _)`.
+pub(crate) fn resolve_placeholders(
+    expr: ExprNode,
+    partial_calls: &mut HashMap<NodeId, ExprNode>,
+) -> ExprNode {
+    let ExprNode {
+        box node,
+        start_loc: start,
+        end_loc: end,
+        id: _,
+    } = expr;
+
+    match node {
+        Expr::Call(callee, args) => {
+            let callee = resolve_placeholders(callee, partial_calls);
+            if args.iter().any(is_placeholder) {
+                let call = ExprNode::new(Expr::Call(callee.clone(), args.clone()), start, end);
+                let partial = partial_application(callee, args, start, end);
+                partial_calls.insert(partial.id, call);
+                partial
+            } else {
+                ExprNode::new(Expr::Call(callee, args), start, end)
+            }
+        }
+        Expr::IndexInto(base, index) => ExprNode::new(
+            Expr::IndexInto(resolve_placeholders(base, partial_calls), index),
+            start,
+            end,
+        ),
+        Expr::SafeIndexInto(base, index) => ExprNode::new(
+            Expr::SafeIndexInto(resolve_placeholders(base, partial_calls), index),
+            start,
+            end,
+        ),
+        other => ExprNode::new(other, start, end),
+    }
+}
+
+/// Creates a closure calling the callee, with its parameters at the placeholder positions
+///
+/// The callee and the other arguments are evaluated once, when the closure is created.
+/// It is syntactic sugar for a block such as `{ f := callee; a := arg; \x -> f(x, a) }`,
+/// where the variables are illegal @ names.
+fn partial_application(
+    callee: ExprNode,
+    args: Vec<ExprNode>,
+    start: CodeLoc,
+    end: CodeLoc,
+) -> ExprNode {
+    let hidden_var = |name: &str| ExprNode::new(Expr::Var(name.to_string()), start, end);
+    let hidden_decl = |name: &str, expr: ExprNode| {
+        StmtNode::new(Stmt::Decl(LValue::Var(name.to_string()), Some(expr)), start, end)
+    };
+
+    let callee_name = "@__partial_callee";
+    let mut stmts = vec![hidden_decl(callee_name, callee)];
+    let mut params = vec![];
+    let mut call_args = vec![];
+
+    for (ind, arg) in args.into_iter().enumerate() {
+        if is_placeholder(&arg) {
+            let name = format!("@__partial_param{}", params.len());
+            call_args.push(hidden_var(&name));
+            params.push(LValue::Var(name));
+        } else {
+            let name = format!("@__partial_arg{ind}");
+            stmts.push(hidden_decl(&name, arg));
+            call_args.push(hidden_var(&name));
+        }
+    }
+
+    let name = format!(
+        "partial application/{} at {}:{}",
+        params.len(),
+        start.line(),
+        start.col()
+    );
+    let call = ExprNode::new(Expr::Call(hidden_var(callee_name), call_args), start, end);
+    let lambda = ExprNode::new(
+        Expr::FunctionDefinition(name, params, call, None),
+        start,
+        end,
+    );
+    stmts.push(StmtNode::new(Stmt::Expr(lambda), start, end));
+
+    ExprNode::new(
+        Expr::Block(Stmts {
+            stmts,
+            output: true,
+        }),
+        start,
+        end,
+    )
+}
@@ -25,9 +25,12 @@ pub enum Token {
     // Invalid(String),
     /// The name of a macro invocation, such as "include!" in "include!(path/to/file)"
     MacroInvocation(String),
+    /// A loop label, such as "outer" in "'outer: for ..." or "break 'outer"
+    Label(String),
 
     // Special constructs
     Struct,
+    Enum,
     Fn,
     If,
     Else,
@@ -48,9 +51,11 @@ pub enum Token {
     RPar,
     LBrace,
     RBrace,
+    HashLBrace,
     LBrack,
     RBrack,
     Dot,
+    DotDot,
     Semicolon,
     Comma,
     Eq,
@@ -82,6 +87,14 @@ pub enum Token {
     // ColonPipe,
     // EqPipe,
     DoublePlus,
+    Ampersand,
+    Bar,
+    Tilde,
+    Xor,
+    DoubleLt,
+    TripleGt,
+    DoubleQuestion,
+    QuestionLBrack,
 }
 
 #[derive(Debug)]
@@ -95,6 +108,11 @@ pub struct TokenInfo {
 
 type Constructor = Box<dyn Fn(&str) -> Token + Sync>;
 lazy_static! {
+    // Labels are scanned before single-quoted strings, as they would otherwise clash
+    // A quote directly after the label makes it a string instead, such as 'done'
+    static ref LABEL_DEF: Regex =
+        Regex::new(r"\A'([\w--\d]\w*)\s*:\s*(for|while)($|[^'\w])").unwrap();
+    static ref LABEL_USE: Regex = Regex::new(r"\A'([\w--\d]\w*)($|[^'\w])").unwrap();
     static ref PATTERNS: Vec<(Regex, Constructor)> = lex_rules![
         (r"[\w--\d]\w*", |str| Token::Identifier(str.to_string())),
         // Not optimal that 001 is scanned as 0 0 1
@@ -105,6 +123,7 @@ lazy_static! {
         (r"//[^\n]*", |str| Token::Comment(str[2..].to_owned())),
         (r"[\w--\d]\w*!", |str| Token::MacroInvocation(str.to_string())),
         (r"struct", |_| Token::Struct),
+        (r"enum", |_| Token::Enum),
         (r"fn", |_| Token::Fn),
         (r"if", |_| Token::If),
         (r"else", |_| Token::Else),
@@ -123,9 +142,11 @@ lazy_static! {
         (r"\)", |_| Token::RPar),
         (r"\{", |_| Token::LBrace),
         (r"\}", |_| Token::RBrace),
+        (r"#\{", |_| Token::HashLBrace),
         (r"\[", |_| Token::LBrack),
         (r"\]", |_| Token::RBrack),
         (r"\.", |_| Token::Dot),
+        (r"\.\.", |_| Token::DotDot),
         (r";", |_| Token::Semicolon),
         (r",", |_| Token::Comma),
         (r"=", |_| Token::Eq),
@@ -154,6 +175,14 @@ lazy_static! {
         // (r":>>", |_| Token::ColonPipe),
         // (r"=>>", |_| Token::EqPipe),
         (r"\+\+", |_| Token::DoublePlus),
+        (r"&", |_| Token::Ampersand),
+        (r"\|", |_| Token::Bar),
+        (r"~", |_| Token::Tilde),
+        (r"xor", |_| Token::Xor),
+        (r"<<", |_| Token::DoubleLt),
+        (r">>>", |_| Token::TripleGt),
+        (r"\?\?", |_| Token::DoubleQuestion),
+        (r"\?\[", |_| Token::QuestionLBrack),
     ];
 }
 
@@ -173,7 +202,11 @@ pub fn tokenize(
             break;
         }
 
-        match parse_token(code, &mut loc, seperated) {
+        let prev_token = tokens.last().map(|info: &TokenInfo| &info.token);
+        let token_info = parse_label(code, &mut loc, seperated, prev_token)
+            .or_else(|| parse_token(code, &mut loc, seperated));
+
+        match token_info {
             // For now just ignore all comments
             Some(token_info) if matches!(token_info.token, Token::Comment(_)) => continue,
             Some(token_info) => tokens.push(token_info),
@@ -243,6 +276,42 @@ fn parse_token(code: &str, loc: &mut CodeLoc, sep: bool) -> Option<TokenInfo> {
         })
 }
 
+/// Parses a loop label, if there is one at the location
+///
+/// A label is either declared right before a loop, or used directly after a break or continue.
+/// Those places are always labels, even if a quote follows later on the line, so strings there
+/// have to be double-quoted.
+fn parse_label(
+    code: &str,
+    loc: &mut CodeLoc,
+    sep: bool,
+    prev_token: Option<&Token>,
+) -> Option<TokenInfo> {
+    let rest = &code[loc.index()..];
+    let name = if let Some(caps) = LABEL_DEF.captures(rest) {
+        caps[1].to_owned()
+    } else if matches!(prev_token, Some(Token::Break | Token::Continue))
+        && let Some(caps) = LABEL_USE.captures(rest)
+    {
+        caps[1].to_owned()
+    } else {
+        return None;
+    };
+
+    let string = format!("'{name}");
+    let start_loc = *loc;
+    loc.adv_col(string.chars().count(), string.len());
+    let end_loc = *loc;
+
+    Some(TokenInfo {
+        token: Token::Label(name),
+        start_loc,
+        end_loc,
+        string,
+        seperated: sep,
+    })
+}
+
 // Want to parse escape sequences properly
 fn parse_string(string: &str) -> String {
     string
@@ -304,6 +373,96 @@ mod tests {
         assert_eq!(&tokens[2].end_loc, &third_end);
     }
 
+    #[test]
+    fn labels() {
+        let mut reporter = ErrorReporter::new();
+        let code = "'outer: for break 'outer; continue 'outer print('x') break 'done'";
+        let tokens = tokenize(code, "test", &mut reporter);
+
+        let expected_tokens = vec![
+            Token::Label("outer".to_string()),
+            Token::Colon,
+            Token::For,
+            Token::Break,
+            Token::Label("outer".to_string()),
+            Token::Semicolon,
+            Token::Continue,
+            Token::Label("outer".to_string()),
+            Token::Identifier("print".to_string()),
+            Token::LPar,
+            Token::String(Rc::new("x".to_string())),
+            Token::RPar,
+            Token::Break,
+            Token::String(Rc::new("done".to_string())),
+            Token::Eof,
+        ];
+        let scanned_tokens: Vec<_> = tokens.iter().map(|info| info.token.clone()).collect();
+        assert_eq!(scanned_tokens, expected_tokens);
+    }
+
+    #[test]
+    fn labels_before_quotes() {
+        let mut reporter = ErrorReporter::new();
+        let code = "'outer: for x in xs { print(' ', x); break 'outer; }";
+        let tokens = tokenize(code, "test", &mut reporter);
+
+        let expected_tokens = vec![
+            Token::Label("outer".to_string()),
+            Token::Colon,
+            Token::For,
+            Token::Identifier("x".to_string()),
+            Token::Identifier("in".to_string()),
+            Token::Identifier("xs".to_string()),
+            Token::LBrace,
+            Token::Identifier("print".to_string()),
+            Token::LPar,
+            Token::String(Rc::new(" ".to_string())),
+            Token::Comma,
+            Token::Identifier("x".to_string()),
+            Token::RPar,
+            Token::Semicolon,
+            Token::Break,
+            Token::Label("outer".to_string()),
+            Token::Semicolon,
+            Token::RBrace,
+            Token::Eof,
+        ];
+        let scanned_tokens: Vec<_> = tokens.iter().map(|info| info.token.clone()).collect();
+        assert_eq!(scanned_tokens, expected_tokens);
+        assert!(!reporter.had_error);
+    }
+
+    #[test]
+    fn strings_resembling_labels() {
+        let mut reporter = ErrorReporter::new();
+        let code = "print('note: for') break 'hi'\n'a: while' 'outer: for x in xs print('hi')";
+        let tokens = tokenize(code, "test", &mut reporter);
+
+        let expected_tokens = vec![
+            Token::Identifier("print".to_string()),
+            Token::LPar,
+            Token::String(Rc::new("note: for".to_string())),
+            Token::RPar,
+            Token::Break,
+            Token::String(Rc::new("hi".to_string())),
+            Token::String(Rc::new("a: while".to_string())),
+            Token::Label("outer".to_string()),
+            Token::Colon,
+            Token::For,
+            Token::Identifier("x".to_string()),
+            Token::Identifier("in".to_string()),
+            Token::Identifier("xs".to_string()),
+            Token::Identifier("print".to_string()),
+            Token::LPar,
+            Token::String(Rc::new("hi".to_string())),
+            Token::RPar,
+            Token::Eof,
+        ];
+        let scanned_tokens: Vec<_> = tokens.iter().map(|info| info.token.clone()).collect();
+        assert_eq!(scanned_tokens, expected_tokens);
+        assert!(!reporter.had_error);
+    }
+
     #[test]
     fn strings() {
         let mut reporter = ErrorReporter::new();
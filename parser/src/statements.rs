@@ -1,7 +1,13 @@
+use std::collections::HashSet;
+
 use either::Either;
 
 use super::{expressions::MAX_ARGS, AstNode, Expr, ExprNode, LValue, Parser};
-use crate::scanner::Token;
+use crate::{
+    errors::ErrorReporter,
+    macros::read_included,
+    scanner::{tokenize, Token, TokenInfo},
+};
 
 pub type StmtNode = AstNode<Stmt>;
 
@@ -15,6 +21,7 @@ pub struct Stmts {
 pub enum Stmt {
     Decl(LValue, Option<ExprNode>),
     Expr(ExprNode),
+    Assert(ExprNode, Option<ExprNode>, String), // The string is the source text of the condition
     Invalid,
 }
 
@@ -61,24 +68,24 @@ impl<'a> Parser<'a> {
 
     // If allow_expr is on, it will match an expression instead of causing error if there is no closing ;
     fn statement(&mut self, terminator: &Token) -> Either<Vec<StmtNode>, ExprNode> {
+        self.panicking = false;
+        let start = *self.peek_start_loc();
         if let Some(nodes) = self.fn_statement(terminator) {
             nodes
         } else {
             // Should we propagate a result to here instead?
             self.synchronize_error();
-            Either::Left(vec![StmtNode::new(
-                Stmt::Invalid,
-                *self.peek_start_loc(),
-                *self.peek_start_loc(),
-            )])
+            // The invalid statement covers the code skipped over
+            let end = *self.peek_last_end_loc().unwrap_or(&start);
+            Either::Left(vec![StmtNode::new(Stmt::Invalid, start, end)])
         }
     }
 
     fn fn_statement(&mut self, terminator: &Token) -> Option<Either<Vec<StmtNode>, ExprNode>> {
-        // decl_stmt | "fn" var "(" parameters? ")" "->" expression ;
+        // decl_stmt | "fn" var "(" parameters? ")" "->" ( type ( "->" | &block ) )? expression ;
         let start = *self.peek_start_loc();
         if !self.match_token(Token::Fn) {
-            self.macro_stmt(terminator)
+            self.enum_stmt(terminator)
         } else if let Token::Identifier(name) = self.peek() {
             let name = name.to_string();
             self.take();
@@ -86,6 +93,13 @@ impl<'a> Parser<'a> {
             let params = self.parameter_list()?;
             self.accept(Token::RPar, "Expect ')' after function parameters")?;
             self.accept(Token::RArrow, "Expect '->' before function body")?;
+            let return_type = if self.at_return_type() {
+                let annotated = self.type_annotation()?;
+                self.match_token(Token::RArrow);
+                Some(annotated)
+            } else {
+                None
+            };
             let body = self.expression()?;
             let end = body.end_loc;
 
@@ -96,7 +110,7 @@ impl<'a> Parser<'a> {
             }
             // let id = format!("fn {name}/{}", params.len());
             let func = ExprNode::new(
-                super::Expr::FunctionDefinition(name.to_owned(), params, body),
+                super::Expr::FunctionDefinition(name.to_owned(), params, body, return_type),
                 start,
                 end,
             );
@@ -112,6 +126,73 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses an enum declaration, declaring one constructor per variant
+    fn enum_stmt(&mut self, terminator: &Token) -> Option<Either<Vec<StmtNode>, ExprNode>> {
+        // enum_stmt -> "enum" var "{" ( variant ( "," variant )* ","? )? "}" ;
+        // variant -> var ( "(" ( var ( "," var )* )? ")" )? ;
+        if !self.match_token(Token::Enum) {
+            return self.macro_stmt(terminator);
+        }
+
+        let Token::Identifier(enum_name) = self.peek().clone() else {
+            self.error("Expect enum name after enum");
+            return None;
+        };
+        self.take();
+        self.accept(Token::LBrace, "Expect '{' before enum variants")?;
+
+        let mut variants = vec![];
+        while !self.match_token(Token::RBrace) {
+            let start = *self.peek_start_loc();
+            let Token::Identifier(name) = self.peek().clone() else {
+                self.error("Expect variant name in enum declaration");
+                return None;
+            };
+            self.take();
+
+            let mut fields = vec![];
+            if self.match_token(Token::LPar) && !self.match_token(Token::RPar) {
+                loop {
+                    let Token::Identifier(field) = self.peek().clone() else {
+                        self.error("Expect field name in enum variant");
+                        return None;
+                    };
+                    self.take();
+                    fields.push(field);
+                    if !self.match_token(Token::Comma) {
+                        break;
+                    }
+                }
+                self.accept(Token::RPar, "Expect ')' after enum variant fields")?;
+            }
+            if fields.len() >= MAX_ARGS {
+                self.error(&format!(
+                    "Cannot have more than {MAX_ARGS} fields in an enum variant"
+                ));
+            }
+
+            let end = *self.peek_last_end_loc().unwrap();
+            let constructor = ExprNode::new(
+                Expr::EnumVariant(enum_name.clone(), name.clone(), fields),
+                start,
+                end,
+            );
+            variants.push(StmtNode::new(
+                Stmt::Decl(LValue::Var(name), Some(constructor)),
+                start,
+                end,
+            ));
+
+            if !self.match_token(Token::Comma) {
+                self.accept(Token::RBrace, "Expect ',' between enum variants")?;
+                break;
+            }
+        }
+        self.match_token(Token::Semicolon);
+
+        Some(Either::Left(variants))
+    }
+
     fn parameter_list(&mut self) -> Option<Vec<LValue>> {
         if self.peek() != &Token::RPar {
             let mut params = vec![];
@@ -119,7 +200,7 @@ impl<'a> Parser<'a> {
             while first || self.match_token(Token::Comma) {
                 first = false;
 
-                let param = self.lvalue(true)?;
+                let param = self.parameter()?;
                 params.push(param);
             }
             if params.len() >= MAX_ARGS {
@@ -134,9 +215,11 @@ impl<'a> Parser<'a> {
     /// Checks for a macro statement, before delegating to leading with an expression
     fn macro_stmt(&mut self, terminator: &Token) -> Option<Either<Vec<StmtNode>, ExprNode>> {
         // macro_stmt -> macro_invocation ( '(' args ')' )
+        let start = *self.peek_start_loc();
         if let Some(name) = self.match_macro_invocation() {
             let res = match name {
                 "include!" => Some(Either::Left(self.macro_include_statement()?)),
+                "assert!" => Some(Either::Left(vec![self.macro_assert_statement(start)?])),
                 otherwise => {
                     let reason = &format!("Could not resolve statement macro '{otherwise}'");
                     self.error(&reason);
@@ -159,7 +242,20 @@ impl<'a> Parser<'a> {
         // varDecl        → (expression | lvalue ":=" expression | expression ":>>" lvalue) ";" ;
         let start = *self.peek_start_loc();
         let expr = self.expression()?; // We can't separate lvalues and assignmen here :/
-        if self.match_token(Token::ColonEq) {
+        if self.peek() == &Token::Colon {
+            // An annotated declaration, such as 'x: int := 0'
+            let lvalue = self.expr_to_lvalue(expr, true)?;
+            let lvalue = self.opt_type_annotation(lvalue)?;
+            self.accept(Token::ColonEq, "Expect ':=' after annotated lvalue")?;
+            let rvalue = self.expression()?;
+            let end = *self.peek_last_end_loc().unwrap();
+            self.accept(Token::Semicolon, "Decl statement must end with ';'")?;
+            Some(Either::Left(vec![StmtNode::new(
+                Stmt::Decl(lvalue, Some(rvalue)),
+                start,
+                end,
+            )]))
+        } else if self.match_token(Token::ColonEq) {
             let lvalue = self.expr_to_lvalue(expr, true)?;
             let rvalue = self.expression()?;
             let end = *self.peek_last_end_loc().unwrap();
@@ -208,13 +304,71 @@ impl<'a> Parser<'a> {
 
 fn semicolon_elision(expr: &ExprNode) -> bool {
     match expr.node.as_ref() {
-        Expr::While(_, block)
-        | Expr::For(_, _, block)
+        Expr::While(_, block, None, _)
+        | Expr::While(_, _, Some(block), _)
+        | Expr::WhileLet(_, _, block, None, _)
+        | Expr::WhileLet(_, _, _, Some(block), _)
+        | Expr::For(_, _, block, None, _)
+        | Expr::For(_, _, _, Some(block), _)
         | Expr::If(_, block, None)
-        | Expr::If(_, _, Some(block)) => {
+        | Expr::If(_, _, Some(block))
+        | Expr::IfLet(_, _, block, None)
+        | Expr::IfLet(_, _, _, Some(block)) => {
             matches!(block.node.as_ref(), Expr::Block(_)) || semicolon_elision(block)
         }
         Expr::Match(_, _) | Expr::Block(_) => true,
         _ => false,
     }
 }
+
+/// Finds the names of all enum variants without fields, so that patterns can match them by name
+///
+/// Enums can be declared after the patterns using them, so this scans all tokens before parsing,
+/// including the tokens of included files.
+pub(crate) fn unit_variants(tokens: &[&TokenInfo]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    collect_unit_variants(tokens, &mut names, &mut HashSet::new());
+    names
+}
+
+fn collect_unit_variants(
+    tokens: &[&TokenInfo],
+    names: &mut HashSet<String>,
+    included: &mut HashSet<String>,
+) {
+    let mut tokens = tokens.iter().map(|info| &info.token).peekable();
+    while let Some(token) = tokens.next() {
+        if let Token::MacroInvocation(name) = token
+            && name == "include!"
+        {
+            // Errors in the included file are reported when it is parsed
+            if let (Some(Token::LPar), Some(Token::String(path))) = (tokens.next(), tokens.next())
+                && included.insert(path.to_string())
+                && let Ok(code) = read_included(path)
+            {
+                let included_tokens = tokenize(&code, path, &mut ErrorReporter::new());
+                let included_tokens: Vec<&TokenInfo> = included_tokens.iter().collect();
+                collect_unit_variants(&included_tokens, names, included);
+            }
+            continue;
+        }
+        if token != &Token::Enum {
+            continue;
+        }
+        let (Some(Token::Identifier(_)), Some(Token::LBrace)) = (tokens.next(), tokens.next())
+        else {
+            continue;
+        };
+
+        while let Some(Token::Identifier(name)) = tokens.next() {
+            if tokens.peek() == Some(&&Token::LPar) {
+                tokens.find(|token| token == &&Token::RPar);
+            } else {
+                names.insert(name.clone());
+            }
+            if tokens.next() != Some(&Token::Comma) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::Display;
+
+/// A type which values can be annotated with, as in `x: int := 0`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    Str,
+    List,
+    Dict,
+    Fn,
+    Nil,
+}
+
+impl Type {
+    /// All types, indexed by their discriminant so they can be encoded as a byte
+    pub const ALL: [Type; 8] = [
+        Type::Int,
+        Type::Float,
+        Type::Bool,
+        Type::Str,
+        Type::List,
+        Type::Dict,
+        Type::Fn,
+        Type::Nil,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+
+    /// The name used when annotating with the type
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::Str => "str",
+            Type::List => "list",
+            Type::Dict => "dict",
+            Type::Fn => "fn",
+            Type::Nil => "nil",
+        }
+    }
+
+    /// Can a value of this type be used where the other type is expected?
+    ///
+    /// Ints can be used as floats, as numbers are promoted when needed.
+    pub fn fits(&self, expected: &Type) -> bool {
+        self == expected || (self == &Type::Int && expected == &Type::Float)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use parser::{BinOper, CodeRange, Expr, ExprNode, LValue, Stmt, Stmts};
+
+use crate::visitor::AstVisitor;
+
+/// The argument counts accepted by a native function
+pub struct NativeArity<'a> {
+    pub name: &'a str,
+
+    /// How the accepted counts are shown in errors, such as "[0, 1]"
+    pub arity: &'a str,
+
+    pub accepts: Box<dyn Fn(usize) -> bool + 'a>,
+}
+
+/// A call to a known function with the wrong number of arguments
+#[derive(Debug, PartialEq)]
+pub struct ArityError {
+    pub name: String,
+    pub range: CodeRange,
+    pub given: usize,
+    pub expected: String,
+}
+
+/// Finds calls with the wrong number of arguments, where the callee is known before running
+///
+/// The known functions are the natives, global functions and enum constructors, as long as
+/// they are declared once and never assigned to. Pipes are already calls at this point,
+/// with the piped value as one of the arguments.
+pub fn find_arity_errors(stmts: &Stmts, natives: &[NativeArity]) -> Vec<ArityError> {
+    let mut assigned = AssignedNames::default();
+    assigned.visit_stmts(stmts);
+
+    let mut declarations: HashMap<&String, usize> = HashMap::new();
+    let mut arities: HashMap<String, Arity> = natives
+        .iter()
+        .map(|native| (native.name.to_string(), Arity::Native(native)))
+        .collect();
+    for stmt in stmts.stmts.iter() {
+        if let Stmt::Decl(lvalue, init) = stmt.node.as_ref() {
+            for name in lvalue_names(lvalue) {
+                *declarations.entry(name).or_default() += 1;
+                arities.remove(name);
+            }
+
+            if let (LValue::Var(name), Some(expr)) = (lvalue, init) {
+                match expr.node.as_ref() {
+                    Expr::FunctionDefinition(_, params, _, _) => {
+                        arities.insert(name.clone(), Arity::Exact(params.len()));
+                    }
+                    // Variants without fields are values, so they are not called
+                    Expr::EnumVariant(_, _, fields) if !fields.is_empty() => {
+                        arities.insert(name.clone(), Arity::Exact(fields.len()));
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    arities.retain(|name, _| {
+        declarations.get(name).copied().unwrap_or_default() <= 1 && !assigned.names.contains(name)
+    });
+
+    let mut checker = ArityChecker {
+        arities,
+        scopes: vec![],
+        range: CodeRange::from_ints(0, 0, 0, 0, 0, 0),
+        errors: vec![],
+    };
+    checker.visit_stmts(stmts);
+    checker.errors
+}
+
+enum Arity<'a> {
+    Exact(usize),
+    Native(&'a NativeArity<'a>),
+}
+
+impl Arity<'_> {
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(arity) => *arity == count,
+            Arity::Native(native) => (native.accepts)(count),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Arity::Exact(arity) => arity.to_string(),
+            Arity::Native(native) => native.arity.to_string(),
+        }
+    }
+}
+
+/// All variable names declared by an lvalue
+pub(crate) fn lvalue_names(lvalue: &LValue) -> Vec<&String> {
+    match lvalue {
+        LValue::Var(name) => vec![name],
+        LValue::Tuple(lvalues) => lvalues.iter().flat_map(lvalue_names).collect(),
+        LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => lvalue_names(lvalue),
+        LValue::Variant(_, fields) => fields.iter().flat_map(lvalue_names).collect(),
+        LValue::Index(_, _) | LValue::Constant(_) | LValue::Wildcard => vec![],
+    }
+}
+
+/// Collects the names of all variables which are assigned to anywhere
+#[derive(Default)]
+pub(crate) struct AssignedNames {
+    pub names: HashSet<String>,
+}
+
+impl AstVisitor for AssignedNames {
+    fn visit_assign(&mut self, lvalue: &LValue, value: &ExprNode) {
+        self.names.extend(lvalue_names(lvalue).into_iter().cloned());
+        self.visit_lvalue(lvalue, false);
+        self.visit_expr(value);
+    }
+
+    fn visit_op_assign(&mut self, lvalue: &LValue, op: &BinOper, value: &ExprNode) {
+        self.names.extend(lvalue_names(lvalue).into_iter().cloned());
+        self.visit_lvalue(lvalue, false);
+        self.visit_binary_oper(op);
+        self.visit_expr(value);
+    }
+}
+
+struct ArityChecker<'a> {
+    /// The known global functions, by name
+    arities: HashMap<String, Arity<'a>>,
+
+    /// The names declared in each nested local scope, which hide the globals
+    scopes: Vec<HashSet<String>>,
+
+    /// The range of the innermost expression being visited
+    range: CodeRange,
+
+    errors: Vec<ArityError>,
+}
+
+impl ArityChecker<'_> {
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+}
+
+impl AstVisitor for ArityChecker<'_> {
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashSet::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn visit_var(&mut self, name: &String, declaration: bool) {
+        if let (true, Some(scope)) = (declaration, self.scopes.last_mut()) {
+            scope.insert(name.clone());
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        let outer = std::mem::replace(&mut self.range, expr.range());
+        self.visit_expr_delegation(expr);
+        self.range = outer;
+    }
+
+    fn visit_call(&mut self, callee: &ExprNode, args: &[ExprNode]) {
+        if let Expr::Var(name) = callee.node.as_ref() {
+            match self.arities.get(name) {
+                Some(arity) if !arity.accepts(args.len()) && !self.is_local(name) => {
+                    self.errors.push(ArityError {
+                        name: name.clone(),
+                        range: self.range.clone(),
+                        given: args.len(),
+                        expected: arity.describe(),
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        self.visit_expr(callee);
+        for arg in args {
+            self.visit_expr(arg)
+        }
+    }
+}
+
+impl Display for ArityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Called '{}' with {} args, but it expects {}",
+            self.name, self.given, self.expected
+        )
+    }
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use parser::{Expr, ExprNode, LValue, Stmts};
+use parser::{Expr, ExprNode, LValue, NodeId, Stmts};
 
-use crate::{ref_id, visitor::AstVisitor, NodeAttr, RefId};
+use crate::{visitor::AstVisitor, NodeAttr};
 
 /// Finds functions declared like
 ///     f := (__args__) -> ... ; or fn f(__args__) -> ... ;
@@ -24,7 +24,7 @@ use crate::{ref_id, visitor::AstVisitor, NodeAttr, RefId};
 /// declaration of f.
 /// This is a bit of a corner case, but becomes important for our strange
 /// recursive closure definitions.
-pub(crate) fn find_recursion_names(ast: &Stmts) -> HashMap<RefId, NodeAttr> {
+pub(crate) fn find_recursion_names(ast: &Stmts) -> HashMap<NodeId, NodeAttr> {
     let mut finder = NameFinder {
         bindings: HashMap::new(),
     };
@@ -40,17 +40,19 @@ pub(crate) fn find_recursion_names(ast: &Stmts) -> HashMap<RefId, NodeAttr> {
 
 struct NameFinder {
     /// Binds the id of Expr (of a func def), to a potential binding for recursion.
-    bindings: HashMap<RefId, String>,
+    bindings: HashMap<NodeId, String>,
 }
 
 impl AstVisitor for NameFinder {
     fn visit_decl(&mut self, lvalue: &LValue, init: Option<&ExprNode>) {
         if let LValue::Var(name) = lvalue {
             if let Some(expr_node) = init {
-                if matches!(expr_node.node.as_ref(), Expr::FunctionDefinition(_, _, _)) {
+                if matches!(
+                    expr_node.node.as_ref(),
+                    Expr::FunctionDefinition(_, _, _, _)
+                ) {
                     // Direct declaration of function, where we should support recursion
-                    self.bindings
-                        .insert(ref_id(expr_node.node.as_ref()), name.clone());
+                    self.bindings.insert(expr_node.id, name.clone());
                 }
             }
         }
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use parser::{CodeLoc, CodeRange, ExprNode, NodeId, Stmt, StmtNode, Stmts};
+
+use crate::{variable_resolution::resolve_variables, visitor::AstVisitor};
+
+/// A declared variable, together with where it can be used
+#[derive(Debug, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+
+    /// The node declaring the variable, such as the declaration statement or function definition
+    pub range: CodeRange,
+
+    /// The code where the variable can be referenced, or None for globals which can be used anywhere
+    pub scope: Option<CodeRange>,
+}
+
+/// A use of a variable
+#[derive(Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+
+    /// The innermost statement or expression using the variable
+    pub range: CodeRange,
+
+    /// The index of the declaration it refers to, or None if it is not declared in the code,
+    /// such as for natives
+    pub declaration: Option<usize>,
+}
+
+/// Finds all variable declarations and references to variables in a program
+///
+/// The variables are resolved in the same way as when finding upvalues for the VM, and the
+/// results are only converted from node ids to the ranges of the nodes here.
+pub fn find_declarations(stmts: &Stmts) -> (Vec<Declaration>, Vec<Reference>) {
+    let (declarations, references) = resolve_variables(stmts);
+
+    let mut ranges = NodeRanges {
+        ranges: HashMap::new(),
+    };
+    ranges.visit_stmts(stmts);
+    let range = |id: NodeId| ranges.ranges[&id].clone();
+
+    let declarations = declarations
+        .into_iter()
+        .map(|declaration| Declaration {
+            name: declaration.name,
+            range: range(declaration.declared_in),
+            scope: declaration.scope.map(range),
+        })
+        .collect();
+    let references = references
+        .into_iter()
+        .map(|reference| Reference {
+            name: reference.name,
+            range: range(reference.node),
+            declaration: reference.declaration,
+        })
+        .collect();
+
+    (declarations, references)
+}
+
+impl Declaration {
+    /// Checks if the variable can be referenced at the location
+    ///
+    /// Locals can be referenced after the start of their declaration within their scope,
+    /// while globals can be referenced anywhere as they are late-bound.
+    pub fn is_visible_at(&self, loc: CodeLoc) -> bool {
+        match &self.scope {
+            Some(scope) => scope.contains(loc) && self.range.start().index() <= loc.index(),
+            None => true,
+        }
+    }
+}
+
+/// Collects the range of every statement and expression by its id
+struct NodeRanges {
+    ranges: HashMap<NodeId, CodeRange>,
+}
+
+impl AstVisitor for NodeRanges {
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        self.ranges.insert(stmt.id, stmt.range());
+        match stmt.node.as_ref() {
+            Stmt::Decl(lvalue, init) => self.visit_decl(lvalue, init.as_ref()),
+            Stmt::Expr(expr) => self.visit_expr(expr),
+            Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            Stmt::Invalid => (),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        self.ranges.insert(expr.id, expr.range());
+        self.visit_expr_delegation(expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::CodeLoc;
+
+    use super::find_declarations;
+
+    /// The lines of each reference, and the line of the declaration it refers to
+    fn resolved_lines(code: &str) -> Vec<(usize, Option<u16>)> {
+        let stmts = parser::parse("test", code).unwrap();
+        let (declarations, references) = find_declarations(&stmts);
+        references
+            .iter()
+            .map(|reference| {
+                let declaration = reference.declaration.map(|index| &declarations[index]);
+                (
+                    reference.range.start().line(),
+                    declaration.map(|declaration| declaration.range.sl()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shadowing() {
+        let code = "x := 1;\nfn f(x) -> {\n\tx := x + 1;\n\tx\n};\nx";
+        assert_eq!(
+            resolved_lines(code),
+            vec![(3, Some(2)), (4, Some(3)), (6, Some(1))]
+        );
+
+        // Only the global declarations are visible outside the function
+        let stmts = parser::parse("test", code).unwrap();
+        let (declarations, _) = find_declarations(&stmts);
+        let end = CodeLoc::new(code.len(), 6, 2);
+        let mut visible: Vec<&str> = declarations
+            .iter()
+            .filter(|declaration| declaration.is_visible_at(end))
+            .map(|declaration| declaration.name.as_str())
+            .collect();
+        visible.sort();
+        assert_eq!(visible, vec!["f", "x"]);
+    }
+
+    #[test]
+    fn scopes() {
+        // Block locals end with their block, and globals can be used before their declaration
+        let code =
+            "fn f(x) -> {\n\tif true {\n\t\tx := 2;\n\t};\n\tx + g()\n};\nfn g() -> 1;\nprint";
+        assert_eq!(
+            resolved_lines(code),
+            vec![(5, Some(1)), (5, Some(7)), (8, None)]
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use parser::{
+    AstNode, BinOper, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, Slice, Stmt,
+    StmtNode, Stmts, Type, UnOper,
+};
+
+/// Rebuilds the AST, where each method can be overridden to transform some of its nodes
+///
+/// The default implementations rebuild every node unchanged, keeping their ids and code ranges,
+/// so a pass only has to override the parts it changes. As with the visitor, an overriding
+/// `fold_expr` can call `fold_expr_delegation` for the expressions it keeps.
+pub trait AstFolder {
+    fn fold_stmts(&mut self, stmts: Stmts) -> Stmts {
+        Stmts {
+            stmts: stmts
+                .stmts
+                .into_iter()
+                .map(|stmt| self.fold_stmt(stmt))
+                .collect(),
+            output: stmts.output,
+        }
+    }
+
+    fn fold_stmt(&mut self, stmt: StmtNode) -> StmtNode {
+        let node = match *stmt.node {
+            Stmt::Decl(lvalue, init) => self.fold_decl(lvalue, init),
+            Stmt::Expr(expr) => Stmt::Expr(self.fold_expr(expr)),
+            Stmt::Assert(cond, message, source) => self.fold_assert(cond, message, source),
+            Stmt::Invalid => Stmt::Invalid,
+        };
+
+        AstNode {
+            node: Box::new(node),
+            ..stmt
+        }
+    }
+
+    fn fold_assert(&mut self, cond: ExprNode, message: Option<ExprNode>, source: String) -> Stmt {
+        Stmt::Assert(
+            self.fold_expr(cond),
+            message.map(|expr| self.fold_expr(expr)),
+            source,
+        )
+    }
+
+    fn fold_decl(&mut self, lvalue: LValue, init: Option<ExprNode>) -> Stmt {
+        Stmt::Decl(
+            self.fold_lvalue(lvalue, true),
+            init.map(|expr| self.fold_expr(expr)),
+        )
+    }
+
+    fn fold_expr_delegation(&mut self, expr: ExprNode) -> ExprNode {
+        let node = match *expr.node {
+            Expr::Call(callee, args) => self.fold_call(callee, args),
+            Expr::IndexInto(indexee, at) => self.fold_index_into(indexee, at),
+            Expr::SafeIndexInto(indexee, at) => self.fold_safe_index_into(indexee, at),
+            Expr::Binary(x, op, y) => self.fold_binary(x, op, y),
+            Expr::ChainedComparison(first, links) => self.fold_chained_comparison(first, links),
+            Expr::Unary(op, x) => self.fold_unary(op, x),
+            Expr::Logical(x, op, y) => self.fold_logical(x, op, y),
+            Expr::Assign(lvalue, value) => self.fold_assign(lvalue, value),
+            Expr::OpAssign(lvalue, op, value) => self.fold_op_assign(lvalue, op, value),
+            Expr::Block(stmts) => self.fold_block(stmts),
+            Expr::If(cond, then, otherwise) => self.fold_if(cond, then, otherwise),
+            Expr::IfLet(lvalue, matched, then, otherwise) => {
+                self.fold_if_let(lvalue, matched, then, otherwise)
+            }
+            Expr::While(cond, body, otherwise, label) => {
+                self.fold_while(cond, body, otherwise, label)
+            }
+            Expr::WhileLet(lvalue, matched, body, otherwise, label) => {
+                self.fold_while_let(lvalue, matched, body, otherwise, label)
+            }
+            Expr::For(lvalue, collection, body, otherwise, label) => {
+                self.fold_for(lvalue, collection, body, otherwise, label)
+            }
+            Expr::Break(label, value) => self.fold_break(label, value),
+            Expr::Return(ret) => self.fold_return(ret),
+            Expr::List(content) => self.fold_list(content),
+            Expr::Dict(pairs) => self.fold_dict(pairs),
+            Expr::Set(exprs) => self.fold_set(exprs),
+            Expr::Tuple(exprs) => self.fold_tuple(exprs),
+            Expr::FunctionDefinition(name, params, body, returns) => {
+                self.fold_function_definition(name, params, body, returns)
+            }
+            Expr::Match(matched, options) => self.fold_match(matched, options),
+            // Leaves without any sub-nodes are kept as they are
+            leaf @ (Expr::Var(_)
+            | Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::String(_)
+            | Expr::Continue(_)
+            | Expr::Nil
+            | Expr::Rest(_)
+            | Expr::EnumVariant(_, _, _)
+            | Expr::Invalid) => leaf,
+        };
+
+        AstNode {
+            node: Box::new(node),
+            ..expr
+        }
+    }
+
+    fn fold_expr(&mut self, expr: ExprNode) -> ExprNode {
+        self.fold_expr_delegation(expr)
+    }
+
+    fn fold_exprs(&mut self, exprs: Vec<ExprNode>) -> Vec<ExprNode> {
+        exprs.into_iter().map(|expr| self.fold_expr(expr)).collect()
+    }
+
+    fn fold_call(&mut self, callee: ExprNode, args: Vec<ExprNode>) -> Expr {
+        Expr::Call(self.fold_expr(callee), self.fold_exprs(args))
+    }
+
+    fn fold_index_into(&mut self, indexee: ExprNode, at: Index) -> Expr {
+        Expr::IndexInto(self.fold_expr(indexee), self.fold_index(at))
+    }
+
+    fn fold_safe_index_into(&mut self, indexee: ExprNode, at: Index) -> Expr {
+        Expr::SafeIndexInto(self.fold_expr(indexee), self.fold_index(at))
+    }
+
+    fn fold_binary(&mut self, x: ExprNode, op: BinOper, y: ExprNode) -> Expr {
+        Expr::Binary(self.fold_expr(x), op, self.fold_expr(y))
+    }
+
+    fn fold_chained_comparison(
+        &mut self,
+        first: ExprNode,
+        links: Vec<(BinOper, ExprNode)>,
+    ) -> Expr {
+        Expr::ChainedComparison(
+            self.fold_expr(first),
+            links
+                .into_iter()
+                .map(|(op, operand)| (op, self.fold_expr(operand)))
+                .collect(),
+        )
+    }
+
+    fn fold_unary(&mut self, op: UnOper, x: ExprNode) -> Expr {
+        Expr::Unary(op, self.fold_expr(x))
+    }
+
+    fn fold_logical(&mut self, x: ExprNode, op: LogicalOper, y: ExprNode) -> Expr {
+        Expr::Logical(self.fold_expr(x), op, self.fold_expr(y))
+    }
+
+    fn fold_assign(&mut self, lvalue: LValue, value: ExprNode) -> Expr {
+        Expr::Assign(self.fold_lvalue(lvalue, false), self.fold_expr(value))
+    }
+
+    fn fold_op_assign(&mut self, lvalue: LValue, op: BinOper, value: ExprNode) -> Expr {
+        Expr::OpAssign(self.fold_lvalue(lvalue, false), op, self.fold_expr(value))
+    }
+
+    fn fold_block(&mut self, stmts: Stmts) -> Expr {
+        Expr::Block(self.fold_stmts(stmts))
+    }
+
+    fn fold_if(&mut self, cond: ExprNode, then: ExprNode, otherwise: Option<ExprNode>) -> Expr {
+        Expr::If(
+            self.fold_expr(cond),
+            self.fold_expr(then),
+            otherwise.map(|expr| self.fold_expr(expr)),
+        )
+    }
+
+    fn fold_if_let(
+        &mut self,
+        lvalue: LValue,
+        matched: ExprNode,
+        then: ExprNode,
+        otherwise: Option<ExprNode>,
+    ) -> Expr {
+        let matched = self.fold_expr(matched);
+        Expr::IfLet(
+            self.fold_lvalue(lvalue, true),
+            matched,
+            self.fold_expr(then),
+            otherwise.map(|expr| self.fold_expr(expr)),
+        )
+    }
+
+    fn fold_while(
+        &mut self,
+        cond: ExprNode,
+        body: ExprNode,
+        otherwise: Option<ExprNode>,
+        label: Option<String>,
+    ) -> Expr {
+        Expr::While(
+            self.fold_expr(cond),
+            self.fold_expr(body),
+            otherwise.map(|expr| self.fold_expr(expr)),
+            label,
+        )
+    }
+
+    fn fold_while_let(
+        &mut self,
+        lvalue: LValue,
+        matched: ExprNode,
+        body: ExprNode,
+        otherwise: Option<ExprNode>,
+        label: Option<String>,
+    ) -> Expr {
+        let matched = self.fold_expr(matched);
+        Expr::WhileLet(
+            self.fold_lvalue(lvalue, true),
+            matched,
+            self.fold_expr(body),
+            otherwise.map(|expr| self.fold_expr(expr)),
+            label,
+        )
+    }
+
+    fn fold_for(
+        &mut self,
+        lvalue: LValue,
+        collection: ExprNode,
+        body: ExprNode,
+        otherwise: Option<ExprNode>,
+        label: Option<String>,
+    ) -> Expr {
+        let collection = self.fold_expr(collection);
+        Expr::For(
+            self.fold_lvalue(lvalue, true),
+            collection,
+            self.fold_expr(body),
+            otherwise.map(|expr| self.fold_expr(expr)),
+            label,
+        )
+    }
+
+    fn fold_break(&mut self, label: Option<String>, value: Option<ExprNode>) -> Expr {
+        Expr::Break(label, value.map(|expr| self.fold_expr(expr)))
+    }
+
+    fn fold_return(&mut self, ret: Option<ExprNode>) -> Expr {
+        Expr::Return(ret.map(|expr| self.fold_expr(expr)))
+    }
+
+    fn fold_list(&mut self, content: ListContent) -> Expr {
+        let content = match content {
+            ListContent::Exprs(exprs) => ListContent::Exprs(self.fold_exprs(exprs)),
+            ListContent::Range(slice) => ListContent::Range(self.fold_slice(slice)),
+        };
+        Expr::List(content)
+    }
+
+    fn fold_dict(&mut self, pairs: Vec<(ExprNode, ExprNode)>) -> Expr {
+        Expr::Dict(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (self.fold_expr(key), self.fold_expr(value)))
+                .collect(),
+        )
+    }
+
+    fn fold_set(&mut self, exprs: Vec<ExprNode>) -> Expr {
+        Expr::Set(self.fold_exprs(exprs))
+    }
+
+    fn fold_tuple(&mut self, exprs: Vec<ExprNode>) -> Expr {
+        Expr::Tuple(self.fold_exprs(exprs))
+    }
+
+    fn fold_function_definition(
+        &mut self,
+        name: String,
+        params: Vec<LValue>,
+        body: ExprNode,
+        returns: Option<Type>,
+    ) -> Expr {
+        Expr::FunctionDefinition(
+            name,
+            params
+                .into_iter()
+                .map(|param| self.fold_lvalue(param, true))
+                .collect(),
+            self.fold_expr(body),
+            returns,
+        )
+    }
+
+    fn fold_match(&mut self, matched: ExprNode, options: Vec<(LValue, ExprNode)>) -> Expr {
+        Expr::Match(
+            self.fold_expr(matched),
+            options
+                .into_iter()
+                .map(|(lvalue, then)| (self.fold_lvalue(lvalue, true), self.fold_expr(then)))
+                .collect(),
+        )
+    }
+
+    fn fold_index(&mut self, at: Index) -> Index {
+        match at {
+            Index::At(expr) => Index::At(self.fold_expr(expr)),
+            Index::Slice(slice) => Index::Slice(self.fold_slice(slice)),
+        }
+    }
+
+    fn fold_lvalue(&mut self, lvalue: LValue, declaration: bool) -> LValue {
+        match lvalue {
+            LValue::Index(indexee, at) => {
+                LValue::Index(self.fold_expr(indexee), self.fold_index(at))
+            }
+            LValue::Tuple(lvalues) => LValue::Tuple(
+                lvalues
+                    .into_iter()
+                    .map(|lvalue| self.fold_lvalue(lvalue, declaration))
+                    .collect(),
+            ),
+            LValue::Constant(expr) => LValue::Constant(self.fold_expr(expr)),
+            LValue::Rest(lvalue) => LValue::Rest(Box::new(self.fold_lvalue(*lvalue, declaration))),
+            LValue::Typed(lvalue, annotated) => {
+                LValue::Typed(Box::new(self.fold_lvalue(*lvalue, declaration)), annotated)
+            }
+            LValue::Variant(name, fields) => LValue::Variant(
+                name,
+                fields
+                    .into_iter()
+                    .map(|field| self.fold_lvalue(field, declaration))
+                    .collect(),
+            ),
+            LValue::Var(_) | LValue::Wildcard => lvalue,
+        }
+    }
+
+    fn fold_slice(&mut self, slice: Slice) -> Slice {
+        Slice {
+            start: slice.start.map(|start| self.fold_expr(start)),
+            stop: slice.stop.map(|stop| self.fold_expr(stop)),
+            step: slice.step.map(|step| self.fold_expr(step)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::{Expr, ExprNode, Stmt};
+
+    use super::AstFolder;
+    use crate::format_parsed;
+
+    /// Doubles all integer literals
+    struct Doubler;
+
+    impl AstFolder for Doubler {
+        fn fold_expr(&mut self, expr: ExprNode) -> ExprNode {
+            match expr.node.as_ref() {
+                Expr::Int(x) => ExprNode {
+                    node: Box::new(Expr::Int(2 * x)),
+                    ..expr
+                },
+                _ => self.fold_expr_delegation(expr),
+            }
+        }
+    }
+
+    #[test]
+    fn rebuilds_changed_nodes() {
+        let stmts = parser::parse("test", "x := [1, 2]; fn f(y) -> y + 3;").unwrap();
+        let original = stmts.clone();
+        let folded = Doubler.fold_stmts(stmts);
+
+        let expected = parser::parse("test", "x := [2, 4]; fn f(y) -> y + 6;").unwrap();
+        assert_eq!(format_parsed(&folded), format_parsed(&expected));
+
+        // The ids are kept, so analysis of the original still applies
+        for (folded, original) in folded.stmts.iter().zip(original.stmts.iter()) {
+            assert_eq!(folded.id, original.id);
+            let (Stmt::Decl(_, Some(folded)), Stmt::Decl(_, Some(original))) =
+                (folded.node.as_ref(), original.node.as_ref())
+            else {
+                panic!("Expected declarations")
+            };
+            assert_eq!(folded.id, original.id);
+        }
+    }
+}
@@ -2,30 +2,56 @@ use std::collections::HashMap;
 
 use closure_naming::find_recursion_names;
 use local_enumerator::count_locals;
-use parser::{Expr, Stmts};
+use parser::{NodeId, Stmts};
 use variable_resolution::find_upvalues;
 
+pub use arity_checking::{find_arity_errors, ArityError, NativeArity};
+pub use declarations::{find_declarations, Declaration, Reference};
+pub use folder::AstFolder;
+pub use lint::{
+    lint, LintWarning, IF_WITHOUT_ELSE, SHADOWED_NATIVE, UNREACHABLE_CODE, UNUSED_VARIABLE,
+};
+pub use type_checking::{find_type_errors, TypeError};
+pub use undefined_variables::{find_undefined_variables, UndefinedVariable};
+pub use variant_checking::{find_variant_errors, VariantError};
+
+mod arity_checking;
 mod closure_naming;
+mod declarations;
+mod folder;
+mod lint;
 mod local_enumerator;
 mod pretty_printer;
+mod type_checking;
+mod undefined_variables;
 mod variable_resolution;
+mod variant_checking;
 mod visitor;
 
+/// The parsed code, together with the analysis results stored by the ids of the nodes
+///
+/// Variables are identified by their name together with the node binding them. That is the
+/// declaration statement, or the body of the function, loop, if let or match arm binding them.
 #[derive(Debug)]
 pub struct AttributedAst<'a> {
     stmts: &'a Stmts,
-    attributes: HashMap<usize, Vec<NodeAttr>>,
+    attributes: HashMap<NodeId, Vec<NodeAttr>>,
+
+    /// The max number of locals in the script, outside all functions
+    global_local_count: usize,
 }
 
 pub fn analyze_ast<'a>(stmts: &'a Stmts) -> AttributedAst<'a> {
     let mut attr_ast = AttributedAst::new(stmts);
-    attr_ast.merge_singles(find_recursion_names(attr_ast.stmts));
+    attr_ast.merge(find_recursion_names(attr_ast.stmts));
 
     // Requires the names of recursive functions to work properly
     let upvalue_attrs = find_upvalues(attr_ast.stmts);
-    attr_ast.merge_singles(upvalue_attrs);
+    attr_ast.merge(upvalue_attrs);
 
-    attr_ast.merge_singles(count_locals(stmts));
+    let (global_local_count, local_counts) = count_locals(stmts);
+    attr_ast.global_local_count = global_local_count;
+    attr_ast.merge(local_counts);
 
     attr_ast
 }
@@ -35,64 +61,39 @@ pub fn format_parsed(stmts: &Stmts) -> String {
     pretty_printer::format(stmts)
 }
 
-type RefId = usize;
-
-fn ref_id<T>(reference: &T) -> RefId {
-    let raw_pointer = reference as *const T;
-    raw_pointer as usize
-}
-
 impl<'a> AttributedAst<'a> {
-    /// Used on the reference to the name of a variable declaration to see if it is an upvalue
-    pub fn is_upvalue(&self, var_ref: &String) -> bool {
-        if let Some(attrs) = self.attributes.get(&ref_id(var_ref)) {
-            attrs.contains(&NodeAttr::UpValue)
-        } else {
-            false
-        }
+    /// Checks if the variable bound in the node is an upvalue, captured by some closure
+    pub fn is_upvalue(&self, binder: NodeId, name: &str) -> bool {
+        self.attrs(binder)
+            .any(|attr| matches!(attr, NodeAttr::UpValue(upvalue) if upvalue == name))
     }
 
-    /// Returns all upvalue names for a function definition Expression.
+    /// Returns all upvalue names for a function definition.
     /// Borrows the attributes for as long as the names live.
-    pub fn upvalue_names<'b>(&'b self, func_ref: &Expr) -> Option<&'b [String]> {
-        if !matches!(func_ref, Expr::FunctionDefinition(_, _, _)) {
-            // Now we see why this approach is very bad with loose pointers
-            panic!("Cannot call upvalue_names on a non-function def Expr")
-        }
-
-        if let Some(attrs) = self.attributes.get(&ref_id(func_ref)) {
-            attrs.iter().find_map(|attr| {
-                if let NodeAttr::UpValues(names) = attr {
-                    Some(names.as_slice())
-                } else {
-                    None
-                }
-            })
-        } else {
-            None
-        }
+    pub fn upvalue_names(&self, func: NodeId) -> Option<&[String]> {
+        self.attrs(func).find_map(|attr| {
+            if let NodeAttr::UpValues(names) = attr {
+                Some(names.as_slice())
+            } else {
+                None
+            }
+        })
     }
 
-    /// For a FunctionDefinition Expr, returns the potential name to use for recursive calls
-    pub fn rec_name(&self, func_ref: &Expr) -> Option<String> {
-        self.recursion_name_raw(ref_id(func_ref))
+    /// For a function definition, returns the potential name to use for recursive calls
+    pub fn rec_name(&self, func: NodeId) -> Option<String> {
+        self.attrs(func).find_map(|attr| {
+            if let NodeAttr::RecursionName(name) = attr {
+                Some(name.clone())
+            } else {
+                None
+            }
+        })
     }
 
     /// Get the max number of locals for a function definition, including arguments
-    pub fn local_count(&self, func_ref: &Expr) -> Option<usize> {
-        let id = ref_id(func_ref);
-        self.local_count_raw(id)
-    }
-
-    /// Gets the max number of locals in in the script, outside all functions
-    pub fn global_local_count(&self) -> usize {
-        let id = ref_id(self.stmts);
-        self.local_count_raw(id)
-            .expect("Should have a scipt-level local count")
-    }
-
-    pub fn local_count_raw(&self, id: RefId) -> Option<usize> {
-        self.attributes.get(&id)?.into_iter().find_map(|attr| {
+    pub fn local_count(&self, func: NodeId) -> Option<usize> {
+        self.attrs(func).find_map(|attr| {
             if let NodeAttr::LocalCount(count) = attr {
                 Some(*count)
             } else {
@@ -101,14 +102,9 @@ impl<'a> AttributedAst<'a> {
         })
     }
 
-    fn recursion_name_raw(&self, id: RefId) -> Option<String> {
-        self.attributes.get(&id)?.into_iter().find_map(|attr| {
-            if let NodeAttr::RecursionName(name) = attr {
-                Some(name.clone())
-            } else {
-                None
-            }
-        })
+    /// Gets the max number of locals in in the script, outside all functions
+    pub fn global_local_count(&self) -> usize {
+        self.global_local_count
     }
 
     pub fn stmts(&self) -> &'a Stmts {
@@ -119,10 +115,16 @@ impl<'a> AttributedAst<'a> {
         Self {
             stmts,
             attributes: HashMap::new(),
+            global_local_count: 0,
         }
     }
 
-    fn merge_singles(&mut self, attrs: HashMap<RefId, NodeAttr>) {
+    /// All attributes of a node
+    fn attrs(&self, id: NodeId) -> impl Iterator<Item = &NodeAttr> {
+        self.attributes.get(&id).into_iter().flatten()
+    }
+
+    fn merge(&mut self, attrs: impl IntoIterator<Item = (NodeId, NodeAttr)>) {
         for (id, attr) in attrs {
             self.attributes.entry(id).or_insert(vec![]).push(attr)
         }
@@ -131,8 +133,8 @@ impl<'a> AttributedAst<'a> {
 
 #[derive(Debug, PartialEq)]
 enum NodeAttr {
-    /// If a declaration is an upvalue
-    UpValue,
+    /// A variable bound in the node which is an upvalue
+    UpValue(String),
 
     /// All upvalues captured by a function
     UpValues(Vec<String>),
@@ -143,3 +145,40 @@ enum NodeAttr {
     /// The max number of locals declared in each function definition
     LocalCount(usize),
 }
+
+#[cfg(test)]
+mod tests {
+    use parser::{Expr, Stmt};
+
+    use super::analyze_ast;
+
+    #[test]
+    fn attributes_survive_cloning() {
+        let stmts = parser::parse("test", "fn outer() -> { x := 1; \\ -> x }").unwrap();
+        let cloned = stmts.clone();
+        let attributed = analyze_ast(&stmts);
+
+        // The analysis of the original can be looked up with the nodes of the clone
+        let Stmt::Decl(_, Some(outer)) = cloned.stmts[0].node.as_ref() else {
+            panic!("Expected a declaration")
+        };
+        assert_eq!(attributed.rec_name(outer.id), Some("outer".to_string()));
+        assert_eq!(attributed.local_count(outer.id), Some(1));
+
+        let Expr::FunctionDefinition(_, _, body, _) = outer.node.as_ref() else {
+            panic!("Expected a function definition")
+        };
+        let Expr::Block(body) = body.node.as_ref() else {
+            panic!("Expected a block")
+        };
+        let Stmt::Expr(inner) = body.stmts[1].node.as_ref() else {
+            panic!("Expected an expression")
+        };
+        assert!(attributed.is_upvalue(body.stmts[0].id, "x"));
+        assert_eq!(
+            attributed.upvalue_names(inner.id),
+            Some(&["x".to_string()][..])
+        );
+        assert_eq!(attributed.upvalue_names(outer.id), None);
+    }
+}
@@ -0,0 +1,410 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use parser::{CodeLoc, CodeRange, Expr, ExprNode, Index, LValue, StmtNode, Stmts};
+
+use crate::visitor::AstVisitor;
+
+/// A variable which is declared but never read
+pub const UNUSED_VARIABLE: &str = "W001";
+
+/// A declaration which hides one of the native functions
+pub const SHADOWED_NATIVE: &str = "W002";
+
+/// A statement which can never be reached, as it follows a return, break or continue
+pub const UNREACHABLE_CODE: &str = "W003";
+
+/// An if expression without an else, whose value is used even though it can be nil
+pub const IF_WITHOUT_ELSE: &str = "W004";
+
+/// A possible mistake in a program, which does not stop it from running
+#[derive(Debug, PartialEq)]
+pub struct LintWarning {
+    pub code: &'static str,
+    pub range: CodeRange,
+    pub message: String,
+}
+
+/// Finds suspicious but valid code in a program
+///
+/// A warning is suppressed by a comment such as `// lint: allow W001`, either on the line
+/// of the warning or on the line above it. The natives are the names to warn about shadowing.
+///
+/// Global functions and enum variants are not reported as unused, as they are what files
+/// included by other files export.
+pub fn lint(stmts: &Stmts, source: &str, natives: &[&str]) -> Vec<LintWarning> {
+    let mut linter = Linter {
+        scopes: vec![],
+        globals: vec![],
+        global_reads: HashSet::new(),
+        natives,
+        source,
+        in_params: false,
+        range: CodeRange::from_ints(0, 0, 0, 0, 0, 0),
+        warnings: vec![],
+    };
+    linter.visit_stmts(stmts);
+
+    let exported: HashSet<&String> = stmts
+        .stmts
+        .iter()
+        .filter_map(|stmt| match stmt.node.as_ref() {
+            parser::Stmt::Decl(LValue::Var(name), Some(init))
+                if matches!(
+                    init.node.as_ref(),
+                    Expr::FunctionDefinition(_, _, _, _) | Expr::EnumVariant(_, _, _)
+                ) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        })
+        .collect();
+    let globals = std::mem::take(&mut linter.globals);
+    for global in globals {
+        if !linter.global_reads.contains(&global.name) && !exported.contains(&global.name) {
+            linter.warn_unused(global);
+        }
+    }
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut warnings = linter.warnings;
+    warnings.retain(|warning| !is_suppressed(warning, &lines));
+    warnings.sort_by_key(|warning| (warning.range.sl(), warning.range.sc()));
+    warnings
+}
+
+/// Checks for a lint comment allowing the warning on its line, or the one above
+fn is_suppressed(warning: &LintWarning, lines: &[&str]) -> bool {
+    let line = warning.range.sl() as usize;
+    [line.checked_sub(2), line.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter_map(|ind| lines.get(ind))
+        .any(|line| allowed_codes(line).any(|code| code == warning.code || code == "all"))
+}
+
+/// The codes allowed by a comment such as `// lint: allow W001, W003` on the line
+fn allowed_codes<'a>(line: &'a str) -> impl Iterator<Item = &'a str> {
+    line.split_once("//")
+        .and_then(|(_, comment)| comment.trim().strip_prefix("lint:"))
+        .and_then(|directive| directive.trim().strip_prefix("allow"))
+        .into_iter()
+        .flat_map(|codes| codes.split(',').map(str::trim))
+}
+
+/// A declared variable, and whether it has been read yet
+struct Local {
+    name: String,
+    range: CodeRange,
+    read: bool,
+}
+
+struct Linter<'a> {
+    /// The locals declared in each nested scope, innermost last
+    scopes: Vec<Vec<Local>>,
+
+    /// The global declarations, which are checked after visiting the whole program as they
+    /// can be read before they are declared
+    globals: Vec<Local>,
+
+    /// The names read anywhere without a local declaration
+    global_reads: HashSet<String>,
+
+    natives: &'a [&'a str],
+
+    source: &'a str,
+
+    /// Set while visiting the parameters of a function
+    in_params: bool,
+
+    /// The range of the innermost statement or expression being visited
+    range: CodeRange,
+
+    warnings: Vec<LintWarning>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, code: &'static str, range: CodeRange, message: String) {
+        self.warnings.push(LintWarning {
+            code,
+            range,
+            message,
+        });
+    }
+
+    fn warn_unused(&mut self, variable: Local) {
+        if !variable.read && !variable.name.starts_with('_') && !variable.name.starts_with('@') {
+            self.warn(
+                UNUSED_VARIABLE,
+                variable.range,
+                format!("Variable '{}' is never read", variable.name),
+            );
+        }
+    }
+
+    /// The range of a parameter name within the function definition being visited
+    ///
+    /// Patterns have no ranges of their own, so the name is looked up in the source.
+    fn param_range(&self, name: &str) -> Option<CodeRange> {
+        let start = self.range.start().index();
+        let code = self.source.get(start..self.range.end().index())?;
+        // The parameters follow the name of a declared function
+        let params_start = if code.starts_with("fn") {
+            code.find('(')?
+        } else {
+            0
+        };
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+        let (offset, _) = code[params_start..]
+            .match_indices(name)
+            .find(|(offset, _)| {
+                let before = code[..params_start + offset].chars().next_back();
+                let after = code[params_start + offset + name.len()..].chars().next();
+                !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+            })?;
+        let index = start + params_start + offset;
+        Some(CodeRange::from_locs(
+            self.loc(index),
+            self.loc(index + name.len()),
+        ))
+    }
+
+    fn loc(&self, index: usize) -> CodeLoc {
+        let before = &self.source[..index];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        CodeLoc::new(
+            index,
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    /// Visits the target of an assignment, where plain variables are written but not read
+    fn visit_assigned(&mut self, lvalue: &LValue) {
+        match lvalue {
+            LValue::Var(_) | LValue::Wildcard => (),
+            LValue::Tuple(lvalues) => {
+                for lvalue in lvalues {
+                    self.visit_assigned(lvalue)
+                }
+            }
+            LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => self.visit_assigned(lvalue),
+            LValue::Variant(name, fields) => {
+                self.visit_var(name, false);
+                for field in fields {
+                    self.visit_assigned(field)
+                }
+            }
+            LValue::Index(_, _) | LValue::Constant(_) => self.visit_lvalue(lvalue, false),
+        }
+    }
+
+    /// Warns if the value of the expression can be an if without an else
+    fn check_value(&mut self, expr: &ExprNode) {
+        match expr.node.as_ref() {
+            Expr::If(_, _, None) => self.warn(
+                IF_WITHOUT_ELSE,
+                expr.range(),
+                "The value of an if without an else is nil when the condition is false".to_string(),
+            ),
+            Expr::Block(stmts) if stmts.output => {
+                if let Some(parser::Stmt::Expr(last)) =
+                    stmts.stmts.last().map(|stmt| stmt.node.as_ref())
+                {
+                    self.check_value(last)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Warns about the first statement following one which always jumps away
+    fn check_reachable(&mut self, stmts: &[StmtNode]) {
+        let jump = stmts.iter().position(|stmt| {
+            matches!(
+                stmt.node.as_ref(),
+                parser::Stmt::Expr(expr) if matches!(
+                    expr.node.as_ref(),
+                    Expr::Return(_) | Expr::Break(_, _) | Expr::Continue(_)
+                )
+            )
+        });
+
+        if let Some(unreachable) = jump.and_then(|ind| stmts.get(ind + 1)) {
+            self.warn(
+                UNREACHABLE_CODE,
+                unreachable.range(),
+                "Unreachable statement".to_string(),
+            );
+        }
+    }
+}
+
+impl<'a> AstVisitor for Linter<'a> {
+    fn enter_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    /// Warns about the locals never read in the scope
+    fn exit_scope(&mut self) {
+        for local in self.scopes.pop().unwrap_or_default() {
+            self.warn_unused(local);
+        }
+    }
+
+    fn visit_stmts(&mut self, stmts: &Stmts) {
+        self.check_reachable(&stmts.stmts);
+        for stmt in stmts.stmts.iter() {
+            self.visit_stmt(stmt)
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        let outer = std::mem::replace(&mut self.range, stmt.range());
+        match stmt.node.as_ref() {
+            parser::Stmt::Decl(lvalue, init) => {
+                if let Some(expr) = init {
+                    self.check_value(expr);
+                }
+                self.visit_decl(lvalue, init.as_ref())
+            }
+            parser::Stmt::Expr(expr) => self.visit_expr(expr),
+            parser::Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            parser::Stmt::Invalid => (),
+        }
+        self.range = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        let outer = std::mem::replace(&mut self.range, expr.range());
+        self.visit_expr_delegation(expr);
+        self.range = outer;
+    }
+
+    fn visit_var(&mut self, name: &String, declaration: bool) {
+        if declaration {
+            let param_range = if self.in_params {
+                self.param_range(name)
+            } else {
+                None
+            };
+            let range = param_range.unwrap_or_else(|| self.range.clone());
+            if self.natives.contains(&name.as_str()) {
+                self.warn(
+                    SHADOWED_NATIVE,
+                    range.clone(),
+                    format!("The declaration of '{name}' shadows the native function"),
+                );
+            }
+            let variable = Local {
+                name: name.clone(),
+                range,
+                read: false,
+            };
+            match self.scopes.last_mut() {
+                Some(scope) => scope.push(variable),
+                None => self.globals.push(variable),
+            }
+        } else if let Some(local) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|local| &local.name == name))
+        {
+            local.read = true;
+        } else {
+            self.global_reads.insert(name.clone());
+        }
+    }
+
+    fn visit_function_definition(&mut self, _name: &str, params: &[LValue], body: &ExprNode) {
+        self.scoped(|linter| {
+            linter.in_params = true;
+            for param in params {
+                linter.visit_lvalue(param, true);
+            }
+            linter.in_params = false;
+            linter.visit_expr(body);
+        });
+    }
+
+    fn visit_assign(&mut self, lvalue: &LValue, value: &ExprNode) {
+        self.visit_assigned(lvalue);
+        self.check_value(value);
+        self.visit_expr(value);
+    }
+
+    fn visit_call(&mut self, callee: &ExprNode, args: &[ExprNode]) {
+        self.visit_expr(callee);
+        for arg in args {
+            self.check_value(arg);
+            self.visit_expr(arg);
+        }
+    }
+
+    fn visit_index_into(&mut self, indexee: &ExprNode, at: &Index) {
+        self.visit_expr(indexee);
+        if let Index::At(expr) = at {
+            self.check_value(expr);
+        }
+        self.visit_index(at);
+    }
+
+    fn visit_return(&mut self, ret: Option<&ExprNode>) {
+        if let Some(expr) = ret {
+            self.check_value(expr);
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_break(&mut self, _label: Option<&String>, value: Option<&ExprNode>) {
+        if let Some(expr) = value {
+            self.check_value(expr);
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_match(&mut self, matched: &ExprNode, options: &[(LValue, ExprNode)]) {
+        self.visit_expr(matched);
+        for (lvalue, then) in options {
+            self.scoped(|linter| {
+                // Patterns have no location, so use the start of the arm for its bindings
+                let outer = std::mem::replace(&mut linter.range, then.range());
+                linter.visit_lvalue(lvalue, true);
+                linter.range = outer;
+                linter.visit_expr(then);
+            });
+        }
+    }
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WARNING {}: [{}] {}",
+            self.code, self.range, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::allowed_codes;
+
+    #[test]
+    fn suppression_comments() {
+        let codes: Vec<&str> = allowed_codes("x := 1; // lint: allow W001, W003").collect();
+        assert_eq!(codes, vec!["W001", "W003"]);
+        assert_eq!(
+            allowed_codes("// lint:allow all").collect::<Vec<_>>(),
+            vec!["all"]
+        );
+        assert_eq!(allowed_codes("x := 1; // allow W001").count(), 0);
+        assert_eq!(allowed_codes("x := 1;").count(), 0);
+    }
+}
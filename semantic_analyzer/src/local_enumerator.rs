@@ -1,12 +1,14 @@
 use std::{collections::HashMap, mem};
 
-use parser::{ExprNode, LValue, Stmts};
+use parser::{ExprNode, LValue, NodeId, Stmts};
 
-use crate::{ref_id, visitor::AstVisitor, NodeAttr, RefId};
+use crate::{visitor::AstVisitor, NodeAttr};
 
 /// Counts how many locals there are at most in each function (including arguments)
 /// This only counts args and locals, no functions or other things on the stack
-pub(crate) fn count_locals(stmts: &Stmts) -> HashMap<RefId, NodeAttr> {
+///
+/// Returns the count for the script outside all functions, and the attributes of the functions
+pub(crate) fn count_locals(stmts: &Stmts) -> (usize, HashMap<NodeId, NodeAttr>) {
     // TODO: We could just do this as part of the compilation pass.
     let mut counter = Counter {
         scope: VarScope {
@@ -22,29 +24,25 @@ pub(crate) fn count_locals(stmts: &Stmts) -> HashMap<RefId, NodeAttr> {
 
     counter.visit_stmts(stmts);
 
-    // Also insert for the local scope
-    counter
-        .func_count
-        .insert(ref_id(stmts), counter.scope.max_vars);
-
-    counter
+    let func_counts = counter
         .func_count
         .into_iter()
         .map(|(id, count)| (id, NodeAttr::LocalCount(count)))
-        .collect()
+        .collect();
+    (counter.scope.max_vars, func_counts)
 }
 
 struct Counter {
     scope: VarScope,
 
     /// The id of the most recently entered expression
-    expr_id: Option<RefId>,
+    expr_id: Option<NodeId>,
 
     /// If we are at global scope, meaning variables are globals
     global_scope: bool,
 
     /// The local + arg count for each function definition
-    func_count: HashMap<RefId, usize>,
+    func_count: HashMap<NodeId, usize>,
 }
 
 struct VarScope {
@@ -121,7 +119,7 @@ impl AstVisitor for Counter {
 
     fn visit_expr(&mut self, expr: &parser::ExprNode) {
         let outer_id = self.expr_id;
-        self.expr_id = Some(ref_id(expr.node.as_ref()));
+        self.expr_id = Some(expr.id);
 
         // Default
         self.visit_expr_delegation(expr);
@@ -179,7 +177,53 @@ impl AstVisitor for Counter {
         }
     }
 
-    fn visit_for(&mut self, lvalue: &LValue, collection: &ExprNode, body: &ExprNode) {
+    fn visit_if_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        then: &ExprNode,
+        otherwise: Option<&ExprNode>,
+    ) {
+        self.visit_expr(matched);
+
+        self.scope.enter_block();
+        self.visit_lvalue(lvalue, true);
+        self.visit_expr(then);
+        self.scope.exit_block();
+
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
+        self.visit_expr(matched);
+
+        self.scope.enter_block();
+        self.visit_lvalue(lvalue, true);
+        self.visit_expr(body);
+        self.scope.exit_block();
+
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_for(
+        &mut self,
+        lvalue: &LValue,
+        collection: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
         self.scope.enter_block();
 
         self.visit_lvalue(lvalue, true);
@@ -187,5 +231,9 @@ impl AstVisitor for Counter {
         self.visit_expr(body);
 
         self.scope.exit_block();
+
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
     }
 }
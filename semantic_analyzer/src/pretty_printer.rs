@@ -37,14 +37,15 @@ impl AstVisitor for Prettifier {
         match stmt.node.as_ref() {
             parser::Stmt::Decl(lvalue, init) => self.visit_decl(lvalue, init.as_ref()),
             parser::Stmt::Expr(expr) => self.visit_expr(expr),
-            parser::Stmt::Invalid => println!("WARNING: Visiting invalid AST node"),
+            parser::Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            parser::Stmt::Invalid => (),
         }
         self.app(";\n");
 
         if let parser::Stmt::Decl(_, Some(expr)) = stmt.node.as_ref() {
             if matches!(
                 expr.node.as_ref(),
-                parser::Expr::FunctionDefinition(_, _, _)
+                parser::Expr::FunctionDefinition(_, _, _, _)
             ) {
                 self.app("\n")
             }
@@ -52,6 +53,34 @@ impl AstVisitor for Prettifier {
     }
 
     fn visit_decl(&mut self, lvalue: &parser::LValue, init: Option<&parser::ExprNode>) {
+        if let Some(expr) = init {
+            if let parser::Expr::EnumVariant(_, _, _) = expr.node.as_ref() {
+                // Each variant is printed as its own enum declaration
+                return self.visit_expr(expr);
+            }
+        }
+
+        if let (parser::LValue::Var(name), Some(expr)) = (lvalue, init) {
+            if let parser::Expr::FunctionDefinition(_, params, body, Some(returns)) =
+                expr.node.as_ref()
+            {
+                // Return types can only be annotated in fn declarations, not in lambdas
+                self.app("fn ");
+                self.app(name);
+                self.app("(");
+                for (i, param) in params.iter().enumerate() {
+                    if i != 0 {
+                        self.app(", ")
+                    }
+                    self.visit_lvalue(param, true);
+                }
+                self.app(") -> ");
+                self.app(returns.name());
+                self.app(" -> ");
+                return self.visit_expr(body);
+            }
+        }
+
         self.visit_lvalue(lvalue, true);
 
         self.app(" := ");
@@ -61,14 +90,29 @@ impl AstVisitor for Prettifier {
         }
     }
 
+    fn visit_assert(&mut self, cond: &parser::ExprNode, message: Option<&parser::ExprNode>) {
+        self.app("assert!(");
+        self.visit_expr(cond);
+        if let Some(expr) = message {
+            self.app(", ");
+            self.visit_expr(expr);
+        }
+        self.app(")");
+    }
+
     fn visit_expr_delegation(&mut self, expr: &parser::ExprNode) {
         match expr.node.as_ref() {
             parser::Expr::Call(callee, args) => self.visit_call(callee, args),
             parser::Expr::IndexInto(indexee, at) => self.visit_index_into(indexee, at),
+            parser::Expr::SafeIndexInto(indexee, at) => self.visit_safe_index_into(indexee, at),
             parser::Expr::Binary(x, op, y) => self.visit_binary(x, op, y),
+            parser::Expr::ChainedComparison(first, links) => {
+                self.visit_chained_comparison(first, links)
+            }
             parser::Expr::Unary(op, x) => self.visit_unary(op, x),
             parser::Expr::Logical(x, op, y) => self.visit_logical(x, op, y),
             parser::Expr::Assign(lvalue, value) => self.visit_assign(lvalue, value),
+            parser::Expr::OpAssign(lvalue, op, value) => self.visit_op_assign(lvalue, op, value),
             parser::Expr::Var(name) => self.visit_var(name, false),
             parser::Expr::Int(int) => self.visit_int(*int),
             parser::Expr::Float(float) => self.visit_float(*float),
@@ -78,18 +122,35 @@ impl AstVisitor for Prettifier {
             parser::Expr::If(cond, then, otherwise) => {
                 self.visit_if(cond, then, otherwise.as_ref())
             }
-            parser::Expr::While(cond, body) => self.visit_while(cond, body),
-            parser::Expr::For(lvalue, collection, body) => self.visit_for(lvalue, collection, body),
-            parser::Expr::Break => self.visit_break(),
-            parser::Expr::Continue => self.visit_continue(),
+            parser::Expr::IfLet(lvalue, matched, then, otherwise) => {
+                self.visit_if_let(lvalue, matched, then, otherwise.as_ref())
+            }
+            parser::Expr::While(cond, body, otherwise, label) => {
+                self.visit_while(cond, body, otherwise.as_ref(), label.as_ref())
+            }
+            parser::Expr::WhileLet(lvalue, matched, body, otherwise, label) => {
+                self.visit_while_let(lvalue, matched, body, otherwise.as_ref(), label.as_ref())
+            }
+            parser::Expr::For(lvalue, collection, body, otherwise, label) => {
+                self.visit_for(lvalue, collection, body, otherwise.as_ref(), label.as_ref())
+            }
+            parser::Expr::Break(label, value) => self.visit_break(label.as_ref(), value.as_ref()),
+            parser::Expr::Continue(label) => self.visit_continue(label.as_ref()),
             parser::Expr::Return(ret) => self.visit_return(ret.as_ref()),
             parser::Expr::Nil => self.visit_nil(),
             parser::Expr::List(content) => self.visit_list(content),
+            parser::Expr::Dict(pairs) => self.visit_dict(pairs),
+            parser::Expr::Set(exprs) => self.visit_set(exprs),
             parser::Expr::Tuple(exprs) => self.visit_tuple(exprs),
-            parser::Expr::FunctionDefinition(name, params, body) => {
+            parser::Expr::FunctionDefinition(name, params, body, _) => {
                 self.visit_function_definition(name, params, body)
             }
             parser::Expr::Match(matched, options) => self.visit_match(matched, options),
+            parser::Expr::Rest(name) => self.visit_rest(name.as_ref()),
+            parser::Expr::EnumVariant(enum_name, name, fields) => {
+                self.visit_enum_variant(enum_name, name, fields)
+            }
+            parser::Expr::Invalid => (),
         }
     }
 
@@ -122,6 +183,13 @@ impl AstVisitor for Prettifier {
         self.app("]");
     }
 
+    fn visit_safe_index_into(&mut self, indexee: &parser::ExprNode, at: &parser::Index) {
+        self.visit_expr(indexee);
+        self.app("?[");
+        self.visit_index(at);
+        self.app("]");
+    }
+
     fn visit_binary(&mut self, x: &parser::ExprNode, op: &parser::BinOper, y: &parser::ExprNode) {
         self.app("(");
         self.visit_expr(x);
@@ -130,6 +198,20 @@ impl AstVisitor for Prettifier {
         self.app(")");
     }
 
+    fn visit_chained_comparison(
+        &mut self,
+        first: &parser::ExprNode,
+        links: &[(parser::BinOper, parser::ExprNode)],
+    ) {
+        self.app("(");
+        self.visit_expr(first);
+        for (op, operand) in links {
+            self.visit_binary_oper(op);
+            self.visit_expr(operand);
+        }
+        self.app(")");
+    }
+
     fn visit_unary(&mut self, op: &parser::UnOper, x: &parser::ExprNode) {
         self.app("(");
         self.visit_unary_oper(op);
@@ -156,6 +238,19 @@ impl AstVisitor for Prettifier {
         self.visit_expr(value);
     }
 
+    fn visit_op_assign(
+        &mut self,
+        lvalue: &parser::LValue,
+        op: &parser::BinOper,
+        value: &parser::ExprNode,
+    ) {
+        self.visit_lvalue(lvalue, false);
+
+        self.app(format!(" {}= ", binary_oper_str(op)));
+
+        self.visit_expr(value);
+    }
+
     fn visit_var(&mut self, name: &String, _declaration: bool) {
         self.app(name)
     }
@@ -204,11 +299,67 @@ impl AstVisitor for Prettifier {
         }
     }
 
-    fn visit_while(&mut self, cond: &parser::ExprNode, body: &parser::ExprNode) {
+    fn visit_if_let(
+        &mut self,
+        lvalue: &parser::LValue,
+        matched: &parser::ExprNode,
+        then: &parser::ExprNode,
+        otherwise: Option<&parser::ExprNode>,
+    ) {
+        self.app("if ");
+        self.visit_lvalue(lvalue, true);
+        self.app(" := ");
+        self.visit_expr(matched);
+        self.app(" ");
+        self.visit_expr(then);
+
+        if let Some(expr) = otherwise {
+            self.app(" else ");
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while_let(
+        &mut self,
+        lvalue: &parser::LValue,
+        matched: &parser::ExprNode,
+        body: &parser::ExprNode,
+        otherwise: Option<&parser::ExprNode>,
+        label: Option<&String>,
+    ) {
+        if let Some(label) = label {
+            self.app(format!("'{label}: "));
+        }
+        self.app("while ");
+        self.visit_lvalue(lvalue, true);
+        self.app(" := ");
+        self.visit_expr(matched);
+        self.app(" ");
+        self.visit_expr(body);
+        if let Some(expr) = otherwise {
+            self.app(" else ");
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while(
+        &mut self,
+        cond: &parser::ExprNode,
+        body: &parser::ExprNode,
+        otherwise: Option<&parser::ExprNode>,
+        label: Option<&String>,
+    ) {
+        if let Some(label) = label {
+            self.app(format!("'{label}: "));
+        }
         self.app("while ");
         self.visit_expr(cond);
         self.app(" ");
         self.visit_expr(body);
+        if let Some(expr) = otherwise {
+            self.app(" else ");
+            self.visit_expr(expr);
+        }
     }
 
     fn visit_for(
@@ -216,21 +367,54 @@ impl AstVisitor for Prettifier {
         lvalue: &parser::LValue,
         collection: &parser::ExprNode,
         body: &parser::ExprNode,
+        otherwise: Option<&parser::ExprNode>,
+        label: Option<&String>,
     ) {
+        if let Some(label) = label {
+            self.app(format!("'{label}: "));
+        }
         self.app("for ");
         self.visit_lvalue(lvalue, true);
         self.app(" in ");
         self.visit_expr(collection);
         self.app(" ");
         self.visit_expr(body);
+        if let Some(expr) = otherwise {
+            self.app(" else ");
+            self.visit_expr(expr);
+        }
     }
 
-    fn visit_break(&mut self) {
+    fn visit_break(&mut self, label: Option<&String>, value: Option<&parser::ExprNode>) {
         self.app("break");
+        if let Some(label) = label {
+            self.app(format!(" '{label}"));
+        }
+        if let Some(expr) = value {
+            self.app(" ");
+            self.visit_expr(expr);
+        }
     }
 
-    fn visit_continue(&mut self) {
+    fn visit_continue(&mut self, label: Option<&String>) {
         self.app("continue");
+        if let Some(label) = label {
+            self.app(format!(" '{label}"));
+        }
+    }
+
+    fn visit_rest(&mut self, name: Option<&String>) {
+        self.app("..");
+        if let Some(name) = name {
+            self.app(name);
+        }
+    }
+
+    fn visit_enum_variant(&mut self, enum_name: &str, name: &str, fields: &[String]) {
+        self.app(&format!(
+            "enum {enum_name} {{ {name}({}) }}",
+            fields.join(", ")
+        ));
     }
 
     fn visit_return(&mut self, ret: Option<&parser::ExprNode>) {
@@ -260,6 +444,33 @@ impl AstVisitor for Prettifier {
         self.app("]");
     }
 
+    fn visit_dict(&mut self, pairs: &[(parser::ExprNode, parser::ExprNode)]) {
+        self.app("{");
+        if pairs.is_empty() {
+            self.app(":");
+        }
+        for (i, (key, value)) in pairs.iter().enumerate() {
+            if i != 0 {
+                self.app(", ");
+            }
+            self.visit_expr(key);
+            self.app(": ");
+            self.visit_expr(value);
+        }
+        self.app("}");
+    }
+
+    fn visit_set(&mut self, exprs: &[parser::ExprNode]) {
+        self.app("#{");
+        for (i, expr) in exprs.iter().enumerate() {
+            if i != 0 {
+                self.app(", ");
+            }
+            self.visit_expr(expr)
+        }
+        self.app("}");
+    }
+
     fn visit_tuple(&mut self, exprs: &[parser::ExprNode]) {
         self.app("(");
         for (i, expr) in exprs.iter().enumerate() {
@@ -321,27 +532,14 @@ impl AstVisitor for Prettifier {
     }
 
     fn visit_binary_oper(&mut self, op: &parser::BinOper) {
-        match op {
-            parser::BinOper::Add => self.app(" + "),
-            parser::BinOper::Sub => self.app(" - "),
-            parser::BinOper::Div => self.app(" / "),
-            parser::BinOper::Mult => self.app(" * "),
-            parser::BinOper::Mod => self.app(" % "),
-            parser::BinOper::Pow => self.app(" ^ "),
-            parser::BinOper::Eq => self.app(" == "),
-            parser::BinOper::Neq => self.app(" != "),
-            parser::BinOper::Lt => self.app(" < "),
-            parser::BinOper::Leq => self.app(" <= "),
-            parser::BinOper::Gt => self.app(" > "),
-            parser::BinOper::Geq => self.app(" >= "),
-            parser::BinOper::Append => self.app(" ++ "),
-        }
+        self.app(format!(" {} ", binary_oper_str(op)));
     }
 
     fn visit_unary_oper(&mut self, op: &parser::UnOper) {
         match op {
             parser::UnOper::Not => self.app("!"),
             parser::UnOper::Sub => self.app("-"),
+            parser::UnOper::BitNot => self.app("~"),
         }
     }
 
@@ -349,6 +547,7 @@ impl AstVisitor for Prettifier {
         match op {
             parser::LogicalOper::And => self.app(" and "),
             parser::LogicalOper::Or => self.app(" or "),
+            parser::LogicalOper::Coalesce => self.app(" ?? "),
         }
     }
 
@@ -367,6 +566,29 @@ impl AstVisitor for Prettifier {
                 self.app(")");
             }
             parser::LValue::Constant(expr) => self.visit_expr(expr),
+            parser::LValue::Wildcard => self.app("_"),
+            parser::LValue::Rest(lvalue) => {
+                self.app("..");
+                if !matches!(lvalue.as_ref(), parser::LValue::Wildcard) {
+                    self.visit_lvalue(lvalue, declaration)
+                }
+            }
+            parser::LValue::Typed(lvalue, annotated) => {
+                self.visit_lvalue(lvalue, declaration);
+                self.app(": ");
+                self.app(annotated.name());
+            }
+            parser::LValue::Variant(name, fields) => {
+                self.app(name);
+                self.app("(");
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        self.app(", ");
+                    }
+                    self.visit_lvalue(field, declaration)
+                }
+                self.app(")");
+            }
         }
     }
 
@@ -398,3 +620,28 @@ impl AstVisitor for Prettifier {
         }
     }
 }
+
+fn binary_oper_str(op: &parser::BinOper) -> &'static str {
+    match op {
+        parser::BinOper::Add => "+",
+        parser::BinOper::Sub => "-",
+        parser::BinOper::Div => "/",
+        parser::BinOper::Mult => "*",
+        parser::BinOper::Mod => "%",
+        parser::BinOper::Pow => "^",
+        parser::BinOper::Eq => "==",
+        parser::BinOper::Neq => "!=",
+        parser::BinOper::Lt => "<",
+        parser::BinOper::Leq => "<=",
+        parser::BinOper::Gt => ">",
+        parser::BinOper::Geq => ">=",
+        parser::BinOper::Append => "++",
+        parser::BinOper::BitAnd => "&",
+        parser::BinOper::BitOr => "|",
+        parser::BinOper::BitXor => "xor",
+        parser::BinOper::LShift => "<<",
+        parser::BinOper::RShift => ">>>",
+        parser::BinOper::In => "in",
+        parser::BinOper::NotIn => "not in",
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use parser::{
+    BinOper, CodeRange, Expr, ExprNode, LValue, NodeId, Stmt, StmtNode, Stmts, Type, UnOper,
+};
+
+use crate::{
+    arity_checking::{lvalue_names, AssignedNames},
+    visitor::AstVisitor,
+};
+
+/// A value which is known before running to not have its annotated type
+#[derive(Debug, PartialEq)]
+pub struct TypeError {
+    pub range: CodeRange,
+    pub message: String,
+}
+
+/// Checks the type annotations against the types which can be inferred
+///
+/// The checking is gradual, so only values with a known type are checked, and unannotated
+/// variables can hold anything. Calls are only checked for global functions which are
+/// declared once and never assigned to.
+pub fn find_type_errors(stmts: &Stmts) -> Vec<TypeError> {
+    let mut assigned = AssignedNames::default();
+    assigned.visit_stmts(stmts);
+
+    let mut declarations: HashMap<&String, usize> = HashMap::new();
+    let mut signatures = HashMap::new();
+    let mut globals = HashMap::new();
+    for stmt in stmts.stmts.iter() {
+        if let Stmt::Decl(lvalue, init) = stmt.node.as_ref() {
+            for name in lvalue_names(lvalue) {
+                *declarations.entry(name).or_default() += 1;
+            }
+
+            match (lvalue, init.as_ref().map(|expr| expr.node.as_ref())) {
+                (LValue::Var(name), Some(Expr::FunctionDefinition(_, params, _, returns))) => {
+                    let signature = Signature {
+                        params: params.iter().map(annotation).collect(),
+                        returns: *returns,
+                    };
+                    signatures.insert(name.clone(), signature);
+                }
+                (LValue::Typed(inner, annotated), _) => {
+                    if let LValue::Var(name) = inner.as_ref() {
+                        globals.insert(name.clone(), *annotated);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    signatures.retain(|name, _| {
+        declarations.get(name).copied().unwrap_or_default() <= 1 && !assigned.names.contains(name)
+    });
+
+    let mut checker = TypeChecker {
+        signatures,
+        globals,
+        scopes: vec![],
+        returns: vec![],
+        types: HashMap::new(),
+        range: CodeRange::from_ints(0, 0, 0, 0, 0, 0),
+        errors: vec![],
+    };
+    checker.visit_stmts(stmts);
+    checker.errors
+}
+
+/// The annotated types of a global function
+struct Signature {
+    params: Vec<Option<Type>>,
+    returns: Option<Type>,
+}
+
+/// The annotated type of a parameter, if it is annotated
+fn annotation(lvalue: &LValue) -> Option<Type> {
+    match lvalue {
+        LValue::Typed(_, annotated) => Some(*annotated),
+        _ => None,
+    }
+}
+
+/// The variable and its type, if the lvalue is an annotated variable
+fn typed_var(lvalue: &LValue) -> Option<(&String, Type)> {
+    match lvalue {
+        LValue::Typed(inner, annotated) => match inner.as_ref() {
+            LValue::Var(name) => Some((name, *annotated)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct TypeChecker {
+    signatures: HashMap<String, Signature>,
+
+    /// The annotated global variables
+    globals: HashMap<String, Type>,
+
+    /// The local variables in each nested scope, with their types if annotated
+    scopes: Vec<HashMap<String, Option<Type>>>,
+
+    /// The annotated return types of the nested functions being visited
+    returns: Vec<Option<Type>>,
+
+    /// The inferred types of the visited expressions
+    types: HashMap<NodeId, Type>,
+
+    /// The range of the innermost expression being visited
+    range: CodeRange,
+
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker {
+    fn error(&mut self, message: String) {
+        self.errors.push(TypeError {
+            range: self.range.clone(),
+            message,
+        });
+    }
+
+    /// The inferred type of an already visited expression
+    fn type_of(&self, expr: &ExprNode) -> Option<Type> {
+        self.types.get(&expr.id).copied()
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+    }
+
+    fn var_type(&self, name: &str) -> Option<Type> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(local) => *local,
+            None => self.globals.get(name).copied(),
+        }
+    }
+
+    fn declare(&mut self, name: &str, annotated: Option<Type>) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.to_string(), annotated);
+            }
+            None => match annotated {
+                Some(annotated) => {
+                    self.globals.insert(name.to_string(), annotated);
+                }
+                None => {
+                    self.globals.remove(name);
+                }
+            },
+        }
+    }
+
+    /// Reports an error if the expression is known to not fit the expected type
+    fn check(&mut self, expr: &ExprNode, expected: Option<Type>, what: impl FnOnce() -> String) {
+        if let (Some(found), Some(expected)) = (self.type_of(expr), expected) {
+            if !found.fits(&expected) {
+                let range = std::mem::replace(&mut self.range, expr.range());
+                self.error(format!(
+                    "Expected {} to be {expected}, but found {found}",
+                    what()
+                ));
+                self.range = range;
+            }
+        }
+    }
+
+    /// Infers the type of an expression, after its sub-expressions have been visited
+    fn infer(&self, expr: &ExprNode) -> Option<Type> {
+        match expr.node.as_ref() {
+            Expr::Int(_) => Some(Type::Int),
+            Expr::Float(_) => Some(Type::Float),
+            Expr::Bool(_) | Expr::ChainedComparison(_, _) => Some(Type::Bool),
+            Expr::String(_) => Some(Type::Str),
+            Expr::Nil => Some(Type::Nil),
+            Expr::List(_) => Some(Type::List),
+            Expr::Dict(_) | Expr::Set(_) => Some(Type::Dict),
+            Expr::EnumVariant(_, _, fields) if fields.is_empty() => None,
+            Expr::FunctionDefinition(_, _, _, _) | Expr::EnumVariant(_, _, _) => Some(Type::Fn),
+            Expr::Var(name) => self.var_type(name),
+            Expr::Assign(_, value) => self.type_of(value),
+            Expr::Call(callee, _) => match callee.node.as_ref() {
+                Expr::Var(name) if !self.is_local(name) => self.signatures.get(name)?.returns,
+                _ => None,
+            },
+            Expr::Binary(x, op, y) => self.infer_binary(self.type_of(x)?, op, self.type_of(y)?),
+            Expr::Unary(UnOper::Not, _) => Some(Type::Bool),
+            Expr::Unary(UnOper::Sub, x) => match self.type_of(x)? {
+                numerical @ (Type::Int | Type::Float) => Some(numerical),
+                _ => None,
+            },
+            Expr::Unary(UnOper::BitNot, x) => (self.type_of(x)? == Type::Int).then_some(Type::Int),
+            Expr::If(_, then, Some(otherwise)) => {
+                let then = self.type_of(then)?;
+                (self.type_of(otherwise)? == then).then_some(then)
+            }
+            Expr::Block(stmts) if stmts.output => match stmts.stmts.last()?.node.as_ref() {
+                Stmt::Expr(last) => self.type_of(last),
+                _ => None,
+            },
+            Expr::Match(_, arms) => {
+                let first = self.type_of(&arms.first()?.1)?;
+                arms.iter()
+                    .all(|(_, then)| self.type_of(then) == Some(first))
+                    .then_some(first)
+            }
+            _ => None,
+        }
+    }
+
+    fn infer_binary(&self, x: Type, op: &BinOper, y: Type) -> Option<Type> {
+        match op {
+            BinOper::Eq
+            | BinOper::Neq
+            | BinOper::Lt
+            | BinOper::Leq
+            | BinOper::Gt
+            | BinOper::Geq
+            | BinOper::In
+            | BinOper::NotIn => Some(Type::Bool),
+            BinOper::Add | BinOper::Sub | BinOper::Mult | BinOper::Div | BinOper::Mod => {
+                match (x, y) {
+                    (Type::Int, Type::Int) => Some(Type::Int),
+                    (Type::Int | Type::Float, Type::Int | Type::Float) => Some(Type::Float),
+                    _ => None,
+                }
+            }
+            BinOper::BitAnd
+            | BinOper::BitOr
+            | BinOper::BitXor
+            | BinOper::LShift
+            | BinOper::RShift => (x == Type::Int && y == Type::Int).then_some(Type::Int),
+            BinOper::Pow | BinOper::Append => None,
+        }
+    }
+}
+
+impl AstVisitor for TypeChecker {
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        match stmt.node.as_ref() {
+            Stmt::Decl(lvalue, init) => {
+                self.visit_decl(lvalue, init.as_ref());
+                if let (Some((name, annotated)), Some(expr)) = (typed_var(lvalue), init) {
+                    self.check(expr, Some(annotated), || format!("'{name}'"));
+                }
+            }
+            Stmt::Expr(expr) => self.visit_expr(expr),
+            Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            Stmt::Invalid => (),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        let outer = std::mem::replace(&mut self.range, expr.range());
+        match expr.node.as_ref() {
+            Expr::FunctionDefinition(_, _, body, returns) => {
+                // Handled here rather than in visit_function_definition to see the return type
+                self.returns.push(*returns);
+                self.visit_expr_delegation(expr);
+                self.check(body, *returns, || "the returned value".to_string());
+                self.returns.pop();
+            }
+            _ => self.visit_expr_delegation(expr),
+        }
+
+        if let Some(inferred) = self.infer(expr) {
+            self.types.insert(expr.id, inferred);
+        }
+        self.range = outer;
+    }
+
+    fn visit_var(&mut self, name: &String, declaration: bool) {
+        if declaration {
+            self.declare(name, None);
+        }
+    }
+
+    fn visit_lvalue(&mut self, lvalue: &LValue, declaration: bool) {
+        match (declaration, typed_var(lvalue)) {
+            (true, Some((name, annotated))) => self.declare(name, Some(annotated)),
+            _ => self.visit_lvalue_delegation(lvalue, declaration),
+        }
+    }
+
+    fn visit_assign(&mut self, lvalue: &LValue, value: &ExprNode) {
+        self.visit_lvalue(lvalue, false);
+        self.visit_expr(value);
+        if let LValue::Var(name) = lvalue {
+            self.check(value, self.var_type(name), || format!("'{name}'"));
+        }
+    }
+
+    fn visit_call(&mut self, callee: &ExprNode, args: &[ExprNode]) {
+        self.visit_expr(callee);
+        for arg in args {
+            self.visit_expr(arg)
+        }
+
+        let Expr::Var(name) = callee.node.as_ref() else {
+            return;
+        };
+        if self.is_local(name) {
+            return;
+        }
+        let Some(signature) = self.signatures.get(name) else {
+            return;
+        };
+
+        let params = signature.params.clone();
+        for (ind, (arg, param)) in args.iter().zip(params).enumerate() {
+            self.check(arg, param, || format!("argument {} of '{name}'", ind + 1));
+        }
+    }
+
+    fn visit_return(&mut self, ret: Option<&ExprNode>) {
+        if let Some(expr) = ret {
+            self.visit_expr(expr);
+            let returns = self.returns.last().copied().flatten();
+            self.check(expr, returns, || "the returned value".to_string());
+        }
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
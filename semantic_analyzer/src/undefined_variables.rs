@@ -0,0 +1,171 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use parser::{CodeRange, ExprNode, Stmt, StmtNode, Stmts};
+
+use crate::visitor::AstVisitor;
+
+/// A reference to a variable which is not declared where it is used
+#[derive(Debug, PartialEq)]
+pub struct UndefinedVariable {
+    pub name: String,
+    pub range: CodeRange,
+
+    /// The most similar name which is declared at that point, if any is close enough
+    pub suggestion: Option<String>,
+}
+
+/// Finds all references to variables which are not declared before they are used
+///
+/// The scoping follows the VM. Top-level declarations are global and can be used anywhere,
+/// while other declarations can only be used after them in their scope. The builtins are
+/// the names declared before the program, such as natives.
+pub fn find_undefined_variables(stmts: &Stmts, builtins: &[&str]) -> Vec<UndefinedVariable> {
+    let mut finder = UndefinedFinder {
+        scopes: vec![],
+        globals: builtins.iter().map(|name| name.to_string()).collect(),
+        collecting_globals: true,
+        range: CodeRange::from_ints(0, 0, 0, 0, 0, 0),
+        undefined: vec![],
+    };
+
+    // Globals are late-bound, so they are all declared before visiting the program
+    for stmt in stmts.stmts.iter() {
+        if let Stmt::Decl(lvalue, _) = stmt.node.as_ref() {
+            finder.visit_lvalue(lvalue, true);
+        }
+    }
+    finder.collecting_globals = false;
+
+    finder.visit_stmts(stmts);
+    finder.undefined
+}
+
+struct UndefinedFinder {
+    /// The names declared in each nested local scope, innermost last
+    scopes: Vec<HashSet<String>>,
+
+    globals: HashSet<String>,
+
+    /// Set while declaring the globals, when no references should be checked
+    collecting_globals: bool,
+
+    /// The range of the innermost statement or expression being visited
+    range: CodeRange,
+
+    undefined: Vec<UndefinedVariable>,
+}
+
+impl UndefinedFinder {
+    fn is_declared(&self, name: &str) -> bool {
+        self.globals.contains(name) || self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Finds the declared name most similar to the given one, if it is similar enough
+    fn suggest(&self, name: &str) -> Option<String> {
+        let max_distance = name.chars().count() / 3 + 1;
+        let mut candidates: Vec<&String> = self
+            .globals
+            .iter()
+            .chain(self.scopes.iter().flatten())
+            .filter(|candidate| !candidate.starts_with('@'))
+            .collect();
+        candidates.sort();
+
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.clone())
+    }
+}
+
+impl AstVisitor for UndefinedFinder {
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashSet::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn visit_var(&mut self, name: &String, declaration: bool) {
+        if declaration {
+            match self.scopes.last_mut() {
+                Some(scope) => scope.insert(name.clone()),
+                None => self.globals.insert(name.clone()),
+            };
+        } else if !self.collecting_globals && !self.is_declared(name) {
+            self.undefined.push(UndefinedVariable {
+                name: name.clone(),
+                range: self.range.clone(),
+                suggestion: self.suggest(name),
+            });
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        // Patterns have no ranges of their own, so names in them are reported at the statement
+        let outer = std::mem::replace(&mut self.range, stmt.range());
+        match stmt.node.as_ref() {
+            Stmt::Decl(lvalue, init) => self.visit_decl(lvalue, init.as_ref()),
+            Stmt::Expr(expr) => self.visit_expr(expr),
+            Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            Stmt::Invalid => (),
+        }
+        self.range = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        let outer = std::mem::replace(
+            &mut self.range,
+            CodeRange::from_locs(expr.start_loc, expr.end_loc),
+        );
+        self.visit_expr_delegation(expr);
+        self.range = outer;
+    }
+}
+
+impl Display for UndefinedVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Var '{}' is not declared", self.name)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ". Did you mean '{suggestion}'?")?;
+        }
+        Ok(())
+    }
+}
+
+/// The Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::edit_distance;
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("map", "map"), 0);
+        assert_eq!(edit_distance("mpa", "map"), 2);
+        assert_eq!(edit_distance("lenght", "length"), 2);
+        assert_eq!(edit_distance("prin", "print"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -3,29 +3,20 @@ use std::{
     mem,
 };
 
-use parser::{Expr, ExprNode, LValue, Stmts};
+use parser::{Expr, ExprNode, LValue, NodeId, Stmt, StmtNode, Stmts};
 
-use crate::{ref_id, visitor::AstVisitor, NodeAttr, RefId};
+use crate::{visitor::AstVisitor, NodeAttr};
 
-/// Finds which declarations are upvalues
+/// Finds which declarations are upvalues, attributed to the node binding them
 /// Also the upvalues (by name) are captured by each closure
-pub(crate) fn find_upvalues(stmts: &Stmts) -> HashMap<RefId, NodeAttr> {
-    let mut resolver = Resolver {
-        scope: VarScope {
-            vars: HashMap::new(),
-            parent: None,
-        },
-        enclosing_functions: vec![],
-        upvalues: HashSet::new(),
-        closure_upvalues: HashMap::new(),
-        expr_id: None,
-        global_scope: true,
-    };
-
-    resolver.visit_stmts(stmts);
+pub(crate) fn find_upvalues(stmts: &Stmts) -> Vec<(NodeId, NodeAttr)> {
+    let resolver = Resolver::resolve(stmts);
 
     // Finds which declarations are upvalues
-    let upvalue_attrs = resolver.upvalues.iter().map(|&id| (id, NodeAttr::UpValue));
+    let upvalue_attrs = resolver
+        .upvalues
+        .into_iter()
+        .map(|(binder, name)| (binder, NodeAttr::UpValue(name)));
 
     // Finds which upvalues are captured by different closures
     let upvalues_attrs = resolver
@@ -36,32 +27,108 @@ pub(crate) fn find_upvalues(stmts: &Stmts) -> HashMap<RefId, NodeAttr> {
     upvalue_attrs.chain(upvalues_attrs).collect()
 }
 
+/// A variable declared in the code, by the ids of the nodes
+#[derive(Debug)]
+pub(crate) struct VarDeclaration {
+    pub name: String,
+
+    /// The node declaring the variable, such as the declaration statement or function definition
+    pub declared_in: NodeId,
+
+    /// The node whose code the variable can be used in, or None for globals
+    pub scope: Option<NodeId>,
+}
+
+/// A use of a variable, by the id of the innermost node it is used in
+#[derive(Debug)]
+pub(crate) struct VarReference {
+    pub name: String,
+    pub node: NodeId,
+
+    /// The index of the declaration it refers to, or None if it is not declared in the code
+    pub declaration: Option<usize>,
+}
+
+/// Finds all declared variables, and which declaration each use of a variable refers to
+///
+/// Globals are late-bound, so they refer to the last declaration of the name before them,
+/// or the first one after them if there is none before.
+pub(crate) fn resolve_variables(stmts: &Stmts) -> (Vec<VarDeclaration>, Vec<VarReference>) {
+    let mut resolver = Resolver::resolve(stmts);
+
+    for (reference, declared_before) in mem::take(&mut resolver.global_references) {
+        let reference = &mut resolver.references[reference];
+        let globals = resolver
+            .global_declarations
+            .iter()
+            .filter(|&&declaration| resolver.declarations[declaration].name == reference.name);
+        reference.declaration = globals
+            .clone()
+            .take_while(|&&declaration| declaration < declared_before)
+            .last()
+            .or(globals.clone().next())
+            .copied();
+    }
+
+    (resolver.declarations, resolver.references)
+}
+
 struct Resolver {
     scope: VarScope,
 
     /// The id of all functions we are enclosed by at the moment
     /// This can be used to index into the closure_upvalues to update their attributes.
-    enclosing_functions: Vec<RefId>,
+    enclosing_functions: Vec<NodeId>,
 
-    /// Set of all Ids to Strings which are actually upvalues. A bit clunky as it really is the variable...
-    /// Only for the declaration String, not its uses
-    upvalues: HashSet<RefId>,
+    /// Set of all variables which are actually upvalues, by the node binding them and their name
+    /// Only for the declaration, not its uses
+    upvalues: HashSet<(NodeId, String)>,
 
     /// Maps each function def to how what upvalues it captures from the outer function
     /// Only contains their names. The offsets are calculated at compile time.
-    closure_upvalues: HashMap<RefId, Vec<String>>,
+    closure_upvalues: HashMap<NodeId, Vec<String>>,
 
-    /// The id of the most recently entered expression
-    expr_id: Option<RefId>,
+    /// The id of the most recently entered statement or expression
+    node_id: Option<NodeId>,
 
-    /// If we are at global scope, meaning variables are globals
-    global_scope: bool,
+    /// The node binding the variables currently being declared, and the node declaring them
+    binder: Option<(NodeId, NodeId)>,
+
+    /// All declared variables, in the order they are declared
+    declarations: Vec<VarDeclaration>,
+
+    /// The indices of the declarations which are globals
+    global_declarations: Vec<usize>,
+
+    references: Vec<VarReference>,
+
+    /// The indices of the references which are not to locals, with the number of declarations
+    /// before them. They are resolved when all globals are declared.
+    global_references: Vec<(usize, usize)>,
 }
 
 impl<'a> Resolver {
-    fn add_upvalue(&mut self, id: RefId, name: &String, func_level: usize) {
+    fn resolve(stmts: &Stmts) -> Self {
+        let mut resolver = Resolver {
+            scope: VarScope::empty(),
+            enclosing_functions: vec![],
+            upvalues: HashSet::new(),
+            closure_upvalues: HashMap::new(),
+            node_id: None,
+            binder: None,
+            declarations: vec![],
+            global_declarations: vec![],
+            references: vec![],
+            global_references: vec![],
+        };
+
+        resolver.visit_stmts(stmts);
+        resolver
+    }
+
+    fn add_upvalue(&mut self, binder: NodeId, name: &String, func_level: usize) {
         // It is an upvalue!
-        self.upvalues.insert(id);
+        self.upvalues.insert((binder, name.clone()));
 
         // Then we should also add it as an upvalue to all enclosing functions
         // where it was not declared.
@@ -78,25 +145,78 @@ impl<'a> Resolver {
             }
         }
     }
+
+    /// Declares the variables in the lvalue as bound by the given node
+    fn bind(&mut self, lvalue: &LValue, binder: NodeId, declared_in: NodeId) {
+        self.binder = Some((binder, declared_in));
+        self.visit_lvalue(lvalue, true);
+    }
+
+    /// Declares the variables in a pattern, which is not a node itself but part of the
+    /// innermost expression
+    fn bind_pattern(&mut self, lvalue: &LValue, binder: NodeId) {
+        let expr = self.node_id.expect("Pattern should be in expression");
+        self.bind(lvalue, binder, expr);
+    }
+
+    /// Visits code in a new local scope, covering the code of the given node
+    fn in_scope(&mut self, node: NodeId, visit: impl FnOnce(&mut Self)) {
+        let scope = mem::replace(&mut self.scope, VarScope::empty());
+        self.scope = scope.enter(node);
+
+        visit(self);
+
+        let scope = mem::replace(&mut self.scope, VarScope::empty());
+        self.scope = scope.exit().unwrap();
+    }
+
+    /// If we are at global scope, meaning variables are globals
+    fn global_scope(&self) -> bool {
+        self.scope.parent.is_none()
+    }
+
+    fn visit_declaration(&mut self, lvalue: &LValue, init: Option<&ExprNode>, binder: NodeId) {
+        if init.is_some_and(|expr_node| {
+            matches!(
+                expr_node.node.as_ref(),
+                Expr::FunctionDefinition(_, _, _, _)
+            )
+        }) {
+            // Switch order to declare funv before initializing
+            self.bind(lvalue, binder, binder);
+
+            if let Some(expr) = init {
+                self.visit_expr(expr)
+            }
+        } else {
+            if let Some(expr) = init {
+                self.visit_expr(expr)
+            }
+            self.bind(lvalue, binder, binder);
+        }
+    }
 }
 
 struct VarScope {
-    /// Keeps both the reference id, as well as the function nesting it was declared at
-    /// The id is of the reference to the
+    /// Keeps the node binding the variable, the function nesting it was declared at, and the
+    /// index of its declaration
     /// Will only hold local variables (no globals!)
     /// For lexical nesting
-    vars: HashMap<String, (RefId, usize)>,
+    vars: HashMap<String, (NodeId, usize, usize)>,
+
+    /// The node whose code the scope covers, or None for the global scope
+    node: Option<NodeId>,
 
-    /// Parent for nesting of functions
+    /// Parent for nesting of scopes
     parent: Option<Box<VarScope>>,
 }
 
 impl VarScope {
-    fn insert(&mut self, name: String, id: RefId, level: usize) {
-        self.vars.insert(name, (id, level));
+    fn insert(&mut self, name: String, binder: NodeId, level: usize, declaration: usize) {
+        self.vars.insert(name, (binder, level, declaration));
     }
 
-    fn resolve(&self, name: &str) -> Option<(RefId, usize)> {
+    fn resolve(&self, name: &str) -> Option<(NodeId, usize, usize)> {
         if let Some(ret) = self.vars.get(name) {
             Some(*ret)
         } else if let Some(parent) = self.parent.as_ref() {
@@ -106,9 +226,10 @@ impl VarScope {
         }
     }
 
-    fn enter(self) -> Self {
+    fn enter(self, node: NodeId) -> Self {
         Self {
             vars: HashMap::new(),
+            node: Some(node),
             parent: Some(Box::new(self)),
         }
     }
@@ -120,58 +241,83 @@ impl VarScope {
     fn empty() -> Self {
         Self {
             vars: HashMap::new(),
+            node: None,
             parent: None,
         }
     }
 }
 
 impl<'a> AstVisitor for Resolver {
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        let outer_id = self.node_id;
+        self.node_id = Some(stmt.id);
+
+        match stmt.node.as_ref() {
+            // Declarations are bound by their statement
+            Stmt::Decl(lvalue, init) => self.visit_declaration(lvalue, init.as_ref(), stmt.id),
+            Stmt::Expr(expr) => self.visit_expr(expr),
+            Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            Stmt::Invalid => (),
+        }
+        self.node_id = outer_id;
+    }
+
     fn visit_var(&mut self, name: &String, declaration: bool) {
         if declaration {
+            let (binder, declared_in) = self.binder.expect("Declaration should have a binder");
+            let index = self.declarations.len();
+            self.declarations.push(VarDeclaration {
+                name: name.clone(),
+                declared_in,
+                scope: self.scope.node,
+            });
+
             // Declare it as reachable
-            if !self.global_scope {
+            if self.global_scope() {
+                self.global_declarations.push(index);
+            } else {
                 self.scope.insert(
                     name.to_string(),
-                    ref_id(name),
+                    binder,
                     self.enclosing_functions.len(),
+                    index,
                 );
             }
-        } else if let Some((id, func_level)) = self.scope.resolve(name) {
-            // globals are not tagged as upvalues
-            if func_level != self.enclosing_functions.len() {
-                self.add_upvalue(id, name, func_level);
-            }
-        } else {
-            // Could be a global which is forward declared, so ignore potential errors here
+            return;
         }
-    }
 
-    fn visit_decl(&mut self, lvalue: &LValue, init: Option<&parser::ExprNode>) {
-        if init.is_some_and(|expr_node| {
-            matches!(expr_node.node.as_ref(), Expr::FunctionDefinition(_, _, _))
-        }) {
-            // Switch order to declare funv before initializing
-            self.visit_lvalue(lvalue, true);
+        let node = self.node_id.expect("Variable should be used in a node");
+        let reference = VarReference {
+            name: name.clone(),
+            node,
+            declaration: None,
+        };
 
-            if let Some(expr) = init {
-                self.visit_expr(expr)
+        if let Some((binder, func_level, declaration)) = self.scope.resolve(name) {
+            // globals are not tagged as upvalues
+            if func_level != self.enclosing_functions.len() {
+                self.add_upvalue(binder, name, func_level);
             }
+            self.references.push(VarReference {
+                declaration: Some(declaration),
+                ..reference
+            });
         } else {
-            if let Some(expr) = init {
-                self.visit_expr(expr)
-            }
-            self.visit_lvalue(lvalue, true);
+            // Could be a global which is forward declared, so it is resolved after visiting
+            self.global_references
+                .push((self.references.len(), self.declarations.len()));
+            self.references.push(reference);
         }
     }
 
     fn visit_expr(&mut self, expr: &parser::ExprNode) {
-        let outer_id = self.expr_id;
-        self.expr_id = Some(ref_id(expr.node.as_ref()));
+        let outer_id = self.node_id;
+        self.node_id = Some(expr.id);
 
         // Default
         self.visit_expr_delegation(expr);
 
-        self.expr_id = outer_id;
+        self.node_id = outer_id;
     }
 
     fn visit_function_definition(
@@ -180,72 +326,99 @@ impl<'a> AstVisitor for Resolver {
         params: &[LValue],
         body: &parser::ExprNode,
     ) {
-        let id = self.expr_id.expect("Func def should be in expression");
+        let id = self.node_id.expect("Func def should be in expression");
 
         // Adds which enclosing functions exist
         self.enclosing_functions.push(id);
 
-        // Ugly way to do it
-        // TODO: Good opportunity to play with unsafe?
-        let scope = mem::replace(&mut self.scope, VarScope::empty());
-        self.scope = scope.enter();
-
         // The parameters are created in a new scope
-        let global_scope = self.global_scope;
-        self.global_scope = false;
-
-        // Default visit
-        for param in params {
-            self.visit_lvalue(param, true);
-        }
-        self.visit_expr(body);
-
-        // Reset global scope status
-        self.global_scope = global_scope;
-
-        // Ugly way to do it
-        let scope = mem::replace(&mut self.scope, VarScope::empty());
-        self.scope = scope.exit().unwrap();
+        self.in_scope(id, |resolver| {
+            // Default visit
+            // The parameters are bound by the body, as they are only reachable there
+            for param in params {
+                resolver.bind(param, body.id, id);
+            }
+            resolver.visit_expr(body);
+        });
 
         self.enclosing_functions.pop().unwrap();
     }
 
     fn visit_block(&mut self, stmts: &Stmts) {
-        let scope = self.global_scope;
-        self.global_scope = false;
-
-        // Default
-        self.visit_stmts(stmts);
-
-        self.global_scope = scope;
+        let id = self.node_id.expect("Block should be in expression");
+        self.in_scope(id, |resolver| resolver.visit_stmts(stmts));
     }
 
-    fn visit_for(&mut self, lvalue: &LValue, collection: &ExprNode, body: &ExprNode) {
-        // Have to reset scope here as well
-        let scope = self.global_scope;
-        self.global_scope = false;
-
+    fn visit_for(
+        &mut self,
+        lvalue: &LValue,
+        collection: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
         // Default, but switched order
         self.visit_expr(collection);
-        self.visit_lvalue(lvalue, true);
-        self.visit_expr(body);
+        self.in_scope(body.id, |resolver| {
+            resolver.bind_pattern(lvalue, body.id);
+            resolver.visit_expr(body);
+        });
+
+        // The else branch is outside of the loop scope
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
 
-        self.global_scope = scope;
+    fn visit_if_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        then: &ExprNode,
+        otherwise: Option<&ExprNode>,
+    ) {
+        self.visit_expr(matched);
+
+        // The pattern is only bound in the then branch
+        self.in_scope(then.id, |resolver| {
+            resolver.bind_pattern(lvalue, then.id);
+            resolver.visit_expr(then);
+        });
+
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
+        self.in_scope(body.id, |resolver| {
+            resolver.visit_expr(matched);
+            resolver.bind_pattern(lvalue, body.id);
+            resolver.visit_expr(body);
+        });
+
+        // The else branch is outside of the loop scope
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
     }
 
     fn visit_match(&mut self, matched: &ExprNode, options: &[(LValue, ExprNode)]) {
         self.visit_expr(matched);
 
-        // Handle scope
-        let scope = self.global_scope;
-        self.global_scope = false;
-
-        // Default
+        // Each arm binds its pattern in its own scope
         for (lvalue, then) in options {
-            self.visit_lvalue(lvalue, true);
-            self.visit_expr(then);
+            self.in_scope(then.id, |resolver| {
+                resolver.bind_pattern(lvalue, then.id);
+                resolver.visit_expr(then);
+            });
         }
-
-        self.global_scope = scope;
     }
 }
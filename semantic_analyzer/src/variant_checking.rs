@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use parser::{CodeRange, Expr, ExprNode, LValue, StmtNode, Stmts};
+
+use crate::visitor::AstVisitor;
+
+/// A declaration which makes an enum variant ambiguous
+#[derive(Debug, PartialEq)]
+pub struct VariantError {
+    pub range: CodeRange,
+    pub message: String,
+}
+
+/// Finds enum variants declared more than once, and variables named as a variant
+///
+/// Patterns only match a variant without fields by name if the parser knows about the variant,
+/// and otherwise bind a new variable, which would then match anything. As enums can be
+/// declared anywhere, declaring a variable with the name of any variant is an error.
+pub fn find_variant_errors(stmts: &Stmts) -> Vec<VariantError> {
+    let mut checker = VariantChecker {
+        variants: HashMap::new(),
+        collecting_variants: true,
+        range: CodeRange::from_ints(0, 0, 0, 0, 0, 0),
+        errors: vec![],
+    };
+
+    // Enums can be declared after the patterns using their variants
+    checker.visit_stmts(stmts);
+    checker.collecting_variants = false;
+    checker.visit_stmts(stmts);
+    checker.errors
+}
+
+struct VariantChecker {
+    /// The enum of each declared variant
+    variants: HashMap<String, String>,
+
+    /// Set while collecting the variants, when no declarations should be checked
+    collecting_variants: bool,
+
+    /// The range of the innermost statement or expression being visited
+    range: CodeRange,
+
+    errors: Vec<VariantError>,
+}
+
+impl VariantChecker {
+    fn error(&mut self, message: String) {
+        self.errors.push(VariantError {
+            range: self.range.clone(),
+            message,
+        });
+    }
+}
+
+impl AstVisitor for VariantChecker {
+    fn visit_stmt(&mut self, stmt: &StmtNode) {
+        let outer = std::mem::replace(
+            &mut self.range,
+            CodeRange::from_locs(stmt.start_loc, stmt.end_loc),
+        );
+        match stmt.node.as_ref() {
+            // The constructor of a variant is declared as a variable with its name
+            parser::Stmt::Decl(LValue::Var(_), Some(init))
+                if matches!(init.node.as_ref(), Expr::EnumVariant(_, _, _)) =>
+            {
+                self.visit_expr(init)
+            }
+            parser::Stmt::Decl(lvalue, init) => self.visit_decl(lvalue, init.as_ref()),
+            parser::Stmt::Expr(expr) => self.visit_expr(expr),
+            parser::Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            parser::Stmt::Invalid => (),
+        }
+        self.range = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &ExprNode) {
+        let outer = std::mem::replace(
+            &mut self.range,
+            CodeRange::from_locs(expr.start_loc, expr.end_loc),
+        );
+        self.visit_expr_delegation(expr);
+        self.range = outer;
+    }
+
+    fn visit_enum_variant(&mut self, enum_name: &str, name: &str, _fields: &[String]) {
+        if !self.collecting_variants {
+            return;
+        }
+        if let Some(declared_in) = self.variants.get(name) {
+            let message = format!("Variant '{name}' is already declared in enum {declared_in}");
+            self.error(message);
+        } else {
+            self.variants
+                .insert(name.to_string(), enum_name.to_string());
+        }
+    }
+
+    fn visit_var(&mut self, name: &String, declaration: bool) {
+        if !declaration || self.collecting_variants {
+            return;
+        }
+        if let Some(enum_name) = self.variants.get(name) {
+            let message = format!("Var '{name}' shadows the variant of enum {enum_name}");
+            self.error(message);
+        }
+    }
+}
+
+impl Display for VariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_variant_errors;
+
+    fn messages(code: &str) -> Vec<String> {
+        let stmts = parser::parse("test", code).unwrap();
+        find_variant_errors(&stmts)
+            .into_iter()
+            .map(|error| format!("[{}] {}", error.range, error.message))
+            .collect()
+    }
+
+    #[test]
+    fn variants() {
+        let code = "enum Option { Some(x), Empty }\nmatch Some(1) { Some(x) -> x, Empty -> 0, };";
+        assert!(messages(code).is_empty());
+
+        let code = "enum A { X, Y(y) }\nenum B { Y }";
+        assert_eq!(
+            messages(code),
+            vec!["[2:10 - 2:11] Variant 'Y' is already declared in enum A"]
+        );
+
+        // Variants with fields are not matched by their bare name
+        let code = "f := \\o -> match o { Some -> 1, _ -> 2, };\nenum Option { Some(x) }";
+        assert_eq!(
+            messages(code),
+            vec!["[1:12 - 1:42] Var 'Some' shadows the variant of enum Option"]
+        );
+    }
+}
@@ -5,7 +5,26 @@ use parser::{
     UnOper,
 };
 
+/// Visits the AST in the order it is evaluated
+///
+/// The default methods also follow the scoping of the VM, calling `enter_scope` and
+/// `exit_scope` around blocks, function definitions and the code where a pattern binds its
+/// variables. So visitors keeping track of the declared variables only implement those and
+/// `visit_var`.
 pub trait AstVisitor {
+    /// Called before visiting the code in a new local scope
+    fn enter_scope(&mut self) {}
+
+    /// Called after visiting the code in the innermost local scope
+    fn exit_scope(&mut self) {}
+
+    /// Visits something in a new local scope
+    fn scoped(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.enter_scope();
+        visit(self);
+        self.exit_scope();
+    }
+
     fn visit_stmts(&mut self, stmts: &Stmts) {
         for stmt in stmts.stmts.iter() {
             self.visit_stmt(stmt)
@@ -16,14 +35,35 @@ pub trait AstVisitor {
         match stmt.node.as_ref() {
             parser::Stmt::Decl(lvalue, init) => self.visit_decl(lvalue, init.as_ref()),
             parser::Stmt::Expr(expr) => self.visit_expr(expr),
-            parser::Stmt::Invalid => println!("WARNING: Visiting invalid AST node"),
+            parser::Stmt::Assert(cond, message, _) => self.visit_assert(cond, message.as_ref()),
+            parser::Stmt::Invalid => (),
+        }
+    }
+
+    fn visit_assert(&mut self, cond: &ExprNode, message: Option<&ExprNode>) {
+        self.visit_expr(cond);
+        if let Some(expr) = message {
+            self.visit_expr(expr)
         }
     }
 
     fn visit_decl(&mut self, lvalue: &LValue, init: Option<&ExprNode>) {
-        self.visit_lvalue(lvalue, true);
+        let is_function = init.is_some_and(|expr_node| {
+            matches!(
+                expr_node.node.as_ref(),
+                Expr::FunctionDefinition(_, _, _, _)
+            )
+        });
+
+        // Functions can call themselves recursively, so declare them before the body
+        if is_function {
+            self.visit_lvalue(lvalue, true);
+        }
         if let Some(expr) = init {
-            self.visit_expr(expr)
+            self.visit_expr(expr);
+        }
+        if !is_function {
+            self.visit_lvalue(lvalue, true);
         }
     }
 
@@ -31,10 +71,13 @@ pub trait AstVisitor {
         match expr.node.as_ref() {
             Expr::Call(callee, args) => self.visit_call(callee, args),
             Expr::IndexInto(indexee, at) => self.visit_index_into(indexee, at),
+            Expr::SafeIndexInto(indexee, at) => self.visit_safe_index_into(indexee, at),
             Expr::Binary(x, op, y) => self.visit_binary(x, op, y),
+            Expr::ChainedComparison(first, links) => self.visit_chained_comparison(first, links),
             Expr::Unary(op, x) => self.visit_unary(op, x),
             Expr::Logical(x, op, y) => self.visit_logical(x, op, y),
             Expr::Assign(lvalue, value) => self.visit_assign(lvalue, value),
+            Expr::OpAssign(lvalue, op, value) => self.visit_op_assign(lvalue, op, value),
             Expr::Var(name) => self.visit_var(name, false),
             Expr::Int(int) => self.visit_int(*int),
             Expr::Float(float) => self.visit_float(*float),
@@ -42,18 +85,36 @@ pub trait AstVisitor {
             Expr::String(string) => self.visit_string(string),
             Expr::Block(stmts) => self.visit_block(stmts),
             Expr::If(cond, then, otherwise) => self.visit_if(cond, then, otherwise.as_ref()),
-            Expr::While(cond, body) => self.visit_while(cond, body),
-            Expr::For(lvalue, collection, body) => self.visit_for(lvalue, collection, body),
-            Expr::Break => self.visit_break(),
-            Expr::Continue => self.visit_continue(),
+            Expr::IfLet(lvalue, matched, then, otherwise) => {
+                self.visit_if_let(lvalue, matched, then, otherwise.as_ref())
+            }
+            Expr::While(cond, body, otherwise, label) => {
+                self.visit_while(cond, body, otherwise.as_ref(), label.as_ref())
+            }
+            Expr::WhileLet(lvalue, matched, body, otherwise, label) => {
+                self.visit_while_let(lvalue, matched, body, otherwise.as_ref(), label.as_ref())
+            }
+            Expr::For(lvalue, collection, body, otherwise, label) => {
+                self.visit_for(lvalue, collection, body, otherwise.as_ref(), label.as_ref())
+            }
+            Expr::Break(label, value) => self.visit_break(label.as_ref(), value.as_ref()),
+            Expr::Continue(label) => self.visit_continue(label.as_ref()),
             Expr::Return(ret) => self.visit_return(ret.as_ref()),
             Expr::Nil => self.visit_nil(),
             Expr::List(content) => self.visit_list(content),
+            Expr::Dict(pairs) => self.visit_dict(pairs),
+            Expr::Set(exprs) => self.visit_set(exprs),
             Expr::Tuple(exprs) => self.visit_tuple(exprs),
-            Expr::FunctionDefinition(name, params, body) => {
+            Expr::FunctionDefinition(name, params, body, _) => {
                 self.visit_function_definition(name, params, body)
             }
             Expr::Match(matched, options) => self.visit_match(matched, options),
+            Expr::Rest(name) => self.visit_rest(name.as_ref()),
+            Expr::EnumVariant(enum_name, name, fields) => {
+                self.visit_enum_variant(enum_name, name, fields)
+            }
+            // Error nodes from partially parsed code have nothing to visit
+            Expr::Invalid => (),
         }
     }
 
@@ -74,12 +135,24 @@ pub trait AstVisitor {
         self.visit_index(at);
     }
 
+    fn visit_safe_index_into(&mut self, indexee: &ExprNode, at: &Index) {
+        self.visit_index_into(indexee, at);
+    }
+
     fn visit_binary(&mut self, x: &ExprNode, op: &BinOper, y: &ExprNode) {
         self.visit_expr(x);
         self.visit_binary_oper(op);
         self.visit_expr(y);
     }
 
+    fn visit_chained_comparison(&mut self, first: &ExprNode, links: &[(BinOper, ExprNode)]) {
+        self.visit_expr(first);
+        for (op, operand) in links {
+            self.visit_binary_oper(op);
+            self.visit_expr(operand);
+        }
+    }
+
     fn visit_unary(&mut self, op: &UnOper, x: &ExprNode) {
         self.visit_unary_oper(op);
         self.visit_expr(x);
@@ -96,6 +169,12 @@ pub trait AstVisitor {
         self.visit_expr(value);
     }
 
+    fn visit_op_assign(&mut self, lvalue: &LValue, op: &BinOper, value: &ExprNode) {
+        self.visit_lvalue(lvalue, false);
+        self.visit_binary_oper(op);
+        self.visit_expr(value);
+    }
+
     fn visit_var(&mut self, _name: &String, _declaration: bool) {} // String instead of str to be more sure of pointer magic
     fn visit_int(&mut self, _int: i64) {}
     fn visit_float(&mut self, _float: f64) {}
@@ -103,7 +182,7 @@ pub trait AstVisitor {
     fn visit_string(&mut self, _string: &Rc<String>) {}
 
     fn visit_block(&mut self, stmts: &Stmts) {
-        self.visit_stmts(stmts)
+        self.scoped(|visitor| visitor.visit_stmts(stmts));
     }
 
     fn visit_if(&mut self, cond: &ExprNode, then: &ExprNode, otherwise: Option<&ExprNode>) {
@@ -115,19 +194,80 @@ pub trait AstVisitor {
         }
     }
 
-    fn visit_while(&mut self, cond: &ExprNode, body: &ExprNode) {
+    fn visit_if_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        then: &ExprNode,
+        otherwise: Option<&ExprNode>,
+    ) {
+        self.visit_expr(matched);
+        self.scoped(|visitor| {
+            visitor.visit_lvalue(lvalue, true);
+            visitor.visit_expr(then);
+        });
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
+        // The matched value is evaluated again in each iteration
+        self.scoped(|visitor| {
+            visitor.visit_expr(matched);
+            visitor.visit_lvalue(lvalue, true);
+            visitor.visit_expr(body);
+        });
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_while(
+        &mut self,
+        cond: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
         self.visit_expr(cond);
         self.visit_expr(body);
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
     }
 
-    fn visit_for(&mut self, lvalue: &LValue, collection: &ExprNode, body: &ExprNode) {
-        self.visit_lvalue(lvalue, true);
+    fn visit_for(
+        &mut self,
+        lvalue: &LValue,
+        collection: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        _label: Option<&String>,
+    ) {
         self.visit_expr(collection);
-        self.visit_expr(body);
+        self.scoped(|visitor| {
+            visitor.visit_lvalue(lvalue, true);
+            visitor.visit_expr(body);
+        });
+        if let Some(expr) = otherwise {
+            self.visit_expr(expr);
+        }
     }
 
-    fn visit_break(&mut self) {}
-    fn visit_continue(&mut self) {}
+    fn visit_break(&mut self, _label: Option<&String>, value: Option<&ExprNode>) {
+        if let Some(expr) = value {
+            self.visit_expr(expr);
+        }
+    }
+    fn visit_continue(&mut self, _label: Option<&String>) {}
 
     fn visit_return(&mut self, ret: Option<&ExprNode>) {
         if let Some(expr) = ret {
@@ -148,6 +288,19 @@ pub trait AstVisitor {
         }
     }
 
+    fn visit_dict(&mut self, pairs: &[(ExprNode, ExprNode)]) {
+        for (key, value) in pairs {
+            self.visit_expr(key);
+            self.visit_expr(value);
+        }
+    }
+
+    fn visit_set(&mut self, exprs: &[ExprNode]) {
+        for expr in exprs {
+            self.visit_expr(expr)
+        }
+    }
+
     fn visit_tuple(&mut self, exprs: &[ExprNode]) {
         for expr in exprs {
             self.visit_expr(expr)
@@ -155,20 +308,28 @@ pub trait AstVisitor {
     }
 
     fn visit_function_definition(&mut self, _name: &str, params: &[LValue], body: &ExprNode) {
-        for param in params {
-            self.visit_lvalue(param, true);
-        }
-        self.visit_expr(body);
+        self.scoped(|visitor| {
+            for param in params {
+                visitor.visit_lvalue(param, true);
+            }
+            visitor.visit_expr(body);
+        });
     }
 
     fn visit_match(&mut self, matched: &ExprNode, options: &[(LValue, ExprNode)]) {
         self.visit_expr(matched);
         for (lvalue, then) in options {
-            self.visit_lvalue(lvalue, true);
-            self.visit_expr(then);
+            self.scoped(|visitor| {
+                visitor.visit_lvalue(lvalue, true);
+                visitor.visit_expr(then);
+            });
         }
     }
 
+    fn visit_rest(&mut self, _name: Option<&String>) {}
+
+    fn visit_enum_variant(&mut self, _enum_name: &str, _name: &str, _fields: &[String]) {}
+
     fn visit_index(&mut self, at: &Index) {
         match at {
             Index::At(expr) => self.visit_expr(expr),
@@ -181,6 +342,10 @@ pub trait AstVisitor {
     fn visit_logical_oper(&mut self, _op: &LogicalOper) {}
 
     fn visit_lvalue(&mut self, lvalue: &LValue, declaration: bool) {
+        self.visit_lvalue_delegation(lvalue, declaration)
+    }
+
+    fn visit_lvalue_delegation(&mut self, lvalue: &LValue, declaration: bool) {
         match lvalue {
             LValue::Index(indexee, at) => self.visit_index_into(indexee, at),
             LValue::Var(name) => self.visit_var(name, declaration),
@@ -190,6 +355,16 @@ pub trait AstVisitor {
                 }
             }
             LValue::Constant(expr) => self.visit_expr(expr),
+            LValue::Wildcard => (),
+            LValue::Rest(lvalue) => self.visit_lvalue(lvalue, declaration),
+            LValue::Typed(lvalue, _) => self.visit_lvalue(lvalue, declaration),
+            LValue::Variant(name, fields) => {
+                // The constructor is only read, to check the tag
+                self.visit_var(name, false);
+                for field in fields {
+                    self.visit_lvalue(field, declaration)
+                }
+            }
         }
     }
 
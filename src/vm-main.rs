@@ -5,7 +5,7 @@ use std::{
     path::{Path, PathBuf},
     process::exit,
 };
-use vm::value::get_natives;
+use vm::{compiler::CompileOptions, value::get_natives};
 
 #[derive(Parser)]
 struct Args {
@@ -16,32 +16,50 @@ struct Args {
     #[clap(short, long, requires = "file", conflicts_with = "doc_functions")]
     format: bool,
 
+    /// Warn about suspicious code in the file instead of running it
+    #[clap(long, requires = "file", conflicts_with_all = ["format", "doc_functions"])]
+    lint: bool,
+
     /// Output all the globally declared functions in the file
     #[clap(short, long, conflicts_with = "format")]
     doc_functions: bool,
+
+    /// Compile away all assert! statements
+    #[clap(long)]
+    no_asserts: bool,
+
+    /// The optimization level, where -O0 disables constant folding to compile the code as written
+    #[clap(short = 'O', default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=1))]
+    opt_level: u8,
 }
 
 fn main() {
     let args = Args::parse();
+    let options = CompileOptions {
+        asserts: !args.no_asserts,
+        optimize: args.opt_level > 0,
+    };
 
     if args.doc_functions {
         document_functions(args.file);
     } else if let Some(ref file) = args.file {
         if args.format {
             format_file(file);
+        } else if args.lint {
+            exit(lint_file(file));
         } else {
-            exit(run_file(file));
+            exit(run_file(file, options));
         }
     } else {
-        run_repl();
+        run_repl(options);
     }
 }
 
 /// Interprets a text file as a Zote script, returning the exit code.
-fn run_file(file: &str) -> i32 {
+fn run_file(file: &str, options: CompileOptions) -> i32 {
     let script = fs::read_to_string(file).expect("Could not open file.");
     let saved = change_dir(file);
-    let res = run_str(file, &script);
+    let res = run_str(file, &script, options);
     restore_dir(saved);
     res
 }
@@ -57,6 +75,28 @@ fn format_file(file: &str) {
     }
 }
 
+/// Prints the lint warnings for a file, returning the exit code
+fn lint_file(file: &str) -> i32 {
+    let script = fs::read_to_string(file).expect("Could not open file.");
+    change_dir(file);
+    let Some(stmts) = parser::parse(file, &script) else {
+        return 65;
+    };
+
+    let natives = get_natives();
+    let names: Vec<&str> = natives.iter().map(|native| native.name()).collect();
+    let warnings = semantic_analyzer::lint(&stmts, &script, &names);
+    for warning in warnings.iter() {
+        println!("{warning}");
+    }
+
+    if warnings.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
 fn document_functions(file_path: Option<String>) {
     // First the builtin functions
     let mut docs = String::new();
@@ -93,10 +133,10 @@ fn restore_dir(saved: Option<PathBuf>) {
 }
 
 /// Interprets the string as if from a file.
-fn run_str(name: &str, code: &str) -> i32 {
+fn run_str(name: &str, code: &str, options: CompileOptions) -> i32 {
     if let Some(stmts) = parser::parse(name, &code) {
         let ast = semantic_analyzer::analyze_ast(&stmts);
-        vm::interpret_once(&ast)
+        vm::interpret_once_with_options(&ast, options)
     } else {
         65
     }
@@ -106,7 +146,7 @@ fn run_str(name: &str, code: &str) -> i32 {
 ///
 /// Each line must be a statement or an expression (in which case its value is printed),
 /// and the state of the program is preserved between lines.
-fn run_repl() {
+fn run_repl(options: CompileOptions) {
     let reader = stdin();
     let mut line = String::new();
     // TODO: Save state between repl calls
@@ -118,6 +158,6 @@ fn run_repl() {
         reader.read_line(&mut line).unwrap_or(0) > 0
     } {
         // Does not preserve program state between calls
-        run_str("REPL", &line);
+        run_str("REPL", &line, options.clone());
     }
 }
@@ -15,6 +15,20 @@ fn interpret(program: &str) -> String {
     String::from_utf8_lossy(&output.stdout).to_string()
 }
 
+fn interpret_error(program: &str) -> String {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("ast-zote")
+        .arg("--")
+        .arg(program)
+        .output()
+        .expect("Could not run file!");
+
+    assert!(!output.status.success(), "Could run program!");
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
 #[test]
 fn fibonachi() {
     let output = interpret("tests/programs/fib.zote");
@@ -48,6 +62,116 @@ fn sort() {
     assert_eq!(output, "false\ntrue\ntrue\nfalse\ntrue\ntrue\ntrue\n")
 }
 
+#[test]
+fn compound_assign() {
+    let output = interpret("tests/programs/compound_assign.zote");
+    assert_eq!(output, "[15, 40, 30]\n2\n9\n9\n2\nhejsan\n[1, 2, 3, 4]\n3\n")
+}
+
+#[test]
+fn nil_safety() {
+    let output = interpret("tests/programs/nil_safety.zote");
+    assert_eq!(output, "3\nfalse\nlast\n1\n0\nNil\n[2, 3]\n3\nNil\nNil\nc\nNil\nNil\nNil\n5\n")
+}
+
+#[test]
+fn loop_values() {
+    let output = interpret("tests/programs/loop_values.zote");
+    assert_eq!(output, "15\nnone\n4\nnever looped\nNil\n[8, 23]\n3\nNil\nif else\n")
+}
+
+#[test]
+fn loop_labels() {
+    let output = interpret("tests/programs/loop_labels.zote");
+    assert_eq!(output, "[1, 1]\n8\n4\n8\nx\nouter\ndone\nhi there\nnote: for you\n")
+}
+
+#[test]
+fn rest_patterns() {
+    let output = interpret("tests/programs/rest_patterns.zote");
+    assert_eq!(
+        output,
+        "1\n[2, 3, 4]\n13\n4\n[]\n312\n12\nempty\none\nstarts with 1 then 2\nends with 0\nother\n9\nbc\n"
+    )
+}
+
+#[test]
+fn let_patterns() {
+    let output = interpret("tests/programs/let_patterns.zote");
+    assert_eq!(output, "12\nno pair\n7\n0\nNil\nc\nb\na\n6\n2\nempty\n")
+}
+
+#[test]
+fn method_calls() {
+    let output = interpret("tests/programs/method_calls.zote");
+    assert_eq!(output, "12\n3\ntrue\n3\n10\n")
+}
+
+#[test]
+fn placeholders() {
+    let output = interpret("tests/programs/placeholders.zote");
+    assert_eq!(output, "-7\n7\n15\n6\n5\n5\n1\n8\n3\n")
+}
+
+#[test]
+fn composition() {
+    let output = interpret("tests/programs/composition.zote");
+    assert_eq!(output, "6\n42\n15\n[11, 12, 13]\n[1, 1]\n[4, 3]\n[2, 12]\n6\n6\n")
+}
+
+#[test]
+fn chained_comparisons() {
+    let output = interpret("tests/programs/chained_comparisons.zote");
+    assert_eq!(output, "false\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\nfalse\n[3, 1]\ntrue\n[1, 2, 2]\ntrue\nfalse\n")
+}
+
+#[test]
+fn membership() {
+    let output = interpret("tests/programs/membership.zote");
+    assert_eq!(output, "true\nfalse\ntrue\ntrue\ntrue\ntrue\nfalse\ntrue\nfalse\nfalse\nfalse\nfalse\n1\n2\ntrue\ntrue\nfalse\n")
+}
+
+#[test]
+fn enums() {
+    let output = interpret("tests/programs/enums.zote");
+    assert_eq!(output, "12\n12\n0\n[Circle(2), Rect(3, 4), Empty]\nthin\nflat\nrect\nother\ntrue\nfalse\n42\nCircle(5)\n10\n2\n3\n0\nNone\n")
+}
+
+#[test]
+fn variant_errors() {
+    let output = interpret_error("tests/programs/variant_errors.zote");
+    assert!(output.contains("Variant 'Empty' is already declared in enum Shape"));
+    assert!(output.contains("Var 'Some' shadows the variant of enum Option"));
+}
+
+#[test]
+fn bitwise() {
+    let output = interpret("tests/programs/bitwise.zote");
+    assert_eq!(output, "8\n14\n6\n16\n-4\n-6\ntrue\n1\n8\n")
+}
+
+#[test]
+fn type_annotations() {
+    let output = interpret("tests/programs/type_annotations.zote");
+    assert_eq!(output, "6\n7\n0\nI am zote\n25\n3\n1\n")
+}
+
+#[test]
+fn type_errors() {
+    let output = interpret_error("tests/programs/type_errors.zote");
+    assert!(output.contains("[4:11 - 4:18] Expected 'y' to be int, but found str"));
+    assert!(output.contains("[12:9 - 12:15] Expected 'count' to be int, but found str"));
+
+    // The program is not run when the types are known to be wrong
+    assert_eq!(output.matches("Expected").count(), 6);
+}
+
+#[test]
+fn return_type_error() {
+    let output = interpret_error("tests/programs/return_type_error.zote");
+    assert!(output.contains("Expected a value of type int, but got String"));
+}
+
 #[test]
 fn ast_aoc_2022_1() {
     let output = interpret("aoc-2022/ast-solutions/day01.zote");
@@ -222,3 +346,13 @@ fn ast_aoc_2022_25() {
     let output = interpret("aoc-2022/ast-solutions/day25.zote");
     assert_eq!(output, "2--1=0=-210-1=00=-=1\n");
 }
+
+#[test]
+fn undefined_variables() {
+    let output = interpret_error("tests/programs/undefined_variables.zote");
+    assert!(output.contains("[6:5 - 6:9] Var 'summ' is not declared. Did you mean 'sum'?"));
+    assert!(output.contains("[13:7 - 13:13] Var 'lenght' is not declared. Did you mean 'len'?"));
+    assert!(output.contains("[17:7 - 17:12] Var 'inner' is not declared"));
+    assert!(output.contains("[20:1 - 20:12] Var 'Q' is not declared"));
+    assert!(!output.contains("latest"));
+}
@@ -24,6 +24,18 @@ fn interpret_error(program: &str) -> String {
     String::from_utf8_lossy(&output.stderr).to_string()
 }
 
+fn lint(program: &str) -> String {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--")
+        .arg("--lint")
+        .arg(program)
+        .output()
+        .expect("Could not run file!");
+
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
 #[test]
 fn vm_fib_simple() {
     let output = interpret("tests/programs/fib_simple.zote");
@@ -116,9 +128,20 @@ fn vm_error_trace() {
     assert!(output.contains("line 4"));
     assert!(output.contains("in f1"));
     assert!(output.contains("in f2"));
+    assert!(output.contains("tail calls"));
     assert!(output.contains("line 10"));
 }
 
+#[test]
+fn vm_undefined_variables() {
+    let output = interpret_error("tests/programs/undefined_variables.zote");
+    assert!(output.contains("[6:5 - 6:9] Var 'summ' is not declared. Did you mean 'sum'?"));
+    assert!(output.contains("[13:7 - 13:13] Var 'lenght' is not declared. Did you mean 'len'?"));
+    assert!(output.contains("[17:7 - 17:12] Var 'inner' is not declared"));
+    assert!(output.contains("[20:1 - 20:12] Var 'Q' is not declared"));
+    assert!(!output.contains("latest"));
+}
+
 #[test]
 fn vm_assign_constant_ok() {
     let output = interpret("tests/programs/assign_constant_ok.zote");
@@ -161,6 +184,19 @@ fn vm_include_print() {
     assert_eq!(output, "party!\nsuccess\n")
 }
 
+#[test]
+fn vm_include_enum() {
+    let output = interpret("tests/programs/include_enum.zote");
+    assert_eq!(output, "1\nempty\n")
+}
+
+#[test]
+fn vm_variant_errors() {
+    let output = interpret_error("tests/programs/variant_errors.zote");
+    assert!(output.contains("Variant 'Empty' is already declared in enum Shape"));
+    assert!(output.contains("Var 'Some' shadows the variant of enum Option"));
+}
+
 #[test]
 fn vm_dict_creation() {
     let output = interpret("tests/programs/dict_creation.zote");
@@ -173,6 +209,15 @@ fn vm_dict_read_write() {
     assert_eq!(output, "10\n20\n11\n22\n")
 }
 
+#[test]
+fn vm_dict_set_literals() {
+    let output = interpret("tests/programs/dict_set_literals.zote");
+    assert_eq!(
+        output,
+        "123\n3\ndict{}\ndict{1: dict{2: 3}}\n3\n3\ntruefalse\ndict{}\n"
+    )
+}
+
 #[test]
 fn vm_string_slice_assign() {
     let output = interpret("tests/programs/string_slice_assign.zote");
@@ -253,3 +298,178 @@ fn vm_trim() {
     let output = interpret("tests/programs/trim.zote");
     assert_eq!(output, "hej! da\nhej! da\nhej! da\n\n");
 }
+
+#[test]
+fn vm_bitwise() {
+    let output = interpret("tests/programs/bitwise.zote");
+    assert_eq!(output, "8\n14\n6\n16\n-4\n-6\ntrue\n1\n8\n");
+}
+
+#[test]
+fn vm_compound_assign() {
+    let output = interpret("tests/programs/compound_assign.zote");
+    assert_eq!(output, "[15, 40, 30]\n2\n9\n9\n2\nhejsan\n[1, 2, 3, 4]\n3\n");
+}
+
+#[test]
+fn vm_nil_safety() {
+    let output = interpret("tests/programs/nil_safety.zote");
+    assert_eq!(output, "3\nfalse\nlast\n1\n0\nNil\n[2, 3]\n3\nNil\nNil\nc\nNil\nNil\nNil\n5\n");
+}
+
+#[test]
+fn vm_assertions() {
+    let output = interpret_error("tests/programs/assertions.zote");
+    assert!(output.contains("Assertion failed: xs[1] * 10 == 21 + 1"));
+    assert!(output.contains("message: the middle is off"));
+    assert!(output.contains("left: 20"));
+    assert!(output.contains("right: 22"));
+    assert!(output.contains("line 10"));
+}
+
+#[test]
+fn vm_no_asserts() {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--")
+        .arg("--no-asserts")
+        .arg("tests/programs/assertions.zote")
+        .output()
+        .expect("Could not run file!");
+
+    assert!(output.status.success(), "Could not run program!");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\nunreachable\n");
+}
+
+#[test]
+fn vm_tail_calls() {
+    let output = interpret("tests/programs/tail_calls.zote");
+    assert_eq!(output, "100000\nfalse\n799980000\n16\n3\n0\n");
+}
+
+#[test]
+fn vm_loop_values() {
+    let output = interpret("tests/programs/loop_values.zote");
+    assert_eq!(output, "15\nnone\n4\nnever looped\nNil\n[8, 23]\n3\nNil\nif else\n");
+}
+
+#[test]
+fn vm_loop_labels() {
+    let output = interpret("tests/programs/loop_labels.zote");
+    assert_eq!(output, "[1, 1]\n8\n4\n8\nx\nouter\ndone\nhi there\nnote: for you\n");
+}
+
+#[test]
+fn vm_rest_patterns() {
+    let output = interpret("tests/programs/rest_patterns.zote");
+    assert_eq!(
+        output,
+        "1\n[2, 3, 4]\n13\n4\n[]\n312\n12\nempty\none\nstarts with 1 then 2\nends with 0\nother\n9\nbc\n"
+    );
+}
+
+#[test]
+fn vm_let_patterns() {
+    let output = interpret("tests/programs/let_patterns.zote");
+    assert_eq!(output, "12\nno pair\n7\n0\nNil\nc\nb\na\n6\n2\nempty\n");
+}
+
+#[test]
+fn vm_method_calls() {
+    let output = interpret("tests/programs/method_calls.zote");
+    assert_eq!(output, "12\n3\ntrue\n3\n10\n");
+}
+
+#[test]
+fn vm_placeholders() {
+    let output = interpret("tests/programs/placeholders.zote");
+    assert_eq!(output, "-7\n7\n15\n6\n5\n5\n1\n8\n3\n");
+}
+
+#[test]
+fn vm_composition() {
+    let output = interpret("tests/programs/composition.zote");
+    assert_eq!(output, "6\n42\n15\n[11, 12, 13]\n[1, 1]\n[4, 3]\n[2, 12]\n6\n6\n");
+}
+
+#[test]
+fn vm_chained_comparisons() {
+    let output = interpret("tests/programs/chained_comparisons.zote");
+    assert_eq!(output, "false\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\nfalse\n[3, 1]\ntrue\n[1, 2, 2]\ntrue\nfalse\n");
+}
+
+#[test]
+fn vm_membership() {
+    let output = interpret("tests/programs/membership.zote");
+    assert_eq!(output, "true\nfalse\ntrue\ntrue\ntrue\ntrue\nfalse\ntrue\nfalse\nfalse\nfalse\nfalse\n1\n2\ntrue\ntrue\nfalse\n");
+}
+
+#[test]
+fn vm_enums() {
+    let output = interpret("tests/programs/enums.zote");
+    assert_eq!(output, "12\n12\n0\n[Circle(2), Rect(3, 4), Empty]\nthin\nflat\nrect\nother\ntrue\nfalse\n42\nCircle(5)\n10\n2\n3\n0\nNone\n");
+}
+
+#[test]
+fn vm_lints() {
+    let output = lint("tests/programs/lints.zote");
+    assert_eq!(
+        output,
+        "WARNING W001: [2:5 - 2:15] Variable 'scale' is never read\n\
+         WARNING W003: [4:5 - 4:19] Unreachable statement\n\
+         WARNING W003: [13:9 - 13:18] Unreachable statement\n\
+         WARNING W002: [18:5 - 18:13] The declaration of 'len' shadows the native function\n\
+         WARNING W004: [19:14 - 19:37] The value of an if without an else is nil when the condition is false\n\
+         WARNING W001: [33:1 - 33:19] Variable 'unused_global' is never read\n\
+         WARNING W001: [36:14 - 36:20] Variable 'factor' is never read\n"
+    );
+
+    // Linting does not run the program
+    assert!(!output.contains("positive"));
+}
+
+#[test]
+fn vm_arity_errors() {
+    let output = interpret_error("tests/programs/arity_errors.zote");
+    assert!(output.contains("[5:7 - 5:19] Called 'add' with 3 args, but it expects 2"));
+    assert!(output.contains("[7:7 - 7:21] Called 'add' with 3 args, but it expects 2"));
+    assert!(output.contains("[8:7 - 8:14] Called 'Rect' with 1 args, but it expects 2"));
+    assert!(output.contains("[9:7 - 9:18] Called 'len' with 2 args, but it expects 1"));
+
+    // Shadowed and reassigned functions are not known before running
+    assert_eq!(output.matches("but it expects").count(), 4);
+}
+
+#[test]
+fn vm_type_annotations() {
+    let output = interpret("tests/programs/type_annotations.zote");
+    assert_eq!(output, "6\n7\n0\nI am zote\n25\n3\n1\n");
+}
+
+#[test]
+fn vm_type_errors() {
+    let output = interpret_error("tests/programs/type_errors.zote");
+    assert!(output.contains("[4:11 - 4:18] Expected 'y' to be int, but found str"));
+    assert!(output.contains("[5:12 - 5:13] Expected 'z' to be bool, but found int"));
+    assert!(
+        output.contains("[6:6 - 6:11] Expected argument 1 of 'half' to be float, but found str")
+    );
+    assert!(output.contains("[7:11 - 7:17] Expected 'w' to be int, but found str"));
+    assert!(output.contains("[9:12 - 9:15] Expected the returned value to be int, but found list"));
+    assert!(output.contains("[12:9 - 12:15] Expected 'count' to be int, but found str"));
+
+    // Values from unannotated code are not known before running
+    assert_eq!(output.matches("Expected").count(), 6);
+}
+
+#[test]
+fn vm_type_runtime_error() {
+    let output = interpret_error("tests/programs/type_runtime_error.zote");
+    assert!(output.contains("Expected a value of type int, but got String"));
+}
+
+#[test]
+fn vm_return_type_error() {
+    let output = interpret_error("tests/programs/return_type_error.zote");
+    assert!(output.contains("Expected a value of type int, but got String"));
+}
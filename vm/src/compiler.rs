@@ -1,6 +1,7 @@
 mod bytecode;
 mod chunk;
 mod code_gen;
+mod constant_folding;
 mod control_flow;
 mod locals;
 
@@ -9,39 +10,75 @@ use std::collections::HashMap;
 pub use bytecode::OpCode;
 pub use chunk::Chunk;
 use parser::{CodeRange, Stmts};
-use semantic_analyzer::AttributedAst;
+use semantic_analyzer::{
+    find_arity_errors, find_type_errors, find_undefined_variables, find_variant_errors, AstFolder,
+    AttributedAst, NativeArity,
+};
 
 use crate::value::get_natives;
 
-use self::{control_flow::FlowPoints, locals::LocalState};
+use self::{constant_folding::ConstantFolder, control_flow::FlowPoints, locals::LocalState};
 
 /// Error type when compiling
 type CompRetRes<T> = Result<T, String>;
 type CompRes = CompRetRes<()>;
 
+/// Options for how the code should be compiled
+#[derive(Clone)]
+pub struct CompileOptions {
+    /// Whether assert! statements should be compiled, or removed from the code
+    pub asserts: bool,
+
+    /// Whether constant expressions should be folded, and branches on constants removed
+    pub optimize: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            asserts: true,
+            optimize: true,
+        }
+    }
+}
+
 /// Struct to store metadata during and between compilations
 pub struct Compiler<'a> {
     attributes: &'a AttributedAst<'a>,
+    options: CompileOptions,
     globals: HashMap<String, usize>,
     locals: LocalState,
     flow_points: FlowPoints,
     had_error: bool,
+
+    /// Is the next compiled expression in tail position, so that its value is returned directly?
+    tail_position: bool,
 }
 
 /// Compile AST to bytecode (top-level)
 pub fn compile<'a>(ast: &'a AttributedAst<'a>) -> Option<Chunk> {
-    let mut compiler = Compiler::new(ast);
+    compile_with_options(ast, CompileOptions::default())
+}
+
+/// Compile AST to bytecode (top-level), with non-default options
+pub fn compile_with_options<'a>(
+    ast: &'a AttributedAst<'a>,
+    options: CompileOptions,
+) -> Option<Chunk> {
+    let mut compiler = Compiler::new(ast, options);
     compiler.compile()
 }
 
 impl<'a> Compiler<'a> {
-    pub fn new(attributes: &'a AttributedAst) -> Self {
+    pub fn new(attributes: &'a AttributedAst, options: CompileOptions) -> Self {
         Self {
             attributes,
+            options,
             globals: HashMap::with_capacity(32),
             locals: LocalState::new(),
             flow_points: FlowPoints::new(),
             had_error: false,
+            tail_position: false,
         }
     }
 
@@ -50,17 +87,33 @@ impl<'a> Compiler<'a> {
     fn compile(&mut self) -> Option<Chunk> {
         let mut chunk = Chunk::new(); // TODO: Take as arg instead? How do we then handle errors?
 
+        // Report all unknown names and bad calls at once, instead of failing at the first one
+        self.report_undefined_variables();
+        self.report_arity_errors();
+        self.report_type_errors();
+        self.report_variant_errors();
+        if self.had_error {
+            return None;
+        }
+
+        // The folded nodes keep their ids, so the attributes of the checked code still apply
+        let folded = self
+            .options
+            .optimize
+            .then(|| ConstantFolder.fold_stmts(self.attributes.stmts().clone()));
+        let stmts = folded.as_ref().unwrap_or(self.attributes.stmts());
+
         self.declare_natives(&mut chunk);
-        self.declare_globals(self.attributes.stmts());
+        self.declare_globals(stmts);
 
         for _ in 0..self.attributes.global_local_count() {
             // Allocate space for global locals
             chunk.push_opcode(OpCode::Nil, CodeRange::from_ints(0, 0, 0, 0, 0, 0))
         }
 
-        self.compile_stmts(self.attributes.stmts(), &mut chunk);
+        self.compile_stmts(stmts, false, &mut chunk);
 
-        if self.attributes.stmts().output {
+        if stmts.output {
             chunk.push_opcode(OpCode::Return, CodeRange::from_ints(0, 0, 0, 0, 0, 0));
         }
 
@@ -70,13 +123,12 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn compile_stmts(&mut self, stmts: &Stmts, chunk: &mut Chunk) {
+    /// Compiles a sequence of statements. If tail is set, an output value is in tail position
+    fn compile_stmts(&mut self, stmts: &Stmts, tail: bool, chunk: &mut Chunk) {
         for (ind, stmt) in stmts.stmts.iter().enumerate() {
-            self.compile_statement(
-                stmt,
-                chunk,
-                stmts.output && (ind == (stmts.stmts.len() - 1)),
-            );
+            let output = stmts.output && (ind == (stmts.stmts.len() - 1));
+            self.tail_position = tail && output;
+            self.compile_statement(stmt, chunk, output);
         }
     }
 
@@ -84,6 +136,45 @@ impl<'a> Compiler<'a> {
         self.locals.is_global()
     }
 
+    fn report_undefined_variables(&mut self) {
+        let natives = get_natives();
+        let names: Vec<&str> = natives.iter().map(|native| native.name()).collect();
+        for undefined in find_undefined_variables(self.attributes.stmts(), &names) {
+            eprintln!("COMPILER ERROR: [{}] {undefined}", undefined.range);
+            self.had_error = true;
+        }
+    }
+
+    fn report_arity_errors(&mut self) {
+        let natives = get_natives();
+        let arities: Vec<NativeArity> = natives
+            .iter()
+            .map(|native| NativeArity {
+                name: native.name(),
+                arity: native.arity(),
+                accepts: Box::new(|count| native.accept_arity(count)),
+            })
+            .collect();
+        for error in find_arity_errors(self.attributes.stmts(), &arities) {
+            eprintln!("COMPILER ERROR: [{}] {error}", error.range);
+            self.had_error = true;
+        }
+    }
+
+    fn report_type_errors(&mut self) {
+        for error in find_type_errors(self.attributes.stmts()) {
+            eprintln!("COMPILER ERROR: [{}] {error}", error.range);
+            self.had_error = true;
+        }
+    }
+
+    fn report_variant_errors(&mut self) {
+        for error in find_variant_errors(self.attributes.stmts()) {
+            eprintln!("COMPILER ERROR: [{}] {error}", error.range);
+            self.had_error = true;
+        }
+    }
+
     pub fn declare_natives(&mut self, chunk: &mut Chunk) {
         let range = CodeRange::from_ints(0, 0, 0, 0, 0, 0);
         for native in get_natives() {
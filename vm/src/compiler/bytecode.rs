@@ -68,6 +68,35 @@ pub enum OpCode {
     /// Appends two collections
     Append,
 
+    /// Bitwise and of the two top-most integers on the stack
+    BitAnd,
+
+    /// Bitwise or of the two top-most integers on the stack
+    BitOr,
+
+    /// Bitwise xor of the two top-most integers on the stack
+    BitXor,
+
+    /// Shifts the second top-most integer on the stack left by the top value
+    LeftShift,
+
+    /// Arithmetically shifts the second top-most integer on the stack right by the top value
+    RightShift,
+
+    /// Bitwise negation of the topmost integer on the stack
+    BitNot,
+
+    /// Checks if the second top-most value is in the top collection, as in `x in xs`
+    In,
+
+    /// Checks if the second top-most value is not in the top collection, as in `x not in xs`
+    NotIn,
+
+    /// Checks if a value is in a range, without creating the list
+    ///
+    /// The stack should contain the value, and then the start, stop, and step as for ListFromSlice.
+    InRange,
+
     /// Assigns to a global variable
     ///
     /// Reads the offset of the global variable from the next bytecode byte.
@@ -137,6 +166,19 @@ pub enum OpCode {
     /// but not local variables.
     Call,
 
+    /// Calls the top value on the stack like Call, but as the return value of the current function
+    ///
+    /// A closure reuses the current call frame instead of creating a new one, so that
+    /// tail-recursive functions run in constant stack space. Other callables are called
+    /// normally, after which the current function returns.
+    TailCall,
+
+    /// Calls a value like Call, passing on all arguments of the current function after the others
+    ///
+    /// The next byte specifies how many arguments are pushed before the passed on ones, which
+    /// lets variadic functions call others with the same arguments.
+    CallForwarding,
+
     /// Intiates a closure from a function and upvalues
     ///
     /// The next byte specifies the constant index of the function to use init from.
@@ -168,6 +210,9 @@ pub enum OpCode {
     /// The topmost value is the index, and the second topmost is the collection
     ReadAtIndex,
 
+    /// Reads a value at an index like ReadAtIndex, but pushes Nil if the index or key is missing
+    SafeReadAtIndex,
+
     /// Reads and slice of a list
     ///
     /// The topmost 3 values is the slice, and the fourth is the list.
@@ -184,6 +229,16 @@ pub enum OpCode {
     /// The following byte tells how many of the top values on the stack to use.
     ListFromValues,
 
+    /// Constructs a dict from a computed set of key-value pairs
+    ///
+    /// The following byte tells how many pairs to use. Each pair is pushed as key then value.
+    DictFromValues,
+
+    /// Constructs a set from a computed set of values
+    ///
+    /// The following byte tells how many of the top values on the stack to use.
+    SetFromValues,
+
     /// Converts the top value of the stack to something iterable
     TopToIter,
 
@@ -201,9 +256,22 @@ pub enum OpCode {
     /// Does consume the top value.
     Len,
 
+    /// Gets the length of the top value like Len, but pushes Nil if it is not a collection
+    SafeLen,
+
     /// Swaps the two topmost values on the stack
     Swap,
 
+    /// Duplicates several of the top values on the stack, keeping their order
+    ///
+    /// The following byte tells how many values to duplicate.
+    DuplicateMany,
+
+    /// Moves the top value of the stack further down the stack
+    ///
+    /// The following byte tells how many values the top value should be moved below.
+    Rotate,
+
     /// Assigns into one index of a sliced value from another value
     ///
     /// Computes Assignee[SliceIndex] <- RHS[Index]
@@ -218,4 +286,18 @@ pub enum OpCode {
 
     /// Raises an error, with the error message at the top of the stack
     RaiseError,
+
+    /// Checks if the second top-most value is a variant of the constructor at the top
+    ///
+    /// The next byte is the number of fields in the pattern, which must match the variant.
+    /// Consumes both values and pushes a bool.
+    IsVariant,
+
+    /// Replaces the variant at the top of the stack with a list of its fields
+    VariantFields,
+
+    /// Raises an error if the top value on the stack does not have the annotated type
+    ///
+    /// The next byte is the index of the type in `Type::ALL`. Does not consume the value.
+    AssertType,
 }
@@ -1,11 +1,14 @@
+use std::rc::Rc;
+
 use parser::{
-    BinOper, CodeRange, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, Slice, Stmt,
-    StmtNode, Stmts, UnOper,
+    BinOper, CodeRange, Expr, ExprNode, Index, LValue, ListContent, LogicalOper, NodeId, Slice,
+    Stmt, StmtNode, Stmts, Type, UnOper,
 };
 
 use super::{Chunk, CompRes, CompRetRes, Compiler, OpCode};
-use crate::value::Value;
+use crate::value::{Value, VariantTag};
 
+mod assertions;
 mod conditionals;
 mod function;
 
@@ -15,12 +18,13 @@ impl Compiler<'_> {
             node,
             start_loc,
             end_loc,
+            id,
         } = statement;
         let range = CodeRange::from_locs(*start_loc, *end_loc);
 
         let res = match node.as_ref() {
             Stmt::Decl(lvalue, expr) => {
-                self.compile_declaration(lvalue, expr.as_ref(), range.clone(), chunk)
+                self.compile_declaration(lvalue, expr.as_ref(), *id, range.clone(), chunk)
             }
             Stmt::Expr(expr) => {
                 let res = self.compile_expression(expr, chunk);
@@ -30,6 +34,9 @@ impl Compiler<'_> {
                 }
                 res
             }
+            Stmt::Assert(cond, message, source) => {
+                self.compile_assert(cond, message.as_ref(), source, range.clone(), chunk)
+            }
             Stmt::Invalid => panic!("Cannot interpret invalid statements!"),
         };
 
@@ -45,14 +52,15 @@ impl Compiler<'_> {
         &mut self,
         lvalue: &LValue,
         expr: Option<&ExprNode>,
+        binder: NodeId,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
-        if let Some(expr_node) = expr && matches!(expr_node.node.as_ref(), Expr::FunctionDefinition(_, _, _))
+        if let Some(expr_node) = expr && matches!(expr_node.node.as_ref(), Expr::FunctionDefinition(_, _, _, _))
         {
             // Special case for function declarations: Allow them to be recursive, so define the variable before initing the function
             if !self.is_global() {
-                self.declare_local(lvalue, range.clone(), chunk)?;
+                self.declare_local(lvalue, binder, range.clone(), chunk)?;
             }
 
             self.compile_expression(expr_node, chunk)?;
@@ -64,7 +72,7 @@ impl Compiler<'_> {
             }
 
             if !self.is_global() {
-                self.declare_local(lvalue, range.clone(), chunk)?;
+                self.declare_local(lvalue, binder, range.clone(), chunk)?;
             }
 
             if expr.is_some() {
@@ -79,34 +87,47 @@ impl Compiler<'_> {
     /// Declares a local
     ///
     /// Mostly no codegen, but it assigns pointers to NIL for declared pointers.
-    /// lvalue_top specifies if this is the topmost lvalue in a declaration
-    fn declare_local(&mut self, lvalue: &LValue, range: CodeRange, chunk: &mut Chunk) -> CompRes {
+    /// The binder is the node binding the variables, used to look up if they are upvalues
+    fn declare_local(
+        &mut self,
+        lvalue: &LValue,
+        binder: NodeId,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
         match lvalue {
             LValue::Index(_, _) => {
                 return Err(format!("Cannot assign at an index in a declaration"))
             }
-            LValue::Var(name) => self.declare_local_var(name, false, range, chunk),
+            LValue::Var(name) => self.declare_local_var(name, binder, false, range, chunk),
             LValue::Tuple(lvalues) => {
                 for lvalue in lvalues.iter() {
-                    self.declare_local(lvalue, range.clone(), chunk)?;
+                    self.declare_local(lvalue, binder, range.clone(), chunk)?;
+                }
+            }
+            LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => {
+                self.declare_local(lvalue, binder, range, chunk)?
+            }
+            LValue::Variant(_, fields) => {
+                for field in fields.iter() {
+                    self.declare_local(field, binder, range.clone(), chunk)?;
                 }
             }
-            LValue::Constant(_) => (),
+            LValue::Constant(_) | LValue::Wildcard => (),
         }
         Ok(())
     }
 
     /// Inner function of declare_local, which declares the Var variant
-    ///
-    /// The name has to be the reference to the name in the Lvalue Var for semantic analysis
     pub fn declare_local_var(
         &mut self,
         name: &String,
+        binder: NodeId,
         already_inplace: bool,
         range: CodeRange,
         chunk: &mut Chunk,
     ) {
-        if self.attributes.is_upvalue(name) {
+        if self.attributes.is_upvalue(binder, name) {
             // Declares the local as a pointer insteal of a flat value
             let offset = self.locals.add_local(name.to_owned(), true);
 
@@ -152,18 +173,39 @@ impl Compiler<'_> {
             node,
             start_loc,
             end_loc,
+            id,
         } = expr;
         let range = CodeRange::from_locs(*start_loc, *end_loc);
 
+        // Only this expression is in tail position, not its sub-expressions
+        let tail = std::mem::take(&mut self.tail_position);
+
         match node.as_ref() {
-            Expr::Call(func, args) => self.compile_call(func, args, range, chunk)?,
+            Expr::Call(func, args) => self.compile_call(func, args, tail, range, chunk)?,
             Expr::IndexInto(base, index) => self.compile_index_into(base, index, range, chunk)?,
+            Expr::SafeIndexInto(base, index) => {
+                self.compile_safe_index_into(base, index, range, chunk)?
+            }
+            Expr::Binary(x, op @ (BinOper::In | BinOper::NotIn), y)
+                if let Expr::List(ListContent::Range(slice)) = y.node.as_ref() =>
+            {
+                // Ranges are common, so check them without creating the list
+                self.compile_expression(x, chunk)?;
+                self.compile_slice(slice, chunk)?;
+                chunk.push_opcode(OpCode::InRange, range.clone());
+                if op == &BinOper::NotIn {
+                    chunk.push_opcode(OpCode::Not, range);
+                }
+            }
             Expr::Binary(x, binop, y) => {
                 self.compile_expression(x, chunk)?;
                 self.compile_expression(y, chunk)?;
                 let opcode = binop_opcode_conv(binop);
                 chunk.push_opcode(opcode, range);
             }
+            Expr::ChainedComparison(first, links) => {
+                self.compile_chained_comparison(first, links, range, chunk)?
+            }
             Expr::Unary(unop, x) => {
                 self.compile_expression(x, chunk)?;
                 let opcode = unop_opcode_conv(unop);
@@ -173,6 +215,12 @@ impl Compiler<'_> {
                 self.compile_and(lhs, rhs, range, chunk)?
             }
             Expr::Logical(lhs, LogicalOper::Or, rhs) => self.compile_or(lhs, rhs, range, chunk)?,
+            Expr::Logical(lhs, LogicalOper::Coalesce, rhs) => {
+                self.compile_coalesce(lhs, rhs, range, chunk)?
+            }
+            Expr::OpAssign(lvalue, op, expr) => {
+                self.compile_op_assign(lvalue, op, expr, range, chunk)?
+            }
             Expr::Assign(lvalue, expr) => {
                 self.compile_lvalue_assignment(lvalue, expr, range, chunk)?;
             }
@@ -183,25 +231,60 @@ impl Compiler<'_> {
             Expr::String(string) => {
                 chunk.push_constant_plus((string.as_ref() as &str).into(), range)
             }
-            Expr::Block(stmts) => self.compile_block(stmts, range, chunk),
+            Expr::Block(stmts) => self.compile_block(stmts, tail, range, chunk),
             Expr::If(pred, then, otherwise) => {
-                self.compile_if(pred, then, otherwise.as_ref(), range, chunk)?
+                self.compile_if(pred, then, otherwise.as_ref(), tail, range, chunk)?
             }
-            Expr::While(pred, body) => self.compile_while(pred, body, range, chunk)?,
-            Expr::For(lvalue, collection, body) => {
-                self.compile_for(lvalue, collection, body, range, chunk)?
+            Expr::IfLet(lvalue, matched, then, otherwise) => self.compile_if_let(
+                lvalue,
+                matched,
+                then,
+                otherwise.as_ref(),
+                tail,
+                range,
+                chunk,
+            )?,
+            Expr::While(pred, body, otherwise, label) => self.compile_while(
+                pred,
+                body,
+                otherwise.as_ref(),
+                label.as_ref(),
+                range,
+                chunk,
+            )?,
+            Expr::WhileLet(lvalue, matched, body, otherwise, label) => self.compile_while_let(
+                lvalue,
+                matched,
+                body,
+                otherwise.as_ref(),
+                label.as_ref(),
+                range,
+                chunk,
+            )?,
+            Expr::For(lvalue, collection, body, otherwise, label) => self.compile_for(
+                lvalue,
+                collection,
+                body,
+                otherwise.as_ref(),
+                label.as_ref(),
+                range,
+                chunk,
+            )?,
+            Expr::Break(label, value) => {
+                self.compile_break(label.as_ref(), value.as_ref(), range, chunk)?
             }
-            Expr::Break => self.compile_break(range, chunk)?,
-            Expr::Continue => self.compile_continue(range, chunk)?,
+            Expr::Continue(label) => self.compile_continue(label.as_ref(), range, chunk)?,
             Expr::Return(opt_expr) => self.compile_return(opt_expr.as_ref(), range, chunk)?,
             Expr::Nil => chunk.push_constant_plus(Value::Nil, range),
             Expr::List(list) => self.compile_list(list, range, chunk)?,
+            Expr::Dict(pairs) => self.compile_dict(pairs, range, chunk)?,
+            Expr::Set(values) => self.compile_set(values, range, chunk)?,
             Expr::Tuple(_) => {
                 return Err("Tuples not implemented as expressions. Use a list.".to_owned())
             }
-            Expr::FunctionDefinition(name, params, body) => {
-                let upvalues = self.attributes.upvalue_names(node.as_ref()).unwrap_or(&[]);
-                let rec_name = self.attributes.rec_name(node.as_ref());
+            Expr::FunctionDefinition(name, params, body, returns) => {
+                let upvalues = self.attributes.upvalue_names(*id).unwrap_or(&[]);
+                let rec_name = self.attributes.rec_name(*id);
                 let name = match &rec_name {
                     Some(rec_name) => rec_name,
                     None => name,
@@ -209,7 +292,7 @@ impl Compiler<'_> {
 
                 let nbr_locals = self
                     .attributes
-                    .local_count(node.as_ref())
+                    .local_count(*id)
                     .expect("Function must have local count"); // TODO: DO this in compilation phase?
 
                 self.compile_function_def(
@@ -219,11 +302,26 @@ impl Compiler<'_> {
                     body,
                     upvalues,
                     nbr_locals,
+                    *returns,
                     range,
                     chunk,
                 )?;
             }
-            Expr::Match(base, arms) => self.compile_match(base, arms, range, chunk)?,
+            Expr::Match(base, arms) => self.compile_match(base, arms, tail, range, chunk)?,
+            Expr::Rest(_) => {
+                return Err("A rest pattern can only be used within a tuple pattern".to_string())
+            }
+            Expr::EnumVariant(enum_name, name, fields) => {
+                let tag = Rc::new(VariantTag::new(enum_name.clone(), name.clone(), fields.len()));
+                // Variants without fields are values, not constructors
+                let value = if fields.is_empty() {
+                    tag.unit()
+                } else {
+                    Value::Constructor(tag)
+                };
+                chunk.push_constant_plus(value, range)
+            }
+            Expr::Invalid => panic!("Cannot compile invalid expressions!"),
         };
 
         Ok(())
@@ -243,6 +341,95 @@ impl Compiler<'_> {
         self.compile_assign(lvalue, range, chunk)
     }
 
+    /// Compiles a compound assignment such as `x[i] += y`
+    ///
+    /// The sub-expressions of the lvalue are only evaluated once, and are kept on the stack
+    /// while the new value is computed. Leaves the assigned value on the stack.
+    fn compile_op_assign(
+        &mut self,
+        lvalue: &LValue,
+        op: &BinOper,
+        expr: &ExprNode,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        match lvalue {
+            LValue::Var(name) => {
+                self.compile_var(name, range.clone(), chunk)?;
+                self.compile_expression(expr, chunk)?;
+                chunk.push_opcode(binop_opcode_conv(op), range.clone());
+                chunk.push_opcode(OpCode::Duplicate, range.clone());
+                self.compile_assign_var(name, range, chunk)
+            }
+            LValue::Index(collection, Index::At(at)) => {
+                // Stack: collection, index
+                self.compile_expression(collection, chunk)?;
+                self.compile_expression(at, chunk)?;
+
+                // Stack: collection, index, new value
+                chunk.push_opcode(OpCode::DuplicateMany, range.clone());
+                chunk.push_u8_offset(2);
+                chunk.push_opcode(OpCode::ReadAtIndex, range.clone());
+                self.compile_expression(expr, chunk)?;
+                chunk.push_opcode(binop_opcode_conv(op), range.clone());
+
+                // Stack: new value, new value, collection, index
+                chunk.push_opcode(OpCode::Duplicate, range.clone());
+                chunk.push_opcode(OpCode::Rotate, range.clone());
+                chunk.push_u8_offset(3);
+                chunk.push_opcode(OpCode::Rotate, range.clone());
+                chunk.push_u8_offset(2);
+
+                chunk.push_opcode(OpCode::AssignAtIndex, range);
+                Ok(())
+            }
+            LValue::Index(collection, Index::Slice(slice)) => {
+                // Stack: collection, start, stop, step (with start and stop computed if omitted)
+                self.compile_expression(collection, chunk)?;
+                if slice.stop.is_none() {
+                    chunk.push_opcode(OpCode::Duplicate, range.clone());
+                    chunk.push_opcode(OpCode::Len, range.clone());
+                }
+                match &slice.start {
+                    Some(expr) => self.compile_expression(expr, chunk)?,
+                    None => chunk.push_constant_plus(Value::Int(0), range.clone()),
+                };
+                match &slice.stop {
+                    Some(stop) => self.compile_expression(stop, chunk)?,
+                    None => chunk.push_opcode(OpCode::Swap, range.clone()),
+                };
+                self.compile_opt_expression(slice.step.as_ref(), chunk)?;
+
+                // Stack: collection, start, stop, step, new value
+                chunk.push_opcode(OpCode::DuplicateMany, range.clone());
+                chunk.push_u8_offset(4);
+                chunk.push_opcode(OpCode::ReadAtSlice, range.clone());
+                self.compile_expression(expr, chunk)?;
+                chunk.push_opcode(binop_opcode_conv(op), range.clone());
+
+                // Stack: new value, RHS, collection, slice, index
+                chunk.push_opcode(OpCode::Duplicate, range.clone());
+                chunk.push_opcode(OpCode::Rotate, range.clone());
+                chunk.push_u8_offset(5);
+                chunk.push_opcode(OpCode::TopToIter, range.clone());
+                chunk.push_opcode(OpCode::Rotate, range.clone());
+                chunk.push_u8_offset(4);
+                chunk.push_opcode(OpCode::ListFromSlice, range.clone());
+                chunk.push_constant_plus(Value::Int(0), range.clone());
+
+                self.compile_assign_between_iterables(range, chunk)
+            }
+            LValue::Tuple(_)
+            | LValue::Constant(_)
+            | LValue::Wildcard
+            | LValue::Rest(_)
+            | LValue::Variant(_, _)
+            | LValue::Typed(_, _) => {
+                Err("Compound assignment requires a variable or an indexing".to_owned())
+            }
+        }
+    }
+
     /// Assigns the top value on the temp stack to the lvalue
     /// Consumes the assigned value.
     fn compile_assign(&mut self, lvalue: &LValue, range: CodeRange, chunk: &mut Chunk) -> CompRes {
@@ -253,9 +440,29 @@ impl Compiler<'_> {
             LValue::Var(name) => self.compile_assign_var(name, range, chunk),
             LValue::Tuple(lvalues) => self.compile_assign_tuple(lvalues, range, chunk),
             LValue::Constant(expected) => self.compile_assign_constant(expected, range, chunk),
+            LValue::Wildcard => {
+                chunk.push_opcode(OpCode::Discard, range);
+                Ok(())
+            }
+            LValue::Rest(_) => {
+                Err("A rest pattern can only be used directly within a tuple".to_owned())
+            }
+            LValue::Variant(name, fields) => {
+                self.compile_assign_variant(name, fields, range, chunk)
+            }
+            LValue::Typed(lvalue, annotated) => {
+                self.compile_assert_type(*annotated, range.clone(), chunk);
+                self.compile_assign(lvalue, range, chunk)
+            }
         }
     }
 
+    /// Raises an error at runtime unless the top value has the annotated type
+    pub fn compile_assert_type(&self, annotated: Type, range: CodeRange, chunk: &mut Chunk) {
+        chunk.push_opcode(OpCode::AssertType, range);
+        chunk.push_u8_offset(annotated as u8);
+    }
+
     /// Compiles the assignment into a tuple of values
     fn compile_assign_tuple(
         &mut self,
@@ -265,12 +472,47 @@ impl Compiler<'_> {
     ) -> CompRes {
         chunk.push_opcode(OpCode::TopToIter, range.clone());
 
+        let rest = rest_position(lvalues);
+        if rest.is_some() {
+            // The rest can be empty, but all other lvalues need a value
+            chunk.push_opcode(OpCode::Duplicate, range.clone());
+            chunk.push_opcode(OpCode::Len, range.clone());
+            chunk.push_constant_plus(((lvalues.len() - 1) as i64).into(), range.clone());
+            chunk.push_opcode(OpCode::LessThan, range.clone());
+            chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+            let reserved_ok = chunk.reserve_jump();
+
+            chunk.push_constant_plus(
+                "Not enough values to unpack in tuple assignment".into(),
+                range.clone(),
+            );
+            chunk.push_opcode(OpCode::RaiseError, range.clone());
+            chunk.patch_reserved_jump(reserved_ok);
+        }
+
         for (ind, lvalue) in lvalues.iter().enumerate() {
             // Clone the iterable as we want to use it several times and it will be consumed in ReadAtIndex
             chunk.push_opcode(OpCode::Duplicate, range.clone());
 
+            if let LValue::Rest(inner) = lvalue {
+                // Push the slice of all values not taken by the other lvalues
+                let after = (lvalues.len() - ind - 1) as i64;
+                chunk.push_constant_plus((ind as i64).into(), range.clone());
+                if after > 0 {
+                    chunk.push_constant_plus((-after).into(), range.clone());
+                } else {
+                    chunk.push_opcode(OpCode::Nil, range.clone());
+                }
+                chunk.push_opcode(OpCode::Nil, range.clone());
+                chunk.push_opcode(OpCode::ReadAtSlice, range.clone());
+
+                self.compile_assign(inner, range.clone(), chunk)?;
+                continue;
+            }
+
             // Push the indexed value
-            chunk.push_constant_plus((ind as i64).into(), range.clone());
+            let index = tuple_index(ind, lvalues.len(), rest);
+            chunk.push_constant_plus(index.into(), range.clone());
             chunk.push_opcode(OpCode::ReadAtIndex, range.clone());
             // TODO: Better error handling?
 
@@ -278,6 +520,12 @@ impl Compiler<'_> {
             self.compile_assign(lvalue, range.clone(), chunk)?;
         }
 
+        if rest.is_some() {
+            // All remaining values are covered by the rest
+            chunk.push_opcode(OpCode::Discard, range);
+            return Ok(());
+        }
+
         // Check that there are no more values in the iterable
         chunk.push_constant_plus((lvalues.len() as i64).into(), range.clone());
         chunk.push_opcode(OpCode::NextOrJump, range.clone());
@@ -321,6 +569,35 @@ impl Compiler<'_> {
         Ok(())
     }
 
+    /// Checks that the value is the expected enum variant, and assigns its fields
+    fn compile_assign_variant(
+        &mut self,
+        name: &String,
+        fields: &[LValue],
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        chunk.push_opcode(OpCode::Duplicate, range.clone());
+        self.compile_var(name, range.clone(), chunk)?;
+        chunk.push_opcode(OpCode::IsVariant, range.clone());
+        chunk.push_u8_offset(fields.len() as u8);
+        chunk.push_opcode(OpCode::Not, range.clone());
+        chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+        let reserved_ok = chunk.reserve_jump();
+
+        chunk.push_constant_plus(
+            format!("Assignment to variant failed (not a {name})")
+                .as_str()
+                .into(),
+            range.clone(),
+        );
+        chunk.push_opcode(OpCode::RaiseError, range.clone());
+
+        chunk.patch_reserved_jump(reserved_ok);
+        chunk.push_opcode(OpCode::VariantFields, range.clone());
+        self.compile_assign_tuple(fields, range, chunk)
+    }
+
     /// Compiles code to assign the top-most temp stack value into an indexed value such as list[index]
     fn compile_assign_index(
         &mut self,
@@ -474,6 +751,12 @@ impl Compiler<'_> {
         Ok(())
     }
 
+    /// Compiles an expression, marking it as in tail position if tail is set
+    fn compile_tail_expression(&mut self, expr: &ExprNode, tail: bool, chunk: &mut Chunk) -> CompRes {
+        self.tail_position = tail;
+        self.compile_expression(expr, chunk)
+    }
+
     /// Compiles the read of a var.
     fn compile_var(&mut self, name: &str, range: CodeRange, chunk: &mut Chunk) -> CompRes {
         if let Some((offset, pointer)) = self.locals.get_local(name) {
@@ -518,14 +801,20 @@ impl Compiler<'_> {
                     self.declare_global_lvalue(lvalue);
                 }
             }
-            LValue::Constant(_) => (),
+            LValue::Rest(lvalue) | LValue::Typed(lvalue, _) => self.declare_global_lvalue(lvalue),
+            LValue::Variant(_, fields) => {
+                for field in fields.iter() {
+                    self.declare_global_lvalue(field);
+                }
+            }
+            LValue::Constant(_) | LValue::Wildcard => (),
         }
     }
 
     /// Compiles the block of statements. Does not throw, as errors are printed and escaped within.
-    fn compile_block(&mut self, stmts: &Stmts, range: CodeRange, chunk: &mut Chunk) {
+    fn compile_block(&mut self, stmts: &Stmts, tail: bool, range: CodeRange, chunk: &mut Chunk) {
         self.locals.enter();
-        self.compile_stmts(stmts, chunk);
+        self.compile_stmts(stmts, tail, chunk);
         if !stmts.output || stmts.stmts.is_empty() {
             chunk.push_opcode(OpCode::Nil, range.clone());
         }
@@ -568,6 +857,46 @@ impl Compiler<'_> {
         Ok(())
     }
 
+    /// Compiles a dict literal, pushing each key followed by its value
+    fn compile_dict(
+        &mut self,
+        pairs: &[(ExprNode, ExprNode)],
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        if pairs.len() > 255 {
+            return Err(format!(
+                "Cannot init dict with over 255 pairs :( This one has {}",
+                pairs.len()
+            ));
+        }
+
+        for (key, value) in pairs {
+            self.compile_expression(key, chunk)?;
+            self.compile_expression(value, chunk)?;
+        }
+        chunk.push_opcode(OpCode::DictFromValues, range);
+        chunk.push_u8_offset(pairs.len() as u8);
+        Ok(())
+    }
+
+    /// Compiles a set literal
+    fn compile_set(&mut self, values: &[ExprNode], range: CodeRange, chunk: &mut Chunk) -> CompRes {
+        if values.len() > 255 {
+            return Err(format!(
+                "Cannot init set with over 255 values :( This one is {} long",
+                values.len()
+            ));
+        }
+
+        for value in values {
+            self.compile_expression(value, chunk)?;
+        }
+        chunk.push_opcode(OpCode::SetFromValues, range);
+        chunk.push_u8_offset(values.len() as u8);
+        Ok(())
+    }
+
     /// Compiles computations for the three parts of the slice
     ///
     /// If any of the fields are omitted, a NIL is pushed instead
@@ -604,6 +933,7 @@ impl Compiler<'_> {
         &mut self,
         base: &ExprNode,
         patterns: &[(LValue, ExprNode)],
+        tail: bool,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
@@ -623,11 +953,11 @@ impl Compiler<'_> {
                 self.compile_try_match(pattern, range.clone(), chunk)?;
 
             // If succesfull, assign into the pattern, consuming the value
-            self.declare_local(pattern, range.clone(), chunk)?;
+            self.declare_local(pattern, then.id, range.clone(), chunk)?;
             self.compile_assign(pattern, range.clone(), chunk)?;
 
             // Execute the expression, and leave it as the top stack value
-            self.compile_expression(then, chunk)?;
+            self.compile_tail_expression(then, tail, chunk)?;
 
             // Exit the match arm scope
             let pointer_offsets = self.locals.exit();
@@ -677,19 +1007,39 @@ impl Compiler<'_> {
                 chunk.push_opcode(OpCode::Discard, range.clone());
                 return Err("Index-into lvalues not supported in match expressions".to_owned());
             }
-            LValue::Var(_) => chunk.push_opcode(OpCode::Discard, range.clone()), // Can match against anything
+            // Can match against anything
+            LValue::Var(_) | LValue::Wildcard => chunk.push_opcode(OpCode::Discard, range.clone()),
             LValue::Tuple(lvalues) => {
                 let mut halfway_abort_jumps = vec![];
+                let rest = rest_position(lvalues);
 
-                // Check that the length is ok
+                // Values without a length cannot match a tuple
                 chunk.push_opcode(OpCode::Duplicate, range.clone());
-                chunk.push_opcode(OpCode::Len, range.clone());
-                chunk.push_constant_plus((lvalues.len() as i64).into(), range.clone());
-                chunk.push_opcode(OpCode::NonEquality, range.clone());
+                chunk.push_opcode(OpCode::SafeLen, range.clone());
+                chunk.push_opcode(OpCode::Duplicate, range.clone());
+                chunk.push_opcode(OpCode::Nil, range.clone());
+                chunk.push_opcode(OpCode::Equality, range.clone());
+                chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+                let has_len_jump = chunk.reserve_jump();
+
+                chunk.push_opcode(OpCode::Discard, range.clone());
+                chunk.push_opcode(OpCode::Jump, range.clone());
+                let no_len_jump = chunk.reserve_jump();
+
+                // Check that the length is ok. With a rest, there can be more values than lvalues
+                chunk.patch_reserved_jump(has_len_jump);
+                if rest.is_some() {
+                    chunk.push_constant_plus(((lvalues.len() - 1) as i64).into(), range.clone());
+                    chunk.push_opcode(OpCode::LessThan, range.clone());
+                } else {
+                    chunk.push_constant_plus((lvalues.len() as i64).into(), range.clone());
+                    chunk.push_opcode(OpCode::NonEquality, range.clone());
+                }
                 chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
                 let success_jump = chunk.reserve_jump();
 
                 // In case lengths don't match, consume the value and abort
+                chunk.patch_reserved_jump(no_len_jump);
                 chunk.push_opcode(OpCode::Discard, range.clone());
                 chunk.push_opcode(OpCode::Jump, range.clone());
                 abort_jumps.push(chunk.reserve_jump());
@@ -699,13 +1049,14 @@ impl Compiler<'_> {
 
                 // Then check that it can match against all individual values
                 for (ind, lvalue) in lvalues.iter().enumerate() {
-                    if let LValue::Var(_) = lvalue {
+                    if let LValue::Var(_) | LValue::Wildcard | LValue::Rest(_) = lvalue {
                         // Don't have to check anything here, just for simplicity
                         continue;
                     }
                     // Take out the value from the collection
                     chunk.push_opcode(OpCode::Duplicate, range.clone());
-                    chunk.push_constant_plus((ind as i64).into(), range.clone());
+                    let index = tuple_index(ind, lvalues.len(), rest);
+                    chunk.push_constant_plus(index.into(), range.clone());
                     chunk.push_opcode(OpCode::ReadAtIndex, range.clone());
 
                     // See if the indexed value matches the lvalue
@@ -734,6 +1085,14 @@ impl Compiler<'_> {
                 // Remember to consume the interating value
                 chunk.push_opcode(OpCode::Discard, range.clone());
             }
+            LValue::Rest(_) => {
+                chunk.push_opcode(OpCode::Discard, range.clone());
+                return Err("A rest pattern can only be used directly within a tuple".to_owned());
+            }
+            LValue::Typed(_, _) => {
+                chunk.push_opcode(OpCode::Discard, range.clone());
+                return Err("Type annotations are not supported in match patterns".to_owned());
+            }
             LValue::Constant(constant) => {
                 self.compile_expression(constant, chunk)?;
                 chunk.push_opcode(OpCode::Equality, range.clone());
@@ -742,12 +1101,52 @@ impl Compiler<'_> {
                 chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
                 abort_jumps.push(chunk.reserve_jump());
             }
+            LValue::Variant(name, fields) => {
+                // Check the tag, consuming the value if it is the wrong variant
+                chunk.push_opcode(OpCode::Duplicate, range.clone());
+                self.compile_var(name, range.clone(), chunk)?;
+                chunk.push_opcode(OpCode::IsVariant, range.clone());
+                chunk.push_u8_offset(fields.len() as u8);
+                chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+                let wrong_tag = chunk.reserve_jump();
+
+                // Then match the fields as a tuple
+                chunk.push_opcode(OpCode::VariantFields, range.clone());
+                let fields = LValue::Tuple(fields.clone());
+                abort_jumps.extend(self.compile_try_match(&fields, range.clone(), chunk)?);
+                chunk.push_opcode(OpCode::Jump, range.clone());
+                let ok_exit = chunk.reserve_jump();
+
+                chunk.patch_reserved_jump(wrong_tag);
+                chunk.push_opcode(OpCode::Discard, range.clone());
+                chunk.push_opcode(OpCode::Jump, range.clone());
+                abort_jumps.push(chunk.reserve_jump());
+
+                chunk.patch_reserved_jump(ok_exit);
+            }
         }
 
         Ok(abort_jumps)
     }
 }
 
+/// The position of the rest pattern in a tuple, if it has one
+fn rest_position(lvalues: &[LValue]) -> Option<usize> {
+    lvalues
+        .iter()
+        .position(|lvalue| matches!(lvalue, LValue::Rest(_)))
+}
+
+/// The index to read the value of a tuple lvalue from
+///
+/// Values after a rest pattern are indexed from the end.
+fn tuple_index(ind: usize, len: usize, rest: Option<usize>) -> i64 {
+    match rest {
+        Some(rest) if ind > rest => ind as i64 - len as i64,
+        _ => ind as i64,
+    }
+}
+
 fn binop_opcode_conv(binop: &BinOper) -> OpCode {
     match binop {
         BinOper::Add => OpCode::Add,
@@ -763,6 +1162,13 @@ fn binop_opcode_conv(binop: &BinOper) -> OpCode {
         BinOper::Gt => OpCode::GreaterThan,
         BinOper::Geq => OpCode::GreaterEqual,
         BinOper::Append => OpCode::Append,
+        BinOper::BitAnd => OpCode::BitAnd,
+        BinOper::BitOr => OpCode::BitOr,
+        BinOper::BitXor => OpCode::BitXor,
+        BinOper::LShift => OpCode::LeftShift,
+        BinOper::RShift => OpCode::RightShift,
+        BinOper::In => OpCode::In,
+        BinOper::NotIn => OpCode::NotIn,
     }
 }
 
@@ -770,5 +1176,6 @@ fn unop_opcode_conv(unop: &UnOper) -> OpCode {
     match unop {
         UnOper::Not => OpCode::Not,
         UnOper::Sub => OpCode::Negate,
+        UnOper::BitNot => OpCode::BitNot,
     }
 }
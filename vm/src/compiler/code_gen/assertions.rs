@@ -0,0 +1,126 @@
+use parser::{BinOper, CodeRange, Expr, ExprNode};
+
+use crate::{
+    compiler::{Chunk, Compiler, OpCode},
+    value::get_natives,
+};
+
+use super::{binop_opcode_conv, CompRes};
+
+impl Compiler<'_> {
+    /// Compiles an assertion, raising an error describing the condition if it is false
+    ///
+    /// For comparisons, the operand values are kept on the stack so they can be shown in
+    /// the error message.
+    pub fn compile_assert(
+        &mut self,
+        cond: &ExprNode,
+        message: Option<&ExprNode>,
+        source: &str,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        if !self.options.asserts {
+            return Ok(());
+        }
+
+        if let Expr::Binary(lhs, op, rhs) = cond.node.as_ref()
+            && is_comparison(op)
+        {
+            self.compile_expression(lhs, chunk)?;
+            self.compile_expression(rhs, chunk)?;
+            chunk.push_opcode(OpCode::DuplicateMany, range.clone());
+            chunk.push_u8_offset(2);
+            chunk.push_opcode(binop_opcode_conv(op), range.clone());
+            chunk.push_opcode(OpCode::Not, range.clone());
+            chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+            let reserved_ok = chunk.reserve_jump();
+
+            // Stack: lhs, rhs. Stringify both, and place the message between them
+            compile_stringify(range.clone(), chunk);
+            chunk.push_opcode(OpCode::Swap, range.clone());
+            compile_stringify(range.clone(), chunk);
+            self.compile_assert_header(message, source, range.clone(), chunk)?;
+            compile_append_str("\n  left: ", range.clone(), chunk);
+            chunk.push_opcode(OpCode::Swap, range.clone());
+            chunk.push_opcode(OpCode::Append, range.clone());
+            compile_append_str("\n  right: ", range.clone(), chunk);
+            chunk.push_opcode(OpCode::Swap, range.clone());
+            chunk.push_opcode(OpCode::Append, range.clone());
+            chunk.push_opcode(OpCode::RaiseError, range.clone());
+
+            chunk.patch_reserved_jump(reserved_ok);
+            chunk.push_opcode(OpCode::Discard, range.clone());
+            chunk.push_opcode(OpCode::Discard, range);
+        } else {
+            self.compile_expression(cond, chunk)?;
+            chunk.push_opcode(OpCode::Not, range.clone());
+            chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+            let reserved_ok = chunk.reserve_jump();
+
+            self.compile_assert_header(message, source, range.clone(), chunk)?;
+            chunk.push_opcode(OpCode::RaiseError, range);
+
+            chunk.patch_reserved_jump(reserved_ok);
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the first part of the error message, including the optional user message
+    fn compile_assert_header(
+        &mut self,
+        message: Option<&ExprNode>,
+        source: &str,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        let header = format!("Assertion failed: {source}");
+        chunk.push_constant_plus(header.as_str().into(), range.clone());
+
+        if let Some(message) = message {
+            // The message is only evaluated when the assertion fails
+            compile_append_str("\n  message: ", range.clone(), chunk);
+            self.compile_expression(message, chunk)?;
+            compile_stringify(range.clone(), chunk);
+            chunk.push_opcode(OpCode::Append, range);
+        }
+
+        Ok(())
+    }
+}
+
+fn is_comparison(op: &BinOper) -> bool {
+    matches!(
+        op,
+        BinOper::Eq
+            | BinOper::Neq
+            | BinOper::Lt
+            | BinOper::Leq
+            | BinOper::Gt
+            | BinOper::Geq
+            | BinOper::In
+            | BinOper::NotIn
+    )
+}
+
+/// Converts the top value of the stack to a string, using the native str function
+///
+/// Pushes the native directly, so that it works even if 'str' is shadowed.
+fn compile_stringify(range: CodeRange, chunk: &mut Chunk) {
+    let str_native = get_natives()
+        .into_iter()
+        .find(|native| native.name() == "str")
+        .expect("Internal error: The native 'str' must exist");
+
+    chunk.push_constant_plus(str_native.into(), range.clone());
+    chunk.push_opcode(OpCode::Swap, range.clone());
+    chunk.push_opcode(OpCode::Call, range);
+    chunk.push_u8_offset(1);
+}
+
+/// Appends a constant string to the string at the top of the stack
+fn compile_append_str(string: &str, range: CodeRange, chunk: &mut Chunk) {
+    chunk.push_constant_plus(string.into(), range.clone());
+    chunk.push_opcode(OpCode::Append, range);
+}
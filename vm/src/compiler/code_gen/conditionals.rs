@@ -1,11 +1,11 @@
-use parser::{CodeRange, ExprNode, LValue};
+use parser::{BinOper, CodeRange, ExprNode, Index, LValue};
 
 use crate::{
     compiler::{Chunk, Compiler, OpCode},
     value::Value,
 };
 
-use super::CompRes;
+use super::{binop_opcode_conv, CompRes};
 
 impl Compiler<'_> {
     pub fn compile_if(
@@ -13,24 +13,72 @@ impl Compiler<'_> {
         pred: &ExprNode,
         then: &ExprNode,
         otherwise: Option<&ExprNode>,
+        tail: bool,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
         self.compile_expression(pred, chunk)?;
         chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
         let reserved_else = chunk.reserve_jump();
-        self.compile_expression(then, chunk)?;
+        self.compile_tail_expression(then, tail, chunk)?;
 
         chunk.push_opcode(OpCode::Jump, range);
         let reserved_end = chunk.reserve_jump();
 
         chunk.patch_reserved_jump(reserved_else); // Jump to the beginning of else clause
-        self.compile_opt_expression(otherwise, chunk)?;
+        match otherwise {
+            Some(otherwise) => self.compile_tail_expression(otherwise, tail, chunk)?,
+            None => self.compile_opt_expression(None, chunk)?,
+        }
         chunk.patch_reserved_jump(reserved_end); // Jump to end of if statement
 
         Ok(())
     }
 
+    /// Compiles an if with a pattern, taking the then branch with the pattern bound if it matches
+    pub fn compile_if_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        then: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        tail: bool,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        self.compile_expression(matched, chunk)?;
+
+        // The pattern is only bound in the then branch
+        self.locals.enter();
+
+        chunk.push_opcode(OpCode::Duplicate, range.clone()); // As the try_match will consume the top
+        let reserved_fails = self.compile_try_match(lvalue, range.clone(), chunk)?;
+
+        // If succesfull, assign into the pattern, consuming the value
+        self.declare_local(lvalue, then.id, range.clone(), chunk)?;
+        self.compile_assign(lvalue, range.clone(), chunk)?;
+        self.compile_tail_expression(then, tail, chunk)?;
+
+        let pointer_offsets = self.locals.exit();
+        self.drop_pointers(&pointer_offsets, range.clone(), chunk);
+
+        chunk.push_opcode(OpCode::Jump, range.clone());
+        let reserved_end = chunk.reserve_jump();
+
+        // If the match fails, the matched value is still on the stack
+        for reserved in reserved_fails {
+            chunk.patch_reserved_jump(reserved);
+        }
+        chunk.push_opcode(OpCode::Discard, range);
+        match otherwise {
+            Some(otherwise) => self.compile_tail_expression(otherwise, tail, chunk)?,
+            None => self.compile_opt_expression(None, chunk)?,
+        }
+        chunk.patch_reserved_jump(reserved_end);
+
+        Ok(())
+    }
+
     pub fn compile_and(
         &mut self,
         lhs: &ExprNode,
@@ -59,6 +107,53 @@ impl Compiler<'_> {
         Ok(())
     }
 
+    /// Compiles comparisons such as `a < b <= c`, evaluating each operand once
+    ///
+    /// Each inner operand is kept below the comparison result, so it can be compared with the next
+    /// operand. Short-circuits to false as soon as a comparison fails.
+    pub fn compile_chained_comparison(
+        &mut self,
+        first: &ExprNode,
+        links: &[(BinOper, ExprNode)],
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        let ((last_op, last_operand), inner_links) =
+            links.split_last().expect("A chain should have several links");
+
+        self.compile_expression(first, chunk)?;
+
+        let mut reserved_fails = vec![];
+        for (op, operand) in inner_links {
+            // Keep a copy of the operand below the lhs and the operand: [operand, lhs, operand]
+            self.compile_expression(operand, chunk)?;
+            chunk.push_opcode(OpCode::Duplicate, range.clone());
+            chunk.push_opcode(OpCode::Rotate, range.clone());
+            chunk.push_u8_offset(2);
+
+            chunk.push_opcode(binop_opcode_conv(op), range.clone());
+            chunk.push_opcode(OpCode::JumpIfFalse, range.clone());
+            reserved_fails.push(chunk.reserve_jump());
+        }
+
+        // The last comparison gives the value of the whole chain
+        self.compile_expression(last_operand, chunk)?;
+        chunk.push_opcode(binop_opcode_conv(last_op), range.clone());
+        chunk.push_opcode(OpCode::Jump, range.clone());
+        let reserved_end = chunk.reserve_jump();
+
+        // Failed, so discard the kept operand and use false
+        for reserved in reserved_fails {
+            chunk.patch_reserved_jump(reserved);
+        }
+        chunk.push_opcode(OpCode::Discard, range.clone());
+        chunk.push_constant_plus(Value::Bool(false), range);
+
+        chunk.patch_reserved_jump(reserved_end);
+
+        Ok(())
+    }
+
     pub fn compile_or(
         &mut self,
         lhs: &ExprNode,
@@ -90,16 +185,80 @@ impl Compiler<'_> {
         Ok(())
     }
 
+    pub fn compile_coalesce(
+        &mut self,
+        lhs: &ExprNode,
+        rhs: &ExprNode,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        self.compile_expression(lhs, chunk)?;
+
+        // If not nil, keep the lhs
+        let reserved_keep = self.compile_jump_if_not_nil(range.clone(), chunk);
+
+        // Otherwise replace it with the rhs
+        chunk.push_opcode(OpCode::Discard, range);
+        self.compile_expression(rhs, chunk)?;
+
+        chunk.patch_reserved_jump(reserved_keep);
+
+        Ok(())
+    }
+
+    /// Indexes into a value, resulting in nil if the value is nil or the index is missing
+    pub fn compile_safe_index_into(
+        &mut self,
+        base: &ExprNode,
+        index: &Index,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        self.compile_expression(base, chunk)?;
+
+        // If nil, skip the indexing and keep the nil
+        let reserved_index = self.compile_jump_if_not_nil(range.clone(), chunk);
+        chunk.push_opcode(OpCode::Jump, range.clone());
+        let reserved_exit = chunk.reserve_jump();
+
+        chunk.patch_reserved_jump(reserved_index);
+        match index {
+            Index::At(at) => {
+                self.compile_expression(at, chunk)?;
+                chunk.push_opcode(OpCode::SafeReadAtIndex, range);
+            }
+            Index::Slice(slice) => {
+                self.compile_slice(slice, chunk)?;
+                chunk.push_opcode(OpCode::ReadAtSlice, range);
+            }
+        }
+
+        chunk.patch_reserved_jump(reserved_exit);
+
+        Ok(())
+    }
+
+    /// Jumps if the top value is not nil, without consuming it. Returns the reserved jump.
+    fn compile_jump_if_not_nil(&mut self, range: CodeRange, chunk: &mut Chunk) -> usize {
+        chunk.push_opcode(OpCode::Duplicate, range.clone());
+        chunk.push_constant_plus(Value::Nil, range.clone());
+        chunk.push_opcode(OpCode::Equality, range.clone());
+        chunk.push_opcode(OpCode::JumpIfFalse, range);
+        chunk.reserve_jump()
+    }
+
     pub fn compile_while(
         &mut self,
         pred: &ExprNode,
         body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        label: Option<&String>,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
         // Start of every loop iteration
         let start_label = chunk.len();
-        self.flow_points.push_loop_entry(start_label);
+        self.flow_points.push_loop_entry(start_label, label, 0);
 
         // Evaluate predicate, potentially exiting
         self.compile_expression(pred, chunk)?;
@@ -117,10 +276,73 @@ impl Compiler<'_> {
         chunk.push_jump(start_label);
 
         // Close the loop
-        self.flow_points.close_loop(chunk)?;
+        let break_exits = self.flow_points.close_loop(chunk)?;
+
+        // Without breaking, the value of the loop is from the else branch
+        self.compile_opt_expression(otherwise, chunk)?;
+
+        // Breaking leaves the value on the stack, so just exit
+        for reserved in break_exits {
+            chunk.patch_reserved_jump(reserved);
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a while loop which keeps looping as long as the matched value fits the pattern
+    pub fn compile_while_let(
+        &mut self,
+        lvalue: &LValue,
+        matched: &ExprNode,
+        body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        label: Option<&String>,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        // Start of every loop iteration
+        let start_label = chunk.len();
+        self.flow_points.push_loop_entry(start_label, label, 0);
+
+        self.compile_expression(matched, chunk)?;
+
+        // Enter a new scope for the pattern bindings
+        self.locals.enter();
+
+        chunk.push_opcode(OpCode::Duplicate, range.clone()); // As the try_match will consume the top
+        let reserved_fails = self.compile_try_match(lvalue, range.clone(), chunk)?;
+        self.declare_local(lvalue, body.id, range.clone(), chunk)?;
+        self.compile_assign(lvalue, range.clone(), chunk)?;
+
+        // Evaluate body, potentially containing wierd control flow
+        self.compile_expression(body, chunk)?;
+        chunk.push_opcode(OpCode::Discard, range.clone());
+
+        let pointer_offsets = self.locals.exit();
+        self.drop_pointers(&pointer_offsets, range.clone(), chunk);
+
+        // Jump back to the start
+        chunk.push_opcode(OpCode::Jump, range.clone());
+        chunk.push_jump(start_label);
+
+        // If the match fails, discard the matched value and exit the loop
+        for reserved in reserved_fails {
+            chunk.patch_reserved_jump(reserved);
+        }
+        chunk.push_opcode(OpCode::Discard, range.clone());
+        chunk.push_opcode(OpCode::Jump, range);
+        self.flow_points.push_loop_exit(chunk.reserve_jump());
+
+        // Close the loop
+        let break_exits = self.flow_points.close_loop(chunk)?;
+
+        // Without breaking, the value of the loop is from the else branch
+        self.compile_opt_expression(otherwise, chunk)?;
 
-        // Push an ending Nil for the expression
-        chunk.push_opcode(OpCode::Nil, range.clone());
+        // Breaking leaves the value on the stack, so just exit
+        for reserved in break_exits {
+            chunk.patch_reserved_jump(reserved);
+        }
 
         Ok(())
     }
@@ -133,6 +355,8 @@ impl Compiler<'_> {
         lvalue: &LValue,
         collection: &ExprNode,
         body: &ExprNode,
+        otherwise: Option<&ExprNode>,
+        label: Option<&String>,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
@@ -143,9 +367,9 @@ impl Compiler<'_> {
         // Push the index of the iterable
         chunk.push_constant_plus(Value::Int(0), range.clone());
 
-        // Start of every loop iteration
+        // Start of every loop iteration. The iterable and index are kept on the stack
         let start_label = chunk.len();
-        self.flow_points.push_loop_entry(start_label);
+        self.flow_points.push_loop_entry(start_label, label, 2);
 
         // Get next item from iterable, potentially abandoning loop
         chunk.push_opcode(OpCode::NextOrJump, range.clone());
@@ -155,7 +379,7 @@ impl Compiler<'_> {
         self.locals.enter();
 
         // Assign the indexed value into the lvalue. Crash if non-compatible
-        self.declare_local(lvalue, range.clone(), chunk)?;
+        self.declare_local(lvalue, body.id, range.clone(), chunk)?;
         self.compile_assign(lvalue, range.clone(), chunk)?;
 
         // Evaluate body, potentially containing wierd control flow
@@ -172,28 +396,79 @@ impl Compiler<'_> {
         chunk.push_jump(start_label);
 
         // Close the loop
-        self.flow_points.close_loop(chunk)?;
+        let break_exits = self.flow_points.close_loop(chunk)?;
 
         // Remove the looping values from the stack
         chunk.push_opcode(OpCode::Discard, range.clone());
         chunk.push_opcode(OpCode::Discard, range.clone());
 
-        // Push a NIL as the return value from the loop
-        chunk.push_opcode(OpCode::Nil, range.clone());
+        // Without breaking, the value of the loop is from the else branch
+        self.compile_opt_expression(otherwise, chunk)?;
+
+        if !break_exits.is_empty() {
+            chunk.push_opcode(OpCode::Jump, range.clone());
+            let reserved_end = chunk.reserve_jump();
+
+            // Breaking leaves the value on top of the looping values, so move it below them
+            for reserved in break_exits {
+                chunk.patch_reserved_jump(reserved);
+            }
+            chunk.push_opcode(OpCode::Rotate, range.clone());
+            chunk.push_u8_offset(2);
+            chunk.push_opcode(OpCode::Discard, range.clone());
+            chunk.push_opcode(OpCode::Discard, range);
+
+            chunk.patch_reserved_jump(reserved_end);
+        }
 
         Ok(())
     }
 
-    pub fn compile_break(&mut self, range: CodeRange, chunk: &mut Chunk) -> CompRes {
+    pub fn compile_break(
+        &mut self,
+        label: Option<&String>,
+        value: Option<&ExprNode>,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        let loop_index = self.flow_points.find_loop(label, "Break")?;
+
+        // The value of the loop is kept on the stack when exiting
+        self.compile_opt_expression(value, chunk)?;
+
+        // Remove the values of the loops we break out of, keeping the value on top
+        let nested = self.flow_points.nested_stack_values(loop_index);
+        if nested > 0 {
+            chunk.push_opcode(OpCode::Rotate, range.clone());
+            chunk.push_u8_offset(nested as u8);
+            for _ in 0..nested {
+                chunk.push_opcode(OpCode::Discard, range.clone());
+            }
+        }
+
         // TODO: ERROR: Does not exit scopes, so does not de-allocate pointers
         chunk.push_opcode(OpCode::Jump, range);
-        self.flow_points.push_break_exit(chunk.reserve_jump())
+        self.flow_points
+            .push_break_exit(loop_index, chunk.reserve_jump());
+        Ok(())
     }
 
-    pub fn compile_continue(&mut self, range: CodeRange, chunk: &mut Chunk) -> CompRes {
+    pub fn compile_continue(
+        &mut self,
+        label: Option<&String>,
+        range: CodeRange,
+        chunk: &mut Chunk,
+    ) -> CompRes {
+        let loop_index = self.flow_points.find_loop(label, "Continue")?;
+
+        // Remove the values of the loops we continue out of
+        for _ in 0..self.flow_points.nested_stack_values(loop_index) {
+            chunk.push_opcode(OpCode::Discard, range.clone());
+        }
+
         // TODO: ERROR: Does not exit scopes, so does not de-allocate pointers
         chunk.push_opcode(OpCode::Jump, range);
-        let loop_entry = self.flow_points.get_loop_entry()?;
+        let loop_entry = self.flow_points.get_loop_entry(loop_index);
         chunk.push_jump(loop_entry);
         Ok(())
     }
@@ -207,7 +482,11 @@ impl Compiler<'_> {
         // if self.locals.parent.is_none() {
         //     return Err("Cannot return from top-level scope".to_string());
         // }
-        self.compile_opt_expression(opt_expr, chunk)?;
+        match opt_expr {
+            // Returned calls can reuse the call frame, but not at the top-level
+            Some(expr) => self.compile_tail_expression(expr, self.locals.in_function(), chunk)?,
+            None => self.compile_opt_expression(None, chunk)?,
+        }
 
         // Drop all pointers before returning
         let pointer_offsets = self.locals.local_pointers();
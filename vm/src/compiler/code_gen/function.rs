@@ -1,4 +1,4 @@
-use parser::{CodeRange, ExprNode, LValue};
+use parser::{CodeRange, ExprNode, LValue, NodeId, Type};
 
 use crate::{
     compiler::{Chunk, CompRes, CompRetRes, Compiler, OpCode},
@@ -18,6 +18,7 @@ impl Compiler<'_> {
         body: &ExprNode,
         upvalues: &[String],
         nbr_locals: usize,
+        returns: Option<Type>,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
@@ -47,11 +48,13 @@ impl Compiler<'_> {
         // Add all of the parameters as reachable locals
         // The function and locals take up the first `arity + 1` spots in the call frame
         // Then they also take up one extra spot for each parameter which is a pattern match
-        let extra_locals = self.declare_parameters(params, range.clone(), &mut func_chunk)?;
-        self.compile_parameter_expansion(params, range.clone(), &mut func_chunk)?;
+        // The parameters are bound by the body of the function
+        let extra_locals =
+            self.declare_parameters(params, body.id, range.clone(), &mut func_chunk)?;
+        self.compile_parameter_expansion(params, body.id, range.clone(), &mut func_chunk)?;
 
-        // Compile the actual body into the func chunk
-        self.compile_expression(body, &mut func_chunk)?;
+        // Compile the actual body into the func chunk. Its value is returned, so it is in tail position
+        self.compile_tail_expression(body, true, &mut func_chunk)?;
 
         // Return implicitly in case of no other return
         func_chunk.push_opcode(OpCode::Return, range.clone());
@@ -64,7 +67,8 @@ impl Compiler<'_> {
             nbr_locals + extra_locals,
             name.to_string(),
             func_chunk,
-        );
+        )
+        .with_returns(returns);
 
         // self.add_function(func);
         // chunk.push_constant_plus(func.into(), range);
@@ -105,6 +109,7 @@ impl Compiler<'_> {
     fn declare_parameters(
         &mut self,
         params: &[LValue],
+        binder: NodeId,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRetRes<usize> {
@@ -115,15 +120,15 @@ impl Compiler<'_> {
 
         for param in params {
             // Parameters are just local variables in outermost scope
-            if let LValue::Var(name) = param {
+            if let Some(name) = in_place_name(param) {
                 // Set already-inplace to signify we have already pushed the value at this point
-                self.declare_local_var(name, true, range.clone(), chunk);
+                self.declare_local_var(name, binder, true, range.clone(), chunk);
 
                 // This uses a normal spot, so we will not need an extra slot for it
                 extra_param_slots -= 1;
             } else {
                 // Declare a dummy local as we will not be able to directly access the value placed in this argument location
-                self.declare_local_var(&"".to_string(), true, range.clone(), chunk);
+                self.declare_local_var(&"".to_string(), binder, true, range.clone(), chunk);
             }
         }
 
@@ -134,15 +139,23 @@ impl Compiler<'_> {
     fn compile_parameter_expansion(
         &mut self,
         params: &[LValue],
+        binder: NodeId,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
         for (param_ind, param) in params.iter().enumerate() {
-            if !matches!(param, LValue::Var(_)) {
+            if let (LValue::Typed(_, annotated), Some(_)) = (param, in_place_name(param)) {
+                // An annotated variable is already in place, but its argument must be checked
+                chunk.push_opcode(OpCode::ReadLocal, range.clone());
+                chunk.push_u8_offset((param_ind + 1) as u8);
+                self.compile_assert_type(*annotated, range.clone(), chunk);
+                chunk.push_opcode(OpCode::Discard, range.clone());
+            } else if !matches!(param, LValue::Var(_) | LValue::Wildcard) {
+                // A wildcard never has to be read, so it can be left in its argument spot.
                 // Pattern matching lvalue. The arg is stored at the arg_ind offset from rbp
 
                 // 1: Declare the variables as locals
-                self.declare_local(param, range.clone(), chunk)?;
+                self.declare_local(param, binder, range.clone(), chunk)?;
 
                 // 2: Read the matching arg
                 chunk.push_opcode(OpCode::ReadLocal, range.clone());
@@ -156,11 +169,12 @@ impl Compiler<'_> {
         Ok(())
     }
 
-    /// So far can only handle hard-coded print calls
+    /// Compiles a call, reusing the current call frame if the call is in tail position
     pub fn compile_call(
         &mut self,
         func: &ExprNode,
         args: &[ExprNode],
+        tail: bool,
         range: CodeRange,
         chunk: &mut Chunk,
     ) -> CompRes {
@@ -177,9 +191,22 @@ impl Compiler<'_> {
             self.compile_expression(arg, chunk)?;
         }
 
-        chunk.push_opcode(OpCode::Call, range);
+        let opcode = if tail { OpCode::TailCall } else { OpCode::Call };
+        chunk.push_opcode(opcode, range);
         chunk.push_u8_offset(args.len() as u8);
 
         Ok(())
     }
 }
+
+/// The name of a parameter whose argument can be used in place, without any pattern matching
+fn in_place_name(param: &LValue) -> Option<&String> {
+    match param {
+        LValue::Var(name) => Some(name),
+        LValue::Typed(lvalue, _) => match lvalue.as_ref() {
+            LValue::Var(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,159 @@
+// Module for evaluating pure operations on literals while compiling
+
+use std::rc::Rc;
+
+use parser::{AstNode, BinOper, Expr, ExprNode, UnOper};
+use semantic_analyzer::AstFolder;
+
+use crate::{
+    interpreter::{cmp_ops, logic_ops, num_ops},
+    value::Value,
+};
+
+/// Replaces pure operations on literals with their values, and constant branches with the taken one
+///
+/// The folded nodes keep the ids of the nodes they replace, so the analysis of the original
+/// code still applies. Operations which would fail, or overflow, are not folded so that they
+/// are reported as usual when running.
+pub struct ConstantFolder;
+
+impl AstFolder for ConstantFolder {
+    fn fold_expr(&mut self, expr: ExprNode) -> ExprNode {
+        let expr = self.fold_expr_delegation(expr);
+
+        // A constant condition always takes the same branch, so the other one can be skipped
+        if let Expr::If(cond, _, _) = expr.node.as_ref()
+            && let Some(Ok(truthy)) = literal_value(cond).map(|value| value.truthy())
+        {
+            return take_branch(expr, truthy);
+        }
+
+        let folded = match expr.node.as_ref() {
+            Expr::Binary(x, op, y) => literal_value(x)
+                .zip(literal_value(y))
+                .and_then(|(x, y)| fold_binary(x, op, y)),
+            Expr::Unary(op, x) => literal_value(x).and_then(|x| fold_unary(op, x)),
+            _ => None,
+        };
+        match folded.and_then(literal) {
+            Some(node) => AstNode {
+                node: Box::new(node),
+                ..expr
+            },
+            None => expr,
+        }
+    }
+}
+
+/// Replaces an if expression with the branch taken, or nil if there is no such branch
+fn take_branch(expr: ExprNode, truthy: bool) -> ExprNode {
+    let AstNode {
+        node,
+        start_loc,
+        end_loc,
+        id,
+    } = expr;
+    let Expr::If(_, then, otherwise) = *node else {
+        unreachable!("Only if expressions have branches to take")
+    };
+    match (truthy, otherwise) {
+        (true, _) => then,
+        (false, Some(otherwise)) => otherwise,
+        (false, None) => AstNode {
+            node: Box::new(Expr::Nil),
+            start_loc,
+            end_loc,
+            id,
+        },
+    }
+}
+
+/// The value of a literal expression
+fn literal_value(expr: &ExprNode) -> Option<Value> {
+    match expr.node.as_ref() {
+        Expr::Int(x) => Some(Value::Int(*x)),
+        Expr::Float(x) => Some(Value::Float(*x)),
+        Expr::Bool(x) => Some(Value::Bool(*x)),
+        Expr::String(string) => Some((string.as_ref() as &str).into()),
+        Expr::Nil => Some(Value::Nil),
+        _ => None,
+    }
+}
+
+/// The literal expression of a folded value
+fn literal(value: Value) -> Option<Expr> {
+    match value {
+        Value::Int(x) => Some(Expr::Int(x)),
+        Value::Float(x) => Some(Expr::Float(x)),
+        Value::Bool(x) => Some(Expr::Bool(x)),
+        Value::String(string) => Some(Expr::String(Rc::new(string.to_string()))),
+        Value::Nil => Some(Expr::Nil),
+        _ => None,
+    }
+}
+
+fn fold_binary(x: Value, op: &BinOper, y: Value) -> Option<Value> {
+    if panics(&x, op, &y) {
+        return None;
+    }
+
+    match op {
+        BinOper::Add => num_ops::add(x, y),
+        BinOper::Sub => num_ops::sub(x, y),
+        BinOper::Mult => num_ops::mult(x, y),
+        BinOper::Div => num_ops::div(x, y),
+        BinOper::Mod => num_ops::modulo(x, y),
+        BinOper::Pow => num_ops::power(x, y),
+        BinOper::Eq => cmp_ops::equal(x, y),
+        BinOper::Neq => cmp_ops::not_equal(x, y),
+        BinOper::Lt => cmp_ops::less(x, y),
+        BinOper::Leq => cmp_ops::less_eq(x, y),
+        BinOper::Gt => cmp_ops::greater(x, y),
+        BinOper::Geq => cmp_ops::greater_eq(x, y),
+        BinOper::Append => x.append(y),
+        BinOper::BitAnd => num_ops::bit_and(x, y),
+        BinOper::BitOr => num_ops::bit_or(x, y),
+        BinOper::BitXor => num_ops::bit_xor(x, y),
+        BinOper::LShift => num_ops::left_shift(x, y),
+        BinOper::RShift => num_ops::right_shift(x, y),
+        BinOper::In => y.contains(x).map(Value::from),
+        BinOper::NotIn => y.contains(x).map(|contained| (!contained).into()),
+    }
+    .ok()
+}
+
+fn fold_unary(op: &UnOper, x: Value) -> Option<Value> {
+    match (op, x) {
+        (UnOper::Sub, Value::Int(x)) => x.checked_neg().map(Value::Int),
+        (UnOper::Sub, x) => num_ops::negate(x).ok(),
+        (UnOper::Not, x) => logic_ops::not(x).ok(),
+        (UnOper::BitNot, x) => num_ops::bit_not(x).ok(),
+    }
+}
+
+/// Checks if an integer operation would overflow or divide by zero, which panics
+fn panics(x: &Value, op: &BinOper, y: &Value) -> bool {
+    let (Some(x), Some(y)) = (as_int(x), as_int(y)) else {
+        return false;
+    };
+
+    let result = match op {
+        BinOper::Add => x.checked_add(y),
+        BinOper::Sub => x.checked_sub(y),
+        BinOper::Mult => x.checked_mul(y),
+        BinOper::Div => x.checked_div(y),
+        BinOper::Mod => x.checked_rem_euclid(y),
+        BinOper::Pow if y >= 0 => u32::try_from(y).ok().and_then(|y| x.checked_pow(y)),
+        _ => Some(0),
+    };
+    result.is_none()
+}
+
+/// The integer value of ints and bools, as bools are promoted to ints in numerical operations
+fn as_int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(x) => Some(*x),
+        Value::Bool(x) => Some(*x as i64),
+        _ => None,
+    }
+}
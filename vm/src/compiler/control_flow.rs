@@ -1,77 +1,93 @@
-use super::{Chunk, CompRes, CompRetRes};
+use super::{Chunk, CompRetRes};
 
 /// Keeps track of point with exceptional flow points
 ///
 /// For example, loops and functions have exception-like behaviour with `break`,
 /// `continue` and `return`, which needs more than local information to compile.
-/// This keeps track of those currently unresolved points.
+/// This keeps track of those currently unresolved points, for every enclosing loop.
 pub struct FlowPoints {
-    points: Vec<FlowPoint>,
+    loops: Vec<LoopPoints>,
 }
 
-enum FlowPoint {
-    LoopEntry { pc: usize },
-    LoopExit { reserved: usize },
-    BreakExit { reserved: usize },
+/// The flow points of a single loop
+struct LoopPoints {
+    label: Option<String>,
+    entry: usize,
+    exit: Option<usize>,
+    breaks: Vec<usize>,
+
+    /// How many values the loop keeps on the stack while looping (like the iterator in for loops)
+    stack_values: usize,
 }
 
 impl FlowPoints {
     pub fn new() -> Self {
-        Self { points: vec![] }
+        Self { loops: vec![] }
     }
-    
-    pub fn push_loop_entry(&mut self, pc: usize) {
-        self.points.push(FlowPoint::LoopEntry { pc })
+
+    /// Enters a new loop, which starts at the pc
+    pub fn push_loop_entry(&mut self, pc: usize, label: Option<&String>, stack_values: usize) {
+        self.loops.push(LoopPoints {
+            label: label.cloned(),
+            entry: pc,
+            exit: None,
+            breaks: vec![],
+            stack_values,
+        })
     }
 
     pub fn push_loop_exit(&mut self, reserved: usize) {
-        self.points.push(FlowPoint::LoopExit { reserved })
+        let current = self
+            .loops
+            .last_mut()
+            .expect("Loop exit should have matching entry");
+        current.exit = Some(reserved);
     }
 
-    pub fn push_break_exit(&mut self, reserved: usize) -> CompRes {
+    /// Adds a break out of the loop with the given index
+    pub fn push_break_exit(&mut self, loop_index: usize, reserved: usize) {
+        self.loops[loop_index].breaks.push(reserved);
+    }
+
+    /// Finds the index of the loop a break or continue refers to
+    ///
+    /// Without a label it is the innermost loop.
+    pub fn find_loop(&self, label: Option<&String>, kind: &str) -> CompRetRes<usize> {
         // TODO: Better error check with functions
-        if !self.points.is_empty() {
-            self.points.push(FlowPoint::BreakExit { reserved });
-            Ok(())
-        } else {
-            Err(format!("Break encountered outside loop"))
+        match label {
+            None if self.loops.is_empty() => Err(format!("{kind} encountered outside loop")),
+            None => Ok(self.loops.len() - 1),
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|points| points.label.as_ref() == Some(label))
+                .ok_or_else(|| format!("Could not find a loop labeled '{label} for {kind}")),
         }
     }
 
-    /// Closes a loop at the top of the chunk, by updating reserved labels
-    pub fn close_loop(&mut self, chunk: &mut Chunk) -> CompRes {
-        loop {
-            let Some(flow_point) = self.points.pop()  else { 
-                return Err(format!("Could not close loop without an opening."))
-            };
+    /// The number of values kept on the stack by the loops nested inside the loop with the index
+    pub fn nested_stack_values(&self, loop_index: usize) -> usize {
+        self.loops[loop_index + 1..]
+            .iter()
+            .map(|points| points.stack_values)
+            .sum()
+    }
 
-            match flow_point {
-                FlowPoint::LoopEntry { pc:_ } => panic!("Loop entry encountered when closing loop"),
-                FlowPoint::LoopExit { reserved } => {
-                    chunk.patch_reserved_jump(reserved);
-                    break
-                },
-                FlowPoint::BreakExit { reserved } => {
-                    chunk.patch_reserved_jump(reserved)
-                },
-            }
-        }
+    /// Closes a loop at the top of the chunk, by updating the reserved loop exit label
+    ///
+    /// Returns the reserved jumps of all breaks, as they exit with a value on the stack.
+    pub fn close_loop(&mut self, chunk: &mut Chunk) -> CompRetRes<Vec<usize>> {
+        let Some(points) = self.loops.pop() else {
+            return Err(format!("Could not close loop without an opening."));
+        };
 
-        // Now that loop labels are updated, and loop closed, remove its entry
-        if let Some(FlowPoint::LoopEntry{pc:_}) = self.points.pop() {
-             Ok(())
-        } else {
-            panic!("Loop exit should have matching entry")
-        }
+        let exit = points.exit.expect("Loop entry should have matching exit");
+        chunk.patch_reserved_jump(exit);
+
+        Ok(points.breaks)
     }
 
-    pub fn get_loop_entry(&mut self) -> CompRetRes<usize> {
-        for flow_point in self.points.iter().rev() {
-            match flow_point {
-                FlowPoint::LoopEntry { pc } => return Ok(*pc),
-                FlowPoint::LoopExit { reserved:_ } | FlowPoint::BreakExit { reserved:_ } => (),
-            }
-        }
-        Err(format!("Cannot use continue outside of loops"))
+    pub fn get_loop_entry(&self, loop_index: usize) -> usize {
+        self.loops[loop_index].entry
     }
 }
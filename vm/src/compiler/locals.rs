@@ -43,6 +43,11 @@ impl LocalState {
         self.scope_depth == 0 && self.parent_locals.is_none()
     }
 
+    /// Is the compiler currently inside a function body?
+    pub fn in_function(&self) -> bool {
+        self.parent_locals.is_some()
+    }
+
     /// Returns the offset of the local variable from the rbp, as well as if it is a pointer
     pub fn get_local(&self, var: &str) -> Option<(u8, bool)> {
         for (ind, local) in self.locals.iter().enumerate().rev() {
@@ -69,6 +69,8 @@ pub fn disassemble_instruction<W: Write>(
             OpCode::Jump => jump_instruction("Jump", chunk, offset, out),
             OpCode::Discard => simple_instruction("Discard", out),
             OpCode::Call => offset_instruction("Call", chunk, offset, out),
+            OpCode::TailCall => offset_instruction("TailCall", chunk, offset, out),
+            OpCode::CallForwarding => offset_instruction("CallForwarding", chunk, offset, out),
             OpCode::AssignUpValue => offset_instruction("AssignUpValue", chunk, offset, out),
             OpCode::ReadUpValue => offset_instruction("ReadUpValue", chunk, offset, out),
             OpCode::InitClosure => closure_init(chunk, offset, out),
@@ -78,17 +80,35 @@ pub fn disassemble_instruction<W: Write>(
             OpCode::EmptyPointer => simple_instruction("EmptyPointer", out),
             OpCode::AssignAtIndex => simple_instruction("AssignAtIndex", out),
             OpCode::ReadAtIndex => simple_instruction("ReadAtIndex", out),
+            OpCode::SafeReadAtIndex => simple_instruction("SafeReadAtIndex", out),
             OpCode::ListFromSlice => simple_instruction("ListFromSlice", out),
             OpCode::ListFromValues => offset_instruction("ListFromValues", chunk, offset, out),
+            OpCode::DictFromValues => offset_instruction("DictFromValues", chunk, offset, out),
+            OpCode::SetFromValues => offset_instruction("SetFromValues", chunk, offset, out),
             OpCode::ReadAtSlice => simple_instruction("ReadAtSlice", out),
             OpCode::TopToIter => simple_instruction("TopToIter", out),
             OpCode::NextOrJump => jump_instruction("NextOrJump", chunk, offset, out),
             OpCode::Duplicate => simple_instruction("Duplicate", out),
             OpCode::Len => simple_instruction("Len", out),
+            OpCode::SafeLen => simple_instruction("SafeLen", out),
             OpCode::Swap => simple_instruction("Swap", out),
+            OpCode::DuplicateMany => offset_instruction("DuplicateMany", chunk, offset, out),
+            OpCode::Rotate => offset_instruction("Rotate", chunk, offset, out),
             OpCode::AssignSliceIndex => simple_instruction("AssignSliceIndex", out),
             OpCode::RaiseError => simple_instruction("RaiseError", out),
+            OpCode::IsVariant => offset_instruction("IsVariant", chunk, offset, out),
+            OpCode::VariantFields => simple_instruction("VariantFields", out),
+            OpCode::AssertType => offset_instruction("AssertType", chunk, offset, out),
             OpCode::Append => simple_instruction("Append", out),
+            OpCode::BitAnd => simple_instruction("BitAnd", out),
+            OpCode::BitOr => simple_instruction("BitOr", out),
+            OpCode::BitXor => simple_instruction("BitXor", out),
+            OpCode::LeftShift => simple_instruction("LeftShift", out),
+            OpCode::RightShift => simple_instruction("RightShift", out),
+            OpCode::BitNot => simple_instruction("BitNot", out),
+            OpCode::In => simple_instruction("In", out),
+            OpCode::NotIn => simple_instruction("NotIn", out),
+            OpCode::InRange => simple_instruction("InRange", out),
         }
     } else {
         simple_instruction("Invalid OpCode", out)
@@ -1,16 +1,18 @@
 mod call_frame;
 mod caller;
-mod cmp_ops;
-mod logic_ops;
-mod num_ops;
+pub(crate) mod cmp_ops;
+pub(crate) mod logic_ops;
+pub(crate) mod num_ops;
 
 use std::{mem, rc::Rc};
 
+use parser::Type;
+
 use crate::{
     compiler::{Chunk, OpCode},
     disassembler::disassemble_instruction,
     error::{RunRes, RunResTrait, RuntimeError},
-    value::{Closure, List, Value, ValuePointer},
+    value::{Closure, Dictionary, List, Value, ValuePointer},
 };
 
 use self::call_frame::CallFrame;
@@ -107,6 +109,14 @@ impl VM {
         match opcode {
             OpCode::Return => {
                 let ret_val = self.pop();
+                // Checked here instead of in the function, so that returned calls are tail calls
+                let returns = &self.frame().returns;
+                if let Some(annotated) = returns.iter().find(|&&ty| !ret_val.has_type(ty)) {
+                    return RuntimeError::error(format!(
+                        "Expected a value of type {annotated}, but got {}",
+                        ret_val.type_of()
+                    ));
+                }
                 if self.frame_count == 1 {
                     // self.pop(); // TODO: Book pushes a script func here, which we should push in case we also do
                     return Ok(InstrResult::Return(ret_val));
@@ -201,6 +211,52 @@ impl VM {
                 let x = self.pop();
                 self.push(x.append(y)?);
             }
+            OpCode::BitAnd => {
+                let y = self.pop();
+                let x = self.pop();
+                self.push(num_ops::bit_and(x, y)?);
+            }
+            OpCode::BitOr => {
+                let y = self.pop();
+                let x = self.pop();
+                self.push(num_ops::bit_or(x, y)?);
+            }
+            OpCode::BitXor => {
+                let y = self.pop();
+                let x = self.pop();
+                self.push(num_ops::bit_xor(x, y)?);
+            }
+            OpCode::LeftShift => {
+                let y = self.pop();
+                let x = self.pop();
+                self.push(num_ops::left_shift(x, y)?);
+            }
+            OpCode::RightShift => {
+                let y = self.pop();
+                let x = self.pop();
+                self.push(num_ops::right_shift(x, y)?);
+            }
+            OpCode::BitNot => {
+                let x = self.pop();
+                self.push(num_ops::bit_not(x)?);
+            }
+            OpCode::In => {
+                let collection = self.pop();
+                let x = self.pop();
+                self.push(collection.contains(x)?.into());
+            }
+            OpCode::NotIn => {
+                let collection = self.pop();
+                let x = self.pop();
+                self.push((!collection.contains(x)?).into());
+            }
+            OpCode::InRange => {
+                let step = self.pop().to_int_or_nil_none()?.unwrap_or(1);
+                let stop = self.pop().to_int()?;
+                let start = self.pop().to_int()?;
+                let x = self.pop();
+                self.push(List::slice_contains(start, stop, step, &x)?.into());
+            }
             OpCode::AssignGlobal => {
                 let offset = self.read_byte();
                 let x = self.pop();
@@ -260,6 +316,29 @@ impl VM {
                 let callee = self.stack[self.stack_top - arg_count - 1].clone();
                 self.call_value(callee, arg_count)?;
             }
+            OpCode::TailCall => {
+                let arg_count = self.read_byte() as usize;
+                let callee = self.stack[self.stack_top - arg_count - 1].clone();
+                if let Value::Closure(closure) = callee {
+                    self.tail_call_closure(closure, arg_count)?;
+                } else {
+                    // Natives finish directly, so just return their value
+                    self.call_value(callee, arg_count)?;
+                    return self.handle_opcode(OpCode::Return);
+                }
+            }
+            OpCode::CallForwarding => {
+                let given = self.read_byte() as usize;
+                let rbp = self.rbp();
+                let forwarded = self.frame().arg_count;
+                for offset in 1..=forwarded {
+                    self.push(self.stack[rbp + offset].clone());
+                }
+
+                let arg_count = given + forwarded;
+                let callee = self.stack[self.stack_top - arg_count - 1].clone();
+                self.call_value(callee, arg_count)?;
+            }
             OpCode::InitClosure => {
                 // Deserialize the constant function
                 let function = self
@@ -311,6 +390,12 @@ impl VM {
 
                 self.push(collection.read_at_index(index)?);
             }
+            OpCode::SafeReadAtIndex => {
+                let index = self.pop();
+                let collection = self.pop();
+
+                self.push(collection.safe_read_at_index(index)?.unwrap_or(NIL));
+            }
             OpCode::ListFromSlice => {
                 let step = self.pop().to_int_or_nil_none()?.unwrap_or(1);
                 let stop = self.pop().to_int()?;
@@ -323,6 +408,30 @@ impl VM {
                 vec.reverse(); // Needs to reverse the actual list, as reversing iter does not have an effect
                 self.push(List::from(vec).into())
             }
+            OpCode::DictFromValues => {
+                let len = self.read_byte();
+                let mut pairs = (0..len)
+                    .map(|_| {
+                        let value = self.pop();
+                        (self.pop(), value)
+                    })
+                    .collect::<Vec<(Value, Value)>>();
+                pairs.reverse(); // Later duplicate keys should override earlier ones
+
+                let dict = Dictionary::new();
+                for (key, value) in pairs {
+                    dict.insert(key, value)?;
+                }
+                self.push(dict.into())
+            }
+            OpCode::SetFromValues => {
+                let len = self.read_byte();
+                let set = Dictionary::new();
+                for _ in 0..len {
+                    set.insert(self.pop(), Value::Nil)?;
+                }
+                self.push(set.into())
+            }
             OpCode::ReadAtSlice => {
                 let step = self.pop().to_int_or_nil_none()?;
                 let stop = self.pop().to_int_or_nil_none()?;
@@ -372,9 +481,24 @@ impl VM {
                 let top = self.pop();
                 self.push((top.len()? as i64).into());
             }
+            OpCode::SafeLen => {
+                let top = self.pop();
+                self.push(top.len().map_or(NIL, |len| (len as i64).into()));
+            }
             OpCode::Swap => {
                 self.stack.swap(self.stack_top - 1, self.stack_top - 2);
             }
+            OpCode::DuplicateMany => {
+                let count = self.read_byte() as usize;
+                for _ in 0..count {
+                    let x = self.peek_many(count);
+                    self.push(x);
+                }
+            }
+            OpCode::Rotate => {
+                let depth = self.read_byte() as usize;
+                self.stack[self.stack_top - depth - 1..self.stack_top].rotate_right(1);
+            }
             OpCode::AssignSliceIndex => {
                 let slice_index = self.pop();
                 let index = self.peek();
@@ -390,6 +514,31 @@ impl VM {
                 let reason = self.pop();
                 return RuntimeError::error(reason.to_string());
             }
+            OpCode::IsVariant => {
+                let nbr_fields = self.read_byte() as usize;
+                let constructor = self.pop();
+                let x = self.pop();
+                self.push(x.is_variant_of(&constructor, nbr_fields)?.into());
+            }
+            OpCode::AssertType => {
+                let annotated = Type::ALL[self.read_byte() as usize];
+                let x = self.peek();
+                if !x.has_type(annotated) {
+                    return RuntimeError::error(format!(
+                        "Expected a value of type {annotated}, but got {}",
+                        x.type_of()
+                    ));
+                }
+            }
+            OpCode::VariantFields => match self.pop() {
+                Value::Variant(variant) => self.push(variant.fields_list().into()),
+                other => {
+                    return RuntimeError::error(format!(
+                        "Cannot destructure a {} as an enum variant",
+                        other.type_of()
+                    ))
+                }
+            },
         }
 
         Ok(InstrResult::Ok)
@@ -519,7 +668,10 @@ impl VM {
                 Value::Closure(closure) => closure.function().name().to_owned(),
                 _ => "script".to_owned(),
             };
-            trace.push_str(&format!("    ({ind}) [line {}] in {}\n", range.sl(), name))
+            trace.push_str(&format!("    ({ind}) [line {}] in {}\n", range.sl(), name));
+            if call_frame.tail_called {
+                trace.push_str("        (...tail calls...)\n");
+            }
         }
 
         trace
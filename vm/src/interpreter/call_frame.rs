@@ -1,5 +1,7 @@
 use std::rc::Rc;
 
+use parser::Type;
+
 use crate::compiler::Chunk;
 
 #[derive(Debug, Clone)]
@@ -12,6 +14,17 @@ pub struct CallFrame {
 
     /// The current program counter
     pub pc: usize,
+
+    /// Has this frame replaced other frames through tail calls?
+    pub tail_called: bool,
+
+    /// The number of arguments the function was called with
+    pub arg_count: usize,
+
+    /// The annotated return types which the returned value must have
+    ///
+    /// Frames replaced by tail calls return through this one, so their types are kept here.
+    pub returns: Vec<Type>,
 }
 
 impl CallFrame {
@@ -20,6 +33,9 @@ impl CallFrame {
             chunk,
             rbp: 0,
             pc: 0,
+            tail_called: false,
+            arg_count: 0,
+            returns: vec![],
         }
     }
 
@@ -28,9 +44,22 @@ impl CallFrame {
     }
 
     /// Initiates the call frame to be used
-    pub fn init(&mut self, chunk: Rc<Chunk>, rbp: usize) {
+    pub fn init(&mut self, chunk: Rc<Chunk>, rbp: usize, arg_count: usize, returns: Option<Type>) {
         self.chunk = chunk;
         self.rbp = rbp;
         self.pc = 0;
+        self.tail_called = false;
+        self.arg_count = arg_count;
+        self.returns.clear();
+        self.returns.extend(returns);
+    }
+
+    /// Keeps the return types of a frame replaced by a tail call, to check them at the return
+    pub fn inherit_returns(&mut self, returns: Vec<Type>) {
+        for annotated in returns {
+            if !self.returns.contains(&annotated) {
+                self.returns.push(annotated);
+            }
+        }
     }
 }
@@ -1,9 +1,11 @@
+use std::{mem, rc::Rc};
+
 use crate::{
     error::{RunRes, RunResTrait},
-    value::Value,
+    value::{Closure, Value},
 };
 
-use super::{FRAMES_SIZE, VM};
+use super::{FRAMES_SIZE, NIL, VM};
 
 impl VM {
     pub fn call_value(&mut self, callee: Value, arg_count: usize) -> RunRes<()> {
@@ -27,13 +29,7 @@ impl VM {
                 // Ok(())
             }
             Value::Closure(closure) => {
-                if !closure.function().validate_argcount(arg_count) {
-                    return RunRes::new_err(format!(
-                        "Tried to call function {} with {arg_count}, but expected {}",
-                        closure.function().name(),
-                        closure.function().arity()
-                    ));
-                }
+                validate_argcount(&closure, arg_count)?;
 
                 // Create the next call frame
                 // The closure and args should be pushed on the stack
@@ -41,10 +37,12 @@ impl VM {
 
                 // Change to it, and init
                 self.frame_count += 1;
-                self.frame_mut().init(closure.chunk_rc(), new_rbp);
+                let returns = closure.function().returns();
+                self.frame_mut()
+                    .init(closure.chunk_rc(), new_rbp, arg_count, returns);
 
                 // Increment the stack top to cover all eventual local variables
-                self.stack_top += closure.nbr_locals() - arg_count;
+                self.stack_top += closure.nbr_locals().saturating_sub(arg_count);
 
                 Ok(())
             }
@@ -61,7 +59,60 @@ impl VM {
 
                 Ok(())
             }
+            Value::Constructor(tag) => {
+                let fields = self.stack[(self.stack_top - arg_count)..self.stack_top].to_vec();
+                let variant = tag.construct(fields)?;
+
+                // Remove the fields and constructor from the stack, like for natives
+                self.stack_top -= arg_count + 1;
+                self.push(variant);
+
+                Ok(())
+            }
             _ => RunRes::new_err(format!("Can only call functions, not {}", callee.type_of())),
         }
     }
+
+    /// Calls a closure by replacing the current call frame with it
+    ///
+    /// The closure and args are moved down to the rbp, and the rest of the old frame is cleared.
+    pub fn tail_call_closure(&mut self, closure: Rc<Closure>, arg_count: usize) -> RunRes<()> {
+        validate_argcount(&closure, arg_count)?;
+
+        let rbp = self.rbp();
+        let callee_start = self.stack_top - 1 - arg_count;
+        for offset in 0..=arg_count {
+            self.stack[rbp + offset] = mem::replace(&mut self.stack[callee_start + offset], NIL);
+        }
+
+        // Must de-allocate the old frame to not keep pointers, just like when returning
+        while self.stack_top > rbp + 1 + arg_count {
+            self.pop();
+        }
+
+        // The replaced frame returns through the new one, so its return types must still hold
+        let outer_returns = mem::take(&mut self.frame_mut().returns);
+        let returns = closure.function().returns();
+        self.frame_mut()
+            .init(closure.chunk_rc(), rbp, arg_count, returns);
+        self.frame_mut().inherit_returns(outer_returns);
+        self.frame_mut().tail_called = true;
+
+        // Increment the stack top to cover all eventual local variables
+        self.stack_top += closure.nbr_locals().saturating_sub(arg_count);
+
+        Ok(())
+    }
+}
+
+fn validate_argcount(closure: &Closure, arg_count: usize) -> RunRes<()> {
+    if closure.function().validate_argcount(arg_count) {
+        Ok(())
+    } else {
+        RunRes::new_err(format!(
+            "Tried to call function {} with {arg_count}, but expected {}",
+            closure.function().name(),
+            closure.function().arity()
+        ))
+    }
 }
@@ -111,3 +111,56 @@ pub fn negate(x: Value) -> RunRes<Value> {
         otherwise => RunRes::new_err(format!("Cannot negate a {}", otherwise.type_of())),
     }
 }
+
+/// Promotes the values to integers, as bitwise operations are not defined on floats
+fn promote_bits(x: Value, y: Value) -> RunRes<(i64, i64)> {
+    match promote(x, y)? {
+        (Value::Int(x), Value::Int(y)) => Ok((x, y)),
+        (_, _) => RunRes::new_err("Bitwise operations are not defined for floats".to_string()),
+    }
+}
+
+pub fn bit_and(x: Value, y: Value) -> RunRes<Value> {
+    let (x, y) = promote_bits(x, y)?;
+    Ok(Value::Int(x & y))
+}
+
+pub fn bit_or(x: Value, y: Value) -> RunRes<Value> {
+    let (x, y) = promote_bits(x, y)?;
+    Ok(Value::Int(x | y))
+}
+
+pub fn bit_xor(x: Value, y: Value) -> RunRes<Value> {
+    let (x, y) = promote_bits(x, y)?;
+    Ok(Value::Int(x ^ y))
+}
+
+pub fn left_shift(x: Value, y: Value) -> RunRes<Value> {
+    let (x, shift) = promote_bits(x, y)?;
+    u32::try_from(shift)
+        .ok()
+        .and_then(|shift| x.checked_shl(shift))
+        .map(Value::Int)
+        .ok_or_else(|| RuntimeError::bare_error(format!("Cannot shift by {shift} bits")))
+}
+
+pub fn right_shift(x: Value, y: Value) -> RunRes<Value> {
+    let (x, shift) = promote_bits(x, y)?;
+    u32::try_from(shift)
+        .ok()
+        .and_then(|shift| x.checked_shr(shift))
+        .map(Value::Int)
+        .ok_or_else(|| RuntimeError::bare_error(format!("Cannot shift by {shift} bits")))
+}
+
+pub fn bit_not(x: Value) -> RunRes<Value> {
+    match x {
+        Value::Bool(x) => Ok(Value::Int(!(x as i64))),
+        Value::Int(x) => Ok(Value::Int(!x)),
+        Value::Pointer(_) => panic!("We should never operate on value pointers"),
+        otherwise => RunRes::new_err(format!(
+            "Cannot bitwise negate a {}",
+            otherwise.type_of()
+        )),
+    }
+}
@@ -9,14 +9,18 @@ pub mod value;
 
 use std::rc::Rc;
 
-use compiler::compile;
+use compiler::{compile_with_options, CompileOptions};
 use interpreter::interpret;
 use semantic_analyzer::AttributedAst;
 
 const DEBUG: bool = false;
 
 pub fn interpret_once(ast: &AttributedAst) -> i32 {
-    let Some(chunk) = compile(ast) else {
+    interpret_once_with_options(ast, CompileOptions::default())
+}
+
+pub fn interpret_once_with_options(ast: &AttributedAst, options: CompileOptions) -> i32 {
+    let Some(chunk) = compile_with_options(ast, options) else {
         return 65; // ? Which error to use? Should we send back trace?
     };
 
@@ -4,6 +4,8 @@ use std::{
     rc::Rc,
 };
 
+use parser::Type;
+
 use crate::error::{RunRes, RunResTrait, RuntimeError};
 
 mod builtins;
@@ -14,16 +16,18 @@ mod list;
 mod priority_queue;
 mod string;
 mod value_pointer;
+mod variant;
 
 pub use self::priority_queue::PriorityQueue;
-pub use builtins::get_natives;
+pub use builtins::{get_natives, Native};
 pub use closure::Closure;
 pub use dictionary::Dictionary;
 pub use function::Function;
 pub use list::List;
 pub use value_pointer::ValuePointer;
+pub use variant::{Variant, VariantTag};
 
-use self::{builtins::Native, string::ValueString};
+use self::string::ValueString;
 
 // OPT: Pack as bytesting instead? Very inefficiently stored now in 128 bits
 #[derive(Clone)]
@@ -50,6 +54,12 @@ pub enum Value {
 
     /// A priority queue
     PriorityQueue(Rc<PriorityQueue>),
+
+    /// A value of an enum variant, with its fields
+    Variant(Rc<Variant>),
+
+    /// The constructor of an enum variant, called to create values of it
+    Constructor(Rc<VariantTag>),
 }
 
 pub enum ValueType {
@@ -64,6 +74,7 @@ pub enum ValueType {
     String,
     Dictionary,
     PriorityQueue,
+    Variant,
 }
 
 /// Impl for delegating tasks between function types and implementing easy queries
@@ -82,6 +93,8 @@ impl Value {
             Value::String(_) => ValueType::String,
             Value::Dictionary(_) => ValueType::Dictionary,
             Value::PriorityQueue(_) => ValueType::PriorityQueue,
+            Value::Variant(_) => ValueType::Variant,
+            Value::Constructor(_) => ValueType::Builtin,
         }
     }
 
@@ -106,6 +119,14 @@ impl Value {
             Value::String(string) => Ok(string.truthy()),
             Value::Dictionary(dict) => Ok(dict.truthy()),
             Value::PriorityQueue(prioq) => Ok(prioq.truthy()),
+            Value::Variant(variant) => RuntimeError::error(format!(
+                "An enum variant does not have a truthiness ({})",
+                variant.tag().name()
+            )),
+            Value::Constructor(tag) => RuntimeError::error(format!(
+                "Constructors don't have a truthiness ({})",
+                tag.name()
+            )),
         }
     }
 
@@ -164,6 +185,8 @@ impl Value {
             | Value::Function(_)
             | Value::Closure(_)
             | Value::PriorityQueue(_)
+            | Value::Variant(_)
+            | Value::Constructor(_)
             | Value::Native(_) => RunRes::new_err(format!("Cannot iterate over {}", typ)),
         }
     }
@@ -183,6 +206,8 @@ impl Value {
             | Value::Function(_)
             | Value::Closure(_)
             | Value::PriorityQueue(_)
+            | Value::Variant(_)
+            | Value::Constructor(_)
             | Value::Native(_) => RunRes::new_err(format!("Cannot iterate over {}", typ)),
         }
     }
@@ -213,11 +238,27 @@ impl Value {
         }
     }
 
-    /// Tries to read at an index of the value
+    /// Tries to read at an index of the value, giving None if the index or key is missing
+    ///
+    /// Negative indices count from the end, as when indexing normally.
     pub fn safe_read_at_index(&self, index: Value) -> RunRes<Option<Value>> {
         match self {
-            Value::List(list) => Ok(list.get(index.to_int()?).ok()),
-            Value::String(string) => Ok(string.get(index.to_int()?).ok()),
+            Value::List(list) => {
+                let index = index.to_int()?;
+                if in_bounds(index, list.len()) {
+                    list.get(index).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+            Value::String(string) => {
+                let index = index.to_int()?;
+                if in_bounds(index, string.len()) {
+                    string.get(index).map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
             Value::Dictionary(dict) => Ok(dict.get(index.clone())?),
             otherwise => RunRes::new_err(format!("Cannot index into a {}", otherwise.type_of())),
         }
@@ -273,6 +314,66 @@ impl Value {
         }
     }
 
+    /// Checks if the value is in this collection, as in `value in self`
+    ///
+    /// Strings check for substrings, and dictionaries for keys.
+    pub fn contains(&self, value: Value) -> RunRes<bool> {
+        match self {
+            Value::List(list) => Ok(list.contains(&value)),
+            Value::String(string) => string.contains_subsequence(value),
+            Value::Dictionary(dict) => Ok(dict.contains_key(value)),
+            otherwise => RunRes::new_err(format!(
+                "Cannot use 'in' on value of type {}",
+                otherwise.type_of()
+            )),
+        }
+    }
+
+    /// Checks if the value is a variant created by the constructor, as in a match pattern
+    ///
+    /// The pattern must have as many fields as the variant, to catch mistakes in it.
+    /// Variants without fields are values rather than constructors, and are matched by tag.
+    pub fn is_variant_of(&self, constructor: &Value, nbr_fields: usize) -> RunRes<bool> {
+        let tag = match constructor {
+            Value::Constructor(tag) => tag.as_ref(),
+            Value::Variant(unit) if unit.fields().is_empty() => unit.tag(),
+            _ => {
+                return RunRes::new_err(format!(
+                    "Can only match against enum variants, not {}",
+                    constructor.type_of()
+                ))
+            }
+        };
+        if tag.arity() != nbr_fields {
+            return RunRes::new_err(format!(
+                "The variant {} has {} fields, but the pattern has {nbr_fields}",
+                tag.name(),
+                tag.arity()
+            ));
+        }
+
+        Ok(matches!(self, Value::Variant(variant) if variant.tag() == tag))
+    }
+
+    /// Checks if the value has the annotated type, where ints can also be used as floats
+    pub fn has_type(&self, annotated: Type) -> bool {
+        match (self, annotated) {
+            (Value::Pointer(pointer), _) => pointer.get_clone().has_type(annotated),
+            (Value::Int(_), Type::Int | Type::Float)
+            | (Value::Float(_), Type::Float)
+            | (Value::Bool(_), Type::Bool)
+            | (Value::String(_), Type::Str)
+            | (Value::List(_), Type::List)
+            | (Value::Dictionary(_), Type::Dict)
+            | (Value::Nil, Type::Nil) => true,
+            (
+                Value::Function(_) | Value::Native(_) | Value::Closure(_) | Value::Constructor(_),
+                Type::Fn,
+            ) => true,
+            _ => false,
+        }
+    }
+
     /// Gets the length of a value, returning error if it does not have a length
     pub fn len(&self) -> RunRes<usize> {
         match self {
@@ -287,6 +388,8 @@ impl Value {
             | Value::Float(_)
             | Value::Function(_)
             | Value::Closure(_)
+            | Value::Variant(_)
+            | Value::Constructor(_)
             | Value::Native(_) => {
                 RunRes::new_err(format!("Cannot get the length of a {}", self.type_of()))
             }
@@ -313,6 +416,8 @@ impl Value {
             | Value::Dictionary(_)
             | Value::Pointer(_)
             | Value::PriorityQueue(_)
+            | Value::Variant(_)
+            | Value::Constructor(_)
             | Value::List(_) => {
                 RunRes::new_err(format!("Cannot convert {} to char", self.type_of()))
             }
@@ -347,6 +452,8 @@ impl Value {
             Value::String(string) => string.as_ref().clone().into(),
             Value::Dictionary(dict) => dict.deepclone().into(),
             Value::PriorityQueue(prioq) => prioq.deepclone().into(),
+            Value::Variant(variant) => Value::Variant(Rc::new(variant.deepclone())),
+            Value::Constructor(_) => self.clone(),
         }
     }
 
@@ -367,6 +474,9 @@ impl Value {
             Value::String(string) => string.as_ref().clone().into(),
             Value::Dictionary(dict) => dict.shallowclone().into(),
             Value::PriorityQueue(prioq) => prioq.as_ref().clone().into(),
+            // Variants are immutable, so sharing the fields is fine
+            Value::Variant(_) => self.clone(),
+            Value::Constructor(_) => self.clone(),
         }
     }
 
@@ -393,7 +503,15 @@ impl Value {
             }
             Value::Pointer(p) => p.borrow_value().try_hash(state),
             Value::String(s) => Ok(s.hash(state)),
+            Value::Variant(variant) => {
+                variant.tag().name().hash(state);
+                for field in variant.fields() {
+                    field.try_hash(state)?;
+                }
+                Ok(())
+            }
             Value::PriorityQueue(_)
+            | Value::Constructor(_)
             | Value::Dictionary(_)
             | Value::Function(_)
             | Value::Closure(_)
@@ -404,6 +522,11 @@ impl Value {
     }
 }
 
+/// If an index is within a collection of the given length, counting negative indices from the end
+fn in_bounds(index: i64, len: usize) -> bool {
+    -(len as i64) <= index && index < len as i64
+}
+
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         // TODO: Implement other values
@@ -438,6 +561,8 @@ impl PartialEq for Value {
             (Value::String(x), Value::String(y)) => x.eq(y),
             (Value::List(x), Value::List(y)) => x.eq(y),
             (Value::Dictionary(x), Value::Dictionary(y)) => x.eq(y),
+            (Value::Variant(x), Value::Variant(y)) => x.eq(y),
+            (Value::Constructor(x), Value::Constructor(y)) => x.eq(y),
             (Value::Pointer(pointer), other) => pointer.get_clone().eq(other),
             (other, Value::Pointer(pointer)) => other.eq(&pointer.get_clone()),
             _ => false, // All other combinations are not equal
@@ -462,6 +587,7 @@ impl Display for ValueType {
             ValueType::String => write!(f, "String"),
             ValueType::Dictionary => write!(f, "Dictionary"),
             ValueType::PriorityQueue => write!(f, "PriorityQueue"),
+            ValueType::Variant => write!(f, "Variant"),
         }
     }
 }
@@ -565,6 +691,8 @@ impl Display for Value {
             Value::String(string) => write!(f, "{}", string),
             Value::Dictionary(dict) => write!(f, "{}", dict),
             Value::PriorityQueue(prioq) => write!(f, "{}", prioq),
+            Value::Variant(variant) => write!(f, "{}", variant),
+            Value::Constructor(tag) => write!(f, "fn {}/{}", tag.name(), tag.arity()),
         }
     }
 }
@@ -584,6 +712,8 @@ impl Debug for Value {
             Value::String(value) => write!(f, "String({value})"),
             Value::Dictionary(dict) => write!(f, "{:?}", dict),
             Value::PriorityQueue(prioq) => write!(f, "{:?}", prioq),
+            Value::Variant(variant) => write!(f, "Variant({:?})", variant),
+            Value::Constructor(tag) => write!(f, "Constructor({})", tag.name()),
         }
     }
 }
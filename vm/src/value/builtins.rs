@@ -9,6 +9,7 @@ use self::natives::get_builtins;
 
 use super::Value;
 
+mod combinators;
 mod natives;
 mod templates;
 
@@ -31,6 +32,10 @@ impl Native {
         }
     }
 
+    pub fn accept_arity(&self, arity: usize) -> bool {
+        self.builtin.accept_arity(arity)
+    }
+
     pub fn name(&self) -> &str {
         self.builtin.name()
     }
@@ -0,0 +1,163 @@
+use std::rc::Rc;
+
+use parser::CodeRange;
+
+use crate::{
+    compiler::{Chunk, OpCode},
+    error::{RunRes, RunResTrait},
+    value::{Closure, Function, Value, ValuePointer},
+};
+
+/// Composes two functions into a closure calling `second(first(args...))`
+///
+/// The closure takes the same arguments as `first`.
+pub fn compose(first: Value, second: Value) -> RunRes<Value> {
+    let arity = callable_arity("compose", &first, 1)?;
+    callable_arity("compose", &second, 1)?;
+
+    let name = format!("compose({}, {})", callable_name(&first), callable_name(&second));
+    let range = native_range();
+    let mut chunk = Chunk::new();
+
+    // Upvalue 1 is the outer function, and upvalue 0 the inner one
+    push_upvalue_read(1, &range, &mut chunk);
+    push_upvalue_read(0, &range, &mut chunk);
+    match arity {
+        Arity::Fixed(arity) => {
+            push_param_reads(arity, &range, &mut chunk);
+            chunk.push_opcode(OpCode::Call, range.clone());
+            chunk.push_u8_offset(arity);
+        }
+        Arity::Variadic(_) => {
+            chunk.push_opcode(OpCode::CallForwarding, range.clone());
+            chunk.push_u8_offset(0);
+        }
+    }
+    chunk.push_opcode(OpCode::TailCall, range.clone());
+    chunk.push_u8_offset(1);
+    chunk.push_opcode(OpCode::Return, range);
+
+    Ok(new_closure(arity, name, chunk, vec![first, second]))
+}
+
+/// Binds the first arguments of a function, returning a closure taking the rest of them
+pub fn partial(mut args: Vec<Value>) -> RunRes<Value> {
+    let func = args.remove(0);
+    let nbr_bound = args.len();
+    let arity = callable_arity("partial", &func, nbr_bound)?.without(nbr_bound as u8);
+
+    let name = format!("partial({}, {nbr_bound} args)", callable_name(&func));
+    let range = native_range();
+    let mut chunk = Chunk::new();
+
+    // Upvalue 0 is the function, followed by the bound arguments
+    for index in 0..=nbr_bound {
+        push_upvalue_read(index as u8, &range, &mut chunk);
+    }
+    match arity {
+        Arity::Fixed(arity) => {
+            push_param_reads(arity, &range, &mut chunk);
+            chunk.push_opcode(OpCode::TailCall, range.clone());
+            chunk.push_u8_offset(nbr_bound as u8 + arity);
+        }
+        Arity::Variadic(_) => {
+            // Natives finish directly, so there is no frame to reuse with a tail call
+            chunk.push_opcode(OpCode::CallForwarding, range.clone());
+            chunk.push_u8_offset(nbr_bound as u8);
+        }
+    }
+    chunk.push_opcode(OpCode::Return, range);
+
+    let mut captured = vec![func];
+    captured.extend(args);
+    Ok(new_closure(arity, name, chunk, captured))
+}
+
+/// How many arguments a created closure takes, and passes on to the callable
+#[derive(Clone, Copy)]
+enum Arity {
+    Fixed(u8),
+
+    /// At least this many arguments, as natives can accept several arities
+    Variadic(u8),
+}
+
+impl Arity {
+    /// The arity left after binding some arguments
+    fn without(self, bound: u8) -> Self {
+        match self {
+            Arity::Fixed(arity) => Arity::Fixed(arity - bound),
+            Arity::Variadic(arity) => Arity::Variadic(arity - bound),
+        }
+    }
+}
+
+/// Finds how many arguments a created closure should pass on to the callable
+///
+/// Closures have a fixed arity, while natives are treated as variadic from the smallest
+/// accepted arity which is at least `min`. The natives then check the exact argument count.
+fn callable_arity(caller: &str, callable: &Value, min: usize) -> RunRes<Arity> {
+    let arity = match callable {
+        Value::Closure(closure) => {
+            Some(closure.function().arity() as usize).filter(|&arity| arity >= min)
+        }
+        Value::Native(native) => (min..u8::MAX as usize).find(|&arity| native.accept_arity(arity)),
+        other => {
+            return RunRes::new_err(format!(
+                "Can only pass functions to {caller}, but got {}",
+                other.type_of()
+            ))
+        }
+    };
+
+    match (arity, callable) {
+        (Some(arity), Value::Native(_)) => Ok(Arity::Variadic(arity as u8)),
+        (Some(arity), _) => Ok(Arity::Fixed(arity as u8)),
+        (None, _) => RunRes::new_err(format!(
+            "The function {} given to {caller} must accept at least {min} arguments",
+            callable_name(callable)
+        )),
+    }
+}
+
+fn callable_name(callable: &Value) -> &str {
+    match callable {
+        Value::Closure(closure) => closure.function().name(),
+        Value::Native(native) => native.name(),
+        _ => "?",
+    }
+}
+
+fn push_upvalue_read(index: u8, range: &CodeRange, chunk: &mut Chunk) {
+    chunk.push_opcode(OpCode::ReadUpValue, range.clone());
+    chunk.push_u8_offset(index);
+}
+
+/// The parameters are placed right after the closure itself in the call frame
+fn push_param_reads(arity: u8, range: &CodeRange, chunk: &mut Chunk) {
+    for offset in 1..=arity {
+        chunk.push_opcode(OpCode::ReadLocal, range.clone());
+        chunk.push_u8_offset(offset);
+    }
+}
+
+/// Natives have no location in the source code
+fn native_range() -> CodeRange {
+    CodeRange::from_ints(0, 0, 0, 0, 0, 0)
+}
+
+fn new_closure(arity: Arity, name: String, chunk: Chunk, captured: Vec<Value>) -> Value {
+    let function = match arity {
+        Arity::Fixed(arity) => Function::new(arity, arity as usize, name, chunk),
+        Arity::Variadic(arity) => Function::new_variadic(arity, name, chunk),
+    };
+    let upvalues = captured
+        .into_iter()
+        .map(|value| {
+            let pointer = ValuePointer::new();
+            pointer.set(value);
+            pointer
+        })
+        .collect();
+    Closure::new(Rc::new(function), upvalues).into()
+}
@@ -3,6 +3,7 @@ use crate::error::{RunRes, RunResTrait, RuntimeError};
 use crate::value::string::ValueString;
 use crate::value::{Dictionary, List, PriorityQueue, Value};
 
+use super::combinators;
 use super::templates::BuiltinTemplate;
 use super::Builtin;
 use std::rc::Rc;
@@ -72,6 +73,8 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
                 | Value::Native(_)
                 | Value::List(_)
                 | Value::PriorityQueue(_)
+                | Value::Variant(_)
+                | Value::Constructor(_)
                 | Value::Dictionary(_) => RunRes::new_err(format!("Cannot convert {kind} to int")),
             }
         }
@@ -94,6 +97,8 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
                 | Value::Native(_)
                 | Value::List(_)
                 | Value::PriorityQueue(_)
+                | Value::Variant(_)
+                | Value::Constructor(_)
                 | Value::Dictionary(_) => {
                     RunRes::new_err(format!("Cannot convert {kind} to float"))
                 }
@@ -224,19 +229,9 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         Ok(d1.union(d2.as_ref()).into())
     });
 
-    builtins.new_2arg(
-        "in",
-        "in(value, collection)",
-        |value, collection| match collection {
-            Value::List(list) => Ok(list.contains(&value).into()),
-            Value::String(string) => string.contains_subsequence(value).map(|b| b.into()),
-            Value::Dictionary(dict) => Ok(dict.contains_key(value).into()),
-            otherwise => RunRes::new_err(format!(
-                "Cannot use 'in' on value of type {}",
-                otherwise.type_of()
-            )),
-        },
-    );
+    builtins.new_2arg("in", "in(value, collection)", |value, collection| {
+        collection.contains(value).map(|b| b.into())
+    });
 
     builtins.new_3arg(
         "push_pq",
@@ -268,6 +263,15 @@ pub fn get_builtins() -> Vec<Rc<dyn Builtin>> {
         Ok(args.get(0).cloned().unwrap_or(Value::Nil))
     });
 
+    builtins.new_2arg("compose", "compose(first, second)", combinators::compose);
+
+    builtins.new_any_arg("partial", "partial(func, args...)", |args| {
+        if args.is_empty() {
+            return RunRes::new_err("Must pass a function to 'partial'".to_owned());
+        }
+        combinators::partial(args)
+    });
+
     builtins.new_any_arg("zip", "zip(colls...)", |args| {
         let colls = args
             .into_iter()
@@ -244,7 +244,8 @@ impl TryFrom<Value> for KeyValue {
             | Value::Float(_)
             | Value::Pointer(_)
             | Value::List(_)
-            | Value::String(_) => Ok(KeyValue(value.deepclone())),
+            | Value::String(_)
+            | Value::Variant(_) => Ok(KeyValue(value.deepclone())),
             otherwise => Err(format!(
                 "Cannot use {} as a key to a dictionary",
                 otherwise.type_of()
@@ -282,6 +283,8 @@ fn valid_key(value: &Value, depth: usize) -> bool {
         Value::String(_) => true,
         Value::Dictionary(_) => false,
         Value::PriorityQueue(_) => false,
+        Value::Variant(v) => v.fields().iter().all(|v| valid_key(v, depth + 1)),
+        Value::Constructor(_) => false,
     }
 }
 
@@ -1,5 +1,7 @@
 use std::rc::Rc;
 
+use parser::Type;
+
 use crate::compiler::Chunk;
 
 #[derive(Debug)]
@@ -10,6 +12,12 @@ pub struct Function {
 
     /// The largest number of locals to use at any time (including parameters)
     nbr_locals: usize,
+
+    /// If the function accepts any number of arguments above its arity
+    variadic: bool,
+
+    /// The annotated return type, which the returned values are checked against
+    returns: Option<Type>,
 }
 
 impl Function {
@@ -19,9 +27,24 @@ impl Function {
             chunk: Rc::new(chunk),
             name,
             nbr_locals: locals,
+            variadic: false,
+            returns: None,
+        }
+    }
+
+    /// Creates a function taking at least `arity` arguments
+    pub fn new_variadic(arity: u8, name: String, chunk: Chunk) -> Self {
+        Self {
+            variadic: true,
+            ..Self::new(arity, arity as usize, name, chunk)
         }
     }
 
+    /// Sets the annotated return type of the function
+    pub fn with_returns(self, returns: Option<Type>) -> Self {
+        Self { returns, ..self }
+    }
+
     pub fn arity(&self) -> u8 {
         self.arity
     }
@@ -30,13 +53,21 @@ impl Function {
         if count >= u8::MAX as usize {
             return false;
         }
-        self.arity == count as u8
+        if self.variadic {
+            count >= self.arity as usize
+        } else {
+            self.arity == count as u8
+        }
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn returns(&self) -> Option<Type> {
+        self.returns
+    }
+
     pub fn nbr_locals(&self) -> usize {
         self.nbr_locals
     }
@@ -97,6 +97,27 @@ impl List {
         Ok(vec.into())
     }
 
+    /// Checks if the list from `from_slice` would contain the value, without constructing it
+    pub fn slice_contains(start: i64, stop: i64, step: i64, value: &Value) -> RunRes<bool> {
+        if empty_solo_slice(start, stop, step) {
+            return Ok(false);
+        } else if step == 0 {
+            return RunRes::new_err("Cannot have stepsize 0 in slice".to_owned());
+        }
+
+        // Only ints are equal to the ints in the list
+        let Value::Int(int) = *value else {
+            return Ok(false);
+        };
+
+        let within = if step > 0 {
+            start <= int && int < stop
+        } else {
+            stop < int && int <= start
+        };
+        Ok(within && int.abs_diff(start) % step.unsigned_abs() == 0)
+    }
+
     /// Constructs a new list, from a slice of this one
     pub fn slice(&self, start: Option<i64>, stop: Option<i64>, step: Option<i64>) -> RunRes<Self> {
         let step = step.unwrap_or(1);
@@ -0,0 +1,128 @@
+use std::{
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use crate::error::{RunRes, RunResTrait};
+
+use super::{List, Value};
+
+/// Identifies one variant of an enum, and constructs values of it when called
+#[derive(Debug)]
+pub struct VariantTag {
+    enum_name: String,
+    name: String,
+    arity: usize,
+}
+
+/// A tagged value, created by calling the constructor of an enum variant
+pub struct Variant {
+    tag: Rc<VariantTag>,
+    fields: Vec<Value>,
+}
+
+impl VariantTag {
+    pub fn new(enum_name: String, name: String, arity: usize) -> Self {
+        Self {
+            enum_name,
+            name,
+            arity,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Creates the value of a variant without fields, which is used instead of a constructor
+    pub fn unit(self: &Rc<Self>) -> Value {
+        Value::Variant(Rc::new(Variant {
+            tag: self.clone(),
+            fields: vec![],
+        }))
+    }
+
+    /// Creates a variant with this tag from the given field values
+    pub fn construct(self: &Rc<Self>, fields: Vec<Value>) -> RunRes<Value> {
+        if fields.len() != self.arity {
+            return RunRes::new_err(format!(
+                "Tried to construct {} with {} values, but expected {}",
+                self.name,
+                fields.len(),
+                self.arity
+            ));
+        }
+
+        Ok(Value::Variant(Rc::new(Variant {
+            tag: self.clone(),
+            fields,
+        })))
+    }
+}
+
+/// Tags are the same if they are the same variant of the same enum, even if declared twice
+impl PartialEq for VariantTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.enum_name == other.enum_name
+    }
+}
+
+impl Variant {
+    pub fn tag(&self) -> &VariantTag {
+        &self.tag
+    }
+
+    pub fn fields(&self) -> &[Value] {
+        &self.fields
+    }
+
+    /// Collects the fields into a list, so they can be destructured as a tuple
+    pub fn fields_list(&self) -> List {
+        self.fields.clone().into()
+    }
+
+    pub fn deepclone(&self) -> Self {
+        Self {
+            tag: self.tag.clone(),
+            fields: self.fields.iter().map(Value::deepclone).collect(),
+        }
+    }
+}
+
+impl PartialEq for Variant {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.fields == other.fields
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tag.name)?;
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, "(")?;
+        for (ind, field) in self.fields.iter().enumerate() {
+            if ind != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{field}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Debug for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}::{}{:?}",
+            self.tag.enum_name, self.tag.name, self.fields
+        )
+    }
+}
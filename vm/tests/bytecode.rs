@@ -1,7 +1,7 @@
 use parser::CodeRange;
 use std::io::Cursor;
 
-use vm::compiler::{Chunk, OpCode};
+use vm::compiler::{compile_with_options, Chunk, CompileOptions, OpCode};
 use vm::disassembler;
 
 fn new_out() -> Cursor<Vec<u8>> {
@@ -12,6 +12,19 @@ fn out_to_string(out: Cursor<Vec<u8>>) -> String {
     String::from_utf8(out.into_inner()).unwrap()
 }
 
+fn disassemble_code(code: &str, optimize: bool) -> String {
+    let stmts = parser::parse("test", code).unwrap();
+    let ast = semantic_analyzer::analyze_ast(&stmts);
+    let options = CompileOptions {
+        optimize,
+        ..Default::default()
+    };
+    let chunk = compile_with_options(&ast, options).unwrap();
+    let mut out = new_out();
+    disassembler::disassemble_chunk(&chunk, "test", &mut out).unwrap();
+    out_to_string(out)
+}
+
 #[test]
 fn disassemble_hello_world() {
     let mut chunk = Chunk::new();
@@ -24,3 +37,25 @@ fn disassemble_hello_world() {
         "== simple test ==\n0000   0:0  -  0:5   Return\n0001   0:6  -  0:11  Return\n"
     );
 }
+
+#[test]
+fn constant_folding() {
+    let code = "x := 2 ^ 10 - 1; y := \"a\" ++ \"b\"; z := if false x else y; w := 1 / 0;\n\
+                v := if 1 + 1 == 2 { \"two\" };";
+
+    let folded = disassemble_code(code, true);
+    assert!(!folded.contains("Power"));
+    assert!(!folded.contains("Append"));
+    assert!(!folded.contains("JumpIfFalse"));
+    assert!(folded.contains("Int(1023)"));
+    assert!(!folded.contains("Equality"));
+
+    // Operations which would fail are left to be reported when running
+    assert!(folded.contains("Divide"));
+
+    let unoptimized = disassemble_code(code, false);
+    assert!(unoptimized.contains("Power"));
+    assert!(unoptimized.contains("Append"));
+    assert!(unoptimized.contains("JumpIfFalse"));
+    assert!(unoptimized.contains("Equality"));
+}